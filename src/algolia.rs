@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::StoryType;
+use crate::error::HNError;
+use crate::item::{Comment, Item, Story};
+use crate::policy::RequestPolicy;
+use crate::query::{Filter, Query};
+
+pub const ALGOLIA_API_BASE: &str = "https://hn.algolia.com/api/v1";
+
+// Ordering of Algolia results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlgoliaSort {
+    #[default]
+    Relevance, // the `search` endpoint
+    Date, // the `search_by_date` endpoint, newest first
+}
+
+// A single request to the Algolia search API
+#[derive(Debug, Clone, Default)]
+pub struct AlgoliaQuery {
+    pub query: String,
+    pub optional_words: Vec<String>, // words of `query` a hit may lack, as long as it has one of them
+    pub sort: AlgoliaSort,
+    pub tags: Vec<String>,            // AND'ed together, e.g. "story", "author_pg"
+    pub numeric_filters: Vec<String>, // e.g. "points>=100", "created_at_i>1700000000"
+    pub page: u32,                    // zero-based
+    pub hits_per_page: u32,
+}
+
+// One page of search results
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlgoliaResponse {
+    pub hits: Vec<AlgoliaHit>,
+    pub nb_hits: u64,
+    pub page: u32,
+    pub nb_pages: u32,
+    pub hits_per_page: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlgoliaHit {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub author: Option<String>,
+    pub points: Option<i32>,
+    pub story_text: Option<String>,
+    pub comment_text: Option<String>,
+    pub parent_id: Option<u32>,
+    pub num_comments: Option<i32>,
+    pub created_at_i: i64,
+    #[serde(rename = "_tags", default)]
+    pub tags: Vec<String>,
+}
+
+impl AlgoliaHit {
+    // Map the hit into the same `Story` type the Firebase API produces.
+    // Algolia does not return child IDs, so `kids` is left empty.
+    pub fn into_story(self) -> Result<Story, HNError> {
+        Ok(Story {
+            id: self.id()?,
+            deleted: false,
+            dead: false,
+            title: self.title,
+            url: self.url,
+            text: self.story_text,
+            by: self.author,
+            score: self.points,
+            descendants: self.num_comments,
+            time: self.created_at_i,
+            kids: None,
+        })
+    }
+
+    // Like `into_story`, but keeps comment hits as comments
+    pub fn into_item(self) -> Result<Item, HNError> {
+        if !self.tags.iter().any(|tag| tag == "comment") {
+            return self.into_story().map(Item::Story);
+        }
+
+        Ok(Item::Comment(Comment {
+            id: self.id()?,
+            deleted: false,
+            dead: false,
+            text: self.comment_text,
+            by: self.author,
+            time: self.created_at_i,
+            parent: self.parent_id.unwrap_or_default(),
+            kids: None,
+        }))
+    }
+
+    fn id(&self) -> Result<u32, HNError> {
+        self.object_id
+            .parse()
+            .map_err(|_| HNError::ApiError(format!("Algolia returned a non-numeric objectID: {}", self.object_id)))
+    }
+}
+
+// Client for the hn.algolia.com search API
+#[derive(Debug, Clone)]
+pub struct AlgoliaClient {
+    http: reqwest::Client,
+    base_url: String,
+    policy: RequestPolicy,
+}
+
+impl AlgoliaClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            policy: RequestPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RequestPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Uses `HN_ALGOLIA_BASE_URL` if set, otherwise the public endpoint
+    pub fn from_env() -> Self {
+        match std::env::var("HN_ALGOLIA_BASE_URL") {
+            Ok(base_url) => Self::new(&base_url),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub async fn search(&self, query: &AlgoliaQuery) -> Result<AlgoliaResponse, HNError> {
+        let endpoint = match query.sort {
+            AlgoliaSort::Relevance => "search",
+            AlgoliaSort::Date => "search_by_date",
+        };
+        let url = format!("{}/{}", self.base_url, endpoint);
+
+        let mut params = vec![
+            ("query", query.query.clone()),
+            ("page", query.page.to_string()),
+            ("hitsPerPage", query.hits_per_page.to_string()),
+        ];
+        if !query.optional_words.is_empty() {
+            params.push(("optionalWords", query.optional_words.join(",")));
+        }
+        if !query.tags.is_empty() {
+            params.push(("tags", query.tags.join(",")));
+        }
+        if !query.numeric_filters.is_empty() {
+            params.push(("numericFilters", query.numeric_filters.join(",")));
+        }
+
+        self.policy.get_json(&self.http, &url, &params).await
+    }
+}
+
+impl Default for AlgoliaClient {
+    fn default() -> Self {
+        Self::new(ALGOLIA_API_BASE)
+    }
+}
+
+// Algolia tag that restricts results to roughly the same items as a ranking list.
+// Algolia has no notion of top/best/new, so those search all stories.
+pub fn story_type_tag(story_type: StoryType) -> &'static str {
+    match story_type {
+        StoryType::Top | StoryType::Best | StoryType::New => "story",
+        StoryType::Ask => "ask_hn",
+        StoryType::Show => "show_hn",
+        StoryType::Job => "job",
+    }
+}
+
+// The parts of a parsed query that Algolia can evaluate itself. Only clauses that
+// every match must satisfy are pushed down; the caller still checks the full query.
+#[derive(Debug, Default)]
+pub struct Pushdown {
+    pub text: String,
+    pub optional_words: Vec<String>,
+    pub tags: Vec<String>,
+    pub numeric_filters: Vec<String>,
+    pub restricts_type: bool, // a type: qualifier replaced the story type tag
+}
+
+impl Pushdown {
+    pub fn from_query(query: &Query) -> Self {
+        let mut pushdown = Pushdown::default();
+        let mut words = Vec::new();
+
+        for clause in query.required_clauses() {
+            match clause {
+                Query::Term(term) => words.push(term.clone()),
+                Query::Phrase(phrase) => words.push(format!("\"{}\"", phrase.join(" "))),
+                Query::Filter(filter) => match filter {
+                    Filter::Author(author) => pushdown.tags.push(format!("author_{}", author)),
+                    Filter::Type(kind) => {
+                        pushdown.tags.push(kind.clone());
+                        pushdown.restricts_type = true;
+                    }
+                    Filter::Points(cmp, n) => pushdown.numeric_filters.push(format!("points{}{}", cmp.as_str(), n)),
+                    Filter::Comments(cmp, n) => {
+                        pushdown.numeric_filters.push(format!("num_comments{}{}", cmp.as_str(), n))
+                    }
+                    Filter::Before(time) => pushdown.numeric_filters.push(format!("created_at_i<{}", time)),
+                    Filter::After(time) => pushdown.numeric_filters.push(format!("created_at_i>{}", time)),
+                    Filter::Domain(_) | Filter::Title(_) => {}
+                },
+                // Alternative words are all searched for, but only one has to be there
+                Query::Or(alternatives) if alternatives.iter().all(|q| matches!(q, Query::Term(_))) => {
+                    for alternative in alternatives {
+                        if let Query::Term(term) = alternative {
+                            words.push(term.clone());
+                            pushdown.optional_words.push(term.clone());
+                        }
+                    }
+                }
+                Query::Not(_) | Query::And(_) | Query::Or(_) => {}
+            }
+        }
+
+        pushdown.text = words.join(" ");
+        pushdown
+    }
+}
+
+// An Algolia endpoint on localhost for tests. It serves canned pages by their
+// `page` parameter and keeps the path and query of every request it gets.
+#[cfg(test)]
+pub(crate) mod fake {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::AlgoliaClient;
+
+    pub struct FakeAlgolia {
+        base_url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeAlgolia {
+        // Pages past the end of `pages` have no hits
+        pub async fn start(pages: Vec<Vec<Value>>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let log = requests.clone();
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 4096];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match socket.read(&mut buffer).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(read) => request.extend_from_slice(&buffer[..read]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let target = request.split(' ').nth(1).unwrap_or_default().to_string();
+                    let page = target
+                        .split(['?', '&'])
+                        .find_map(|param| param.strip_prefix("page="))
+                        .and_then(|page| page.parse().ok())
+                        .unwrap_or(0);
+                    log.lock().unwrap().push(target);
+
+                    let body = page_body(&pages, page).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+
+            Self { base_url, requests }
+        }
+
+        pub fn client(&self) -> AlgoliaClient {
+            AlgoliaClient::new(&self.base_url)
+        }
+
+        // Path and query of each request so far, e.g. "/search?query=rust&page=0&hitsPerPage=5"
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn page_body(pages: &[Vec<Value>], page: usize) -> Value {
+        let hits = pages.get(page).cloned().unwrap_or_default();
+        let per_page = pages.first().map_or(0, Vec::len);
+        json!({
+            "hits": hits,
+            "nbHits": pages.iter().map(Vec::len).sum::<usize>(),
+            "page": page,
+            "nbPages": pages.len(),
+            "hitsPerPage": per_page
+        })
+    }
+
+    // A story hit as Algolia returns it
+    pub fn story_hit(id: u32, title: &str, url: &str) -> Value {
+        json!({
+            "objectID": id.to_string(),
+            "title": title,
+            "url": url,
+            "author": "pg",
+            "points": 10,
+            "story_text": null,
+            "comment_text": null,
+            "num_comments": 0,
+            "created_at_i": 1_700_000_000 + i64::from(id),
+            "_tags": ["story", "author_pg", format!("story_{}", id)]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::fake::{story_hit, FakeAlgolia};
+    use super::*;
+
+    // Trimmed from a real response of /api/v1/search?query=dropbox
+    const RESPONSE: &str = r#"{
+        "hits": [
+            {
+                "created_at": "2007-04-04T19:16:40Z",
+                "title": "My YC app: Dropbox - Throw away your USB drive",
+                "url": "http://www.getdropbox.com/u/2/screencast.html",
+                "author": "dhouston",
+                "points": 111,
+                "story_text": null,
+                "comment_text": null,
+                "num_comments": 71,
+                "story_id": null,
+                "story_title": null,
+                "story_url": null,
+                "parent_id": null,
+                "created_at_i": 1175714200,
+                "_tags": ["story", "author_dhouston", "story_8863"],
+                "objectID": "8863"
+            },
+            {
+                "created_at": "2007-04-05T00:49:40Z",
+                "title": null,
+                "url": null,
+                "author": "pg",
+                "points": null,
+                "story_text": null,
+                "comment_text": "I remember when Dropbox applied to YC.",
+                "num_comments": null,
+                "story_id": 8863,
+                "story_title": "My YC app: Dropbox - Throw away your USB drive",
+                "story_url": "http://www.getdropbox.com/u/2/screencast.html",
+                "parent_id": 8863,
+                "created_at_i": 1175734180,
+                "_tags": ["comment", "author_pg", "story_8863"],
+                "objectID": "8952"
+            }
+        ],
+        "nbHits": 2,
+        "page": 0,
+        "nbPages": 1,
+        "hitsPerPage": 20,
+        "processingTimeMS": 3,
+        "query": "dropbox",
+        "params": "query=dropbox"
+    }"#;
+
+    fn pushdown(query: &str) -> Pushdown {
+        Pushdown::from_query(&Query::parse(query).unwrap())
+    }
+
+    #[test]
+    fn pushes_down_required_clauses() {
+        let pushdown = pushdown(
+            "rust AND \"memory safe\" author:pg type:comment points>=100 comments<5 before:2024-01-01 after:2023-01-01",
+        );
+        assert_eq!(pushdown.text, "rust \"memory safe\"");
+        assert_eq!(pushdown.tags, ["author_pg", "comment"]);
+        assert!(pushdown.restricts_type);
+        assert_eq!(
+            pushdown.numeric_filters,
+            ["points>=100", "num_comments<5", "created_at_i<1704067200", "created_at_i>1672531200"]
+        );
+    }
+
+    #[test]
+    fn leaves_what_algolia_cannot_express_to_the_caller() {
+        let pushdown = pushdown("rust AND (async OR \"green threads\") -go domain:github.com title:crate");
+        assert_eq!(pushdown.text, "rust");
+        assert!(pushdown.tags.is_empty());
+        assert!(pushdown.numeric_filters.is_empty());
+        assert!(!pushdown.restricts_type);
+    }
+
+    #[test]
+    fn alternative_words_are_optional() {
+        let words = pushdown("rust lang programming points>10");
+        assert_eq!(words.text, "rust lang programming");
+        assert_eq!(words.optional_words, ["rust", "lang", "programming"]);
+        assert_eq!(words.numeric_filters, ["points>10"]);
+
+        let phrases = pushdown("\"memory safe\" rust");
+        assert!(phrases.text.is_empty());
+        assert!(phrases.optional_words.is_empty());
+    }
+
+    #[test]
+    fn pushes_nothing_down_from_alternatives() {
+        let pushdown = pushdown("rust OR author:pg points>10");
+        assert!(pushdown.text.is_empty());
+        assert!(pushdown.tags.is_empty());
+        assert!(pushdown.numeric_filters.is_empty());
+    }
+
+    #[test]
+    fn single_clauses_are_pushed_down() {
+        assert_eq!(pushdown("Rust").text, "rust");
+        assert_eq!(pushdown("points>10").numeric_filters, ["points>10"]);
+    }
+
+    #[test]
+    fn maps_story_and_comment_hits() {
+        let response: AlgoliaResponse = serde_json::from_str(RESPONSE).unwrap();
+        assert_eq!((response.nb_hits, response.nb_pages, response.hits_per_page), (2, 1, 20));
+        let mut hits = response.hits.into_iter();
+
+        let Item::Story(story) = hits.next().unwrap().into_item().unwrap() else { panic!("not a story") };
+        assert_eq!(story.id, 8863);
+        assert_eq!(story.title.as_deref(), Some("My YC app: Dropbox - Throw away your USB drive"));
+        assert_eq!(story.url.as_deref(), Some("http://www.getdropbox.com/u/2/screencast.html"));
+        assert_eq!((story.by.as_deref(), story.score, story.descendants), (Some("dhouston"), Some(111), Some(71)));
+        assert_eq!(story.time, 1175714200);
+        assert!(story.kids.is_none());
+
+        let Item::Comment(comment) = hits.next().unwrap().into_item().unwrap() else { panic!("not a comment") };
+        assert_eq!((comment.id, comment.parent), (8952, 8863));
+        assert_eq!(comment.text.as_deref(), Some("I remember when Dropbox applied to YC."));
+        assert_eq!(comment.by.as_deref(), Some("pg"));
+    }
+
+    #[test]
+    fn hits_without_tags_are_stories() {
+        let hit: AlgoliaHit = serde_json::from_value(json!({"objectID": "1", "created_at_i": 0})).unwrap();
+        assert!(matches!(hit.into_item().unwrap(), Item::Story(_)));
+    }
+
+    #[test]
+    fn non_numeric_object_ids_are_errors() {
+        let hit: AlgoliaHit = serde_json::from_value(json!({"objectID": "abc", "created_at_i": 0})).unwrap();
+        let error = hit.clone().into_story().unwrap_err();
+        assert_eq!(error.to_string(), "API error: Algolia returned a non-numeric objectID: abc");
+        assert!(hit.into_item().is_err());
+    }
+
+    #[tokio::test]
+    async fn sorts_pick_the_endpoint() {
+        let algolia = FakeAlgolia::start(vec![vec![story_hit(1, "Rust", "https://rust-lang.org")]]).await;
+        let mut query = AlgoliaQuery {
+            query: "rust".to_string(),
+            tags: vec!["story".to_string(), "author_pg".to_string()],
+            numeric_filters: vec!["points>=10".to_string()],
+            hits_per_page: 5,
+            ..AlgoliaQuery::default()
+        };
+        let response = algolia.client().search(&query).await.unwrap();
+        assert_eq!(response.hits[0].object_id, "1");
+
+        query.sort = AlgoliaSort::Date;
+        query.page = 2;
+        algolia.client().search(&query).await.unwrap();
+
+        assert_eq!(
+            algolia.requests(),
+            [
+                "/search?query=rust&page=0&hitsPerPage=5&tags=story%2Cauthor_pg&numericFilters=points%3E%3D10",
+                "/search_by_date?query=rust&page=2&hitsPerPage=5&tags=story%2Cauthor_pg&numericFilters=points%3E%3D10",
+            ]
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rig::embeddings::{DocumentEmbeddings, Embedding};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{HnClient, StoryType};
+use crate::error::{HNError, SkippedItem};
+use crate::item::{Item, Updates, User};
+use crate::query::{Comparison, Filter, Query};
+use crate::rank::stem_word;
+
+// Name of the archive database in the data directory
+pub const ARCHIVE_FILE_NAME: &str = "archive.sqlite";
+
+// Default location of the archive database
+pub fn default_archive_path() -> PathBuf {
+    std::env::var_os("HN_ARCHIVE_PATH").map(PathBuf::from).unwrap_or_else(|| {
+        dirs::data_dir()
+            .map(|dir| dir.join("rig-hn-assistant"))
+            .unwrap_or_else(|| PathBuf::from(".data"))
+            .join(ARCHIVE_FILE_NAME)
+    })
+}
+
+// A local SQLite mirror of HN items and profiles, filled by `sync::sync`.
+// Unlike the cache nothing here expires; items are replaced when HN reports
+// them as updated.
+pub struct Archive {
+    conn: Mutex<Connection>,
+}
+
+// Which items `Archive::search` returns. The SQL only narrows the candidates;
+// callers still check each one with `Query::matches`.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveQuery {
+    pub terms: Vec<String>, // each must appear somewhere in the title, text or author
+    pub author: Option<String>,
+    pub kind: Option<String>,  // an item type, which replaces the story type
+    pub story_type: StoryType, // ask, show and job narrow the stories; the ranking lists don't apply
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    pub min_points: Option<i64>,
+    pub min_comments: Option<i64>,
+    pub limit: usize,
+}
+
+impl ArchiveQuery {
+    // The parts of a query every match must satisfy, as far as SQL can check them.
+    // Terms become substrings short enough to also find the other forms of a word
+    // when `stem` is on.
+    pub fn from_query(query: &Query, stem: bool) -> Self {
+        let mut archive_query = ArchiveQuery::default();
+        let mut terms = Vec::new();
+
+        for clause in query.required_clauses() {
+            match clause {
+                Query::Term(term) => terms.push(substring_of(term, stem)),
+                Query::Phrase(words) | Query::Filter(Filter::Title(words)) => {
+                    terms.extend(words.iter().map(|word| substring_of(word, stem)))
+                }
+                Query::Filter(filter) => match filter {
+                    Filter::Author(author) => archive_query.author = Some(author.clone()),
+                    Filter::Type(kind) => archive_query.kind = Some(kind.clone()),
+                    Filter::Points(cmp, n) => archive_query.min_points = minimum(archive_query.min_points, *cmp, *n),
+                    Filter::Comments(cmp, n) => {
+                        archive_query.min_comments = minimum(archive_query.min_comments, *cmp, *n)
+                    }
+                    Filter::Before(time) => archive_query.created_before = Some(*time),
+                    Filter::After(time) => archive_query.created_after = Some(*time),
+                    Filter::Domain(_) | Filter::Title(_) => {}
+                },
+                Query::Not(_) | Query::And(_) | Query::Or(_) => {}
+            }
+        }
+
+        terms.retain(|term| !term.is_empty());
+        archive_query.terms = terms;
+        archive_query
+    }
+}
+
+// The part of a word shared with its stem: "libraries" and "library" both become "librar"
+fn substring_of(word: &str, stem: bool) -> String {
+    if !stem {
+        return word.to_string();
+    }
+    let stem = stem_word(word);
+    let stem = stem.strip_suffix('y').unwrap_or(&stem);
+    let shared = word.chars().zip(stem.chars()).take_while(|(a, b)| a == b).count();
+    word.chars().take(shared).collect()
+}
+
+// The lower bound implied by a comparison, combined with one already known
+fn minimum(current: Option<i64>, cmp: Comparison, bound: i64) -> Option<i64> {
+    let implied = match cmp {
+        Comparison::Greater => bound + 1,
+        Comparison::GreaterOrEqual | Comparison::Equal => bound,
+        Comparison::Less | Comparison::LessOrEqual => return current,
+    };
+    Some(current.map_or(implied, |current| current.max(implied)))
+}
+
+// A LIKE pattern matching `text` anywhere, with wildcards in it taken literally
+fn contains_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// What the archive holds
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ArchiveStats {
+    pub items: u64,
+    pub stories: u64,
+    pub users: u64,
+    pub oldest_id: Option<u32>,
+    pub newest_id: Option<u32>,
+    pub oldest_time: Option<i64>,
+    pub newest_time: Option<i64>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self, HNError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| HNError::Cache(format!("cannot create {}: {}", dir.display(), e)))?;
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    // The archive at `path` if a sync has created one
+    pub fn open_existing(path: &Path) -> Result<Option<Self>, HNError> {
+        if path.exists() {
+            Self::open(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn in_memory() -> Result<Self, HNError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, HNError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS items (
+                id INTEGER PRIMARY KEY,
+                type TEXT NOT NULL,
+                by TEXT,
+                time INTEGER NOT NULL,
+                title TEXT,
+                url TEXT,
+                text TEXT,
+                score INTEGER,
+                descendants INTEGER,
+                parent INTEGER,
+                deleted INTEGER NOT NULL,
+                dead INTEGER NOT NULL,
+                json TEXT NOT NULL,  -- the item as the API returned it
+                synced_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS items_type_time ON items (type, time);
+            CREATE INDEX IF NOT EXISTS items_parent ON items (parent);
+            CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                json TEXT NOT NULL,
+                synced_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sync_state (
+                key TEXT PRIMARY KEY,
+                value INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS pending (
+                id INTEGER PRIMARY KEY, -- an item whose fetch failed, to try again at the next sync
+                reason TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS embeddings (
+                id INTEGER NOT NULL,
+                model TEXT NOT NULL,    -- embeddings from different models can't be compared
+                document TEXT NOT NULL, -- the JSON document the vector store returns
+                text TEXT NOT NULL,     -- what was embedded
+                vector BLOB NOT NULL,   -- little-endian f64s
+                PRIMARY KEY (id, model)
+            )",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    // Insert or replace items in one transaction
+    pub fn put_items(&self, items: &[Item]) -> Result<(), HNError> {
+        let now = chrono::Utc::now().timestamp();
+        let mut conn = self.conn.lock().expect("archive connection poisoned");
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO items
+                    (id, type, by, time, title, url, text, score, descendants, parent, deleted, dead, json, synced_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )?;
+            // A changed item is embedded again
+            let mut forget = tx.prepare_cached("DELETE FROM embeddings WHERE id = ?1")?;
+            for item in items {
+                forget.execute(params![item.id()])?;
+                insert.execute(params![
+                    item.id(),
+                    item.kind(),
+                    item.by(),
+                    item.time(),
+                    item.title(),
+                    item.url(),
+                    item.text(),
+                    item.score(),
+                    item.descendants(),
+                    item.parent(),
+                    item.is_deleted(),
+                    item.is_dead(),
+                    serde_json::to_string(item)?,
+                    now,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn put_user(&self, user: &User) -> Result<(), HNError> {
+        let now = chrono::Utc::now().timestamp();
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        conn.execute(
+            "INSERT OR REPLACE INTO users (id, json, synced_at) VALUES (?1, ?2, ?3)",
+            params![user.id, serde_json::to_string(user)?, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn item(&self, id: u32) -> Result<Option<Item>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let json: Option<String> =
+            conn.query_row("SELECT json FROM items WHERE id = ?1", params![id], |row| row.get(0)).optional()?;
+        Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    pub fn user(&self, username: &str) -> Result<Option<User>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let json: Option<String> =
+            conn.query_row("SELECT json FROM users WHERE id = ?1", params![username], |row| row.get(0)).optional()?;
+        Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    // Newest items first that pass every filter in `query`
+    pub fn search(&self, query: &ArchiveQuery) -> Result<Vec<Item>, HNError> {
+        let mut sql = String::from("SELECT json FROM items WHERE deleted = 0 AND dead = 0");
+        let mut values: Vec<Value> = Vec::new();
+
+        match &query.kind {
+            Some(kind) => {
+                sql.push_str(" AND type = ?");
+                values.push(Value::Text(kind.clone()));
+            }
+            None => sql.push_str(match query.story_type {
+                StoryType::Ask => " AND type = 'story' AND title LIKE 'Ask HN%'",
+                StoryType::Show => " AND type = 'story' AND title LIKE 'Show HN%'",
+                StoryType::Job => " AND type = 'job'",
+                StoryType::Top | StoryType::Best | StoryType::New => " AND type IN ('story', 'poll')",
+            }),
+        }
+        for term in &query.terms {
+            sql.push_str(" AND (title LIKE ? ESCAPE '\\' OR text LIKE ? ESCAPE '\\' OR by LIKE ? ESCAPE '\\')");
+            values.extend(std::iter::repeat_n(Value::Text(contains_pattern(term)), 3));
+        }
+        if let Some(author) = &query.author {
+            sql.push_str(" AND by = ? COLLATE NOCASE");
+            values.push(Value::Text(author.clone()));
+        }
+        let filters = [
+            (" AND time > ?", query.created_after),
+            (" AND time < ?", query.created_before),
+            (" AND score >= ?", query.min_points),
+            (" AND descendants >= ?", query.min_comments),
+        ];
+        for (condition, value) in filters {
+            if let Some(value) = value {
+                sql.push_str(condition);
+                values.push(Value::Integer(value));
+            }
+        }
+        sql.push_str(" ORDER BY time DESC LIMIT ?");
+        values.push(Value::Integer(query.limit as i64));
+
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?;
+        let mut items = Vec::new();
+        for json in rows {
+            items.push(serde_json::from_str(&json?)?);
+        }
+        Ok(items)
+    }
+
+    // The story, job or poll a comment or poll option belongs to, found by walking up
+    // its parents. None if a parent along the way is not archived yet.
+    pub fn root_of(&self, id: u32) -> Result<Option<u32>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        Ok(conn
+            .query_row(
+                "WITH RECURSIVE up(id, parent) AS (
+                    SELECT id, parent FROM items WHERE id = ?1
+                    UNION ALL
+                    SELECT items.id, items.parent FROM items JOIN up ON items.id = up.parent
+                 )
+                 SELECT id FROM up WHERE parent IS NULL",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    // Every archived reply below an item, at any depth, in ID order
+    pub fn descendants(&self, id: u32) -> Result<Vec<Item>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement = conn.prepare_cached(
+            "WITH RECURSIVE down(id) AS (
+                SELECT id FROM items WHERE parent = ?1
+                UNION ALL
+                SELECT items.id FROM items JOIN down ON items.parent = down.id
+             )
+             SELECT json FROM items WHERE id IN down ORDER BY id",
+        )?;
+        let rows = statement.query_map(params![id], |row| row.get::<_, String>(0))?;
+        let mut items = Vec::new();
+        for json in rows {
+            items.push(serde_json::from_str(&json?)?);
+        }
+        Ok(items)
+    }
+
+    // IDs of archived stories, jobs and polls above `after`, in order, for paging
+    // through the whole archive
+    pub fn root_ids(&self, after: u32, limit: usize) -> Result<Vec<u32>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement =
+            conn.prepare_cached("SELECT id FROM items WHERE parent IS NULL AND id > ?1 ORDER BY id LIMIT ?2")?;
+        let rows = statement.query_map(params![after, limit as i64], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // The lowest ID among the newest `count` live items with a title or text,
+    // or 0 if there are fewer
+    pub fn text_window_start(&self, count: usize) -> Result<u32, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let start = conn
+            .query_row(
+                "SELECT id FROM items
+                 WHERE deleted = 0 AND dead = 0 AND (title IS NOT NULL OR text IS NOT NULL)
+                 ORDER BY id DESC LIMIT 1 OFFSET ?1",
+                params![count.saturating_sub(1) as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(start.unwrap_or(0))
+    }
+
+    // Live items with a title or text and an ID from `start` up to, but not
+    // including, `before` that `model` hasn't embedded yet, newest first
+    pub fn items_to_embed(&self, model: &str, start: u32, before: u32, limit: usize) -> Result<Vec<Item>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement = conn.prepare_cached(
+            "SELECT json FROM items
+             WHERE id >= ?1 AND id < ?2 AND deleted = 0 AND dead = 0 AND (title IS NOT NULL OR text IS NOT NULL)
+               AND NOT EXISTS (SELECT 1 FROM embeddings WHERE embeddings.id = items.id AND model = ?3)
+             ORDER BY id DESC LIMIT ?4",
+        )?;
+        let rows = statement.query_map(params![start, before, model, limit as i64], |row| row.get::<_, String>(0))?;
+        let mut items = Vec::new();
+        for json in rows {
+            items.push(serde_json::from_str(&json?)?);
+        }
+        Ok(items)
+    }
+
+    // Store embeddings made by `model`. Document IDs are item IDs, and only the
+    // first embedding of each document is kept.
+    pub fn put_embeddings(&self, model: &str, documents: &[DocumentEmbeddings]) -> Result<(), HNError> {
+        let mut conn = self.conn.lock().expect("archive connection poisoned");
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO embeddings (id, model, document, text, vector) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for document in documents {
+                let id: u32 = document
+                    .id
+                    .parse()
+                    .map_err(|_| HNError::InvalidArgument(format!("'{}' is not an item ID", document.id)))?;
+                let Some(embedding) = document.embeddings.first() else { continue };
+                let vector: Vec<u8> = embedding.vec.iter().flat_map(|value| value.to_le_bytes()).collect();
+                insert.execute(params![id, model, document.document.to_string(), embedding.document, vector])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // The newest `limit` embeddings made by `model`
+    pub fn embeddings(&self, model: &str, limit: usize) -> Result<Vec<DocumentEmbeddings>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement = conn.prepare_cached(
+            "SELECT id, document, text, vector FROM embeddings WHERE model = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![model, limit as i64], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Vec<u8>>(3)?))
+        })?;
+        let mut documents = Vec::new();
+        for row in rows {
+            let (id, document, text, vector) = row?;
+            let vec = vector
+                .chunks_exact(8)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().expect("chunks of 8 bytes")))
+                .collect();
+            documents.push(DocumentEmbeddings {
+                id: id.to_string(),
+                document: serde_json::from_str(&document)?,
+                embeddings: vec![Embedding { document: text, vec }],
+            });
+        }
+        Ok(documents)
+    }
+
+    pub fn stats(&self) -> Result<ArchiveStats, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let (items, oldest_id, newest_id, oldest_time, newest_time) = conn.query_row(
+            "SELECT COUNT(*), MIN(id), MAX(id), MIN(NULLIF(time, 0)), MAX(time) FROM items",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )?;
+        let stories = conn.query_row("SELECT COUNT(*) FROM items WHERE type = 'story'", [], |row| row.get(0))?;
+        let users = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        Ok(ArchiveStats { items, stories, users, oldest_id, newest_id, oldest_time, newest_time })
+    }
+
+    // Progress markers kept by `sync` between runs
+    pub(crate) fn state(&self, key: &str) -> Result<Option<u32>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        Ok(conn.query_row("SELECT value FROM sync_state WHERE key = ?1", params![key], |row| row.get(0)).optional()?)
+    }
+
+    pub(crate) fn set_state(&self, key: &str, value: u32) -> Result<(), HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        conn.execute("INSERT OR REPLACE INTO sync_state (key, value) VALUES (?1, ?2)", params![key, value])?;
+        Ok(())
+    }
+
+    // IDs `sync` failed to fetch, lowest first. The watermarks have moved past
+    // them, so only this list brings them back.
+    pub(crate) fn pending(&self) -> Result<Vec<u32>, HNError> {
+        let conn = self.conn.lock().expect("archive connection poisoned");
+        let mut statement = conn.prepare_cached("SELECT id FROM pending ORDER BY id")?;
+        let rows = statement.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // Note the fetches that failed and clear the ones that are settled, whether
+    // they returned an item or nothing
+    pub(crate) fn update_pending(&self, failed: &[SkippedItem], settled: &[u32]) -> Result<(), HNError> {
+        let mut conn = self.conn.lock().expect("archive connection poisoned");
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached("INSERT OR REPLACE INTO pending (id, reason) VALUES (?1, ?2)")?;
+            for skipped in failed {
+                insert.execute(params![skipped.id, skipped.reason])?;
+            }
+            let mut delete = tx.prepare_cached("DELETE FROM pending WHERE id = ?1")?;
+            for id in settled {
+                delete.execute(params![id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+// Reads served from the archive alone, so archived discussions can be opened
+// and searched without the network. Items and users outside the archive read
+// as missing; there are no story lists.
+impl HnClient for Archive {
+    async fn list_stories(&self, story_type: StoryType) -> Result<Vec<u32>, HNError> {
+        Err(HNError::ApiError(format!("the local archive has no {} list", story_type.endpoint())))
+    }
+
+    async fn get_item(&self, id: u32) -> Result<Option<Item>, HNError> {
+        self.item(id)
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, HNError> {
+        self.user(username)
+    }
+
+    async fn get_max_item(&self) -> Result<u32, HNError> {
+        Ok(self.stats()?.newest_id.unwrap_or_default())
+    }
+
+    async fn get_updates(&self) -> Result<Updates, HNError> {
+        Ok(Updates { items: Vec::new(), profiles: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story};
+
+    fn scored(mut item: Item, score: i32) -> Item {
+        if let Item::Story(story) = &mut item {
+            story.score = Some(score);
+        }
+        item
+    }
+
+    fn archive() -> Archive {
+        let mut deleted = story(5, "pg", "Rust deleted", &[]);
+        if let Item::Story(story) = &mut deleted {
+            story.deleted = true;
+        }
+        let archive = Archive::in_memory().unwrap();
+        archive
+            .put_items(&[
+                scored(story(1, "pg", "Ask HN: Is Rust ready?", &[]), 50),
+                scored(story(2, "dang", "Show HN: 100%_done, a tracker", &[]), 5),
+                scored(story(3, "steve", "Rust 2024 released", &[4]), 200),
+                comment(4, "pg", 3, "rust is great", &[]),
+                deleted,
+            ])
+            .unwrap();
+        archive
+    }
+
+    fn search(archive: &Archive, query: ArchiveQuery) -> Vec<u32> {
+        archive.search(&ArchiveQuery { limit: 10, ..query }).unwrap().iter().map(Item::id).collect()
+    }
+
+    fn terms(terms: &[&str]) -> ArchiveQuery {
+        ArchiveQuery { terms: terms.iter().map(|term| term.to_string()).collect(), ..ArchiveQuery::default() }
+    }
+
+    #[test]
+    fn searches_live_stories_newest_first() {
+        let archive = archive();
+        assert_eq!(search(&archive, terms(&["rust"])), [3, 1]);
+        assert_eq!(search(&archive, ArchiveQuery { kind: Some("comment".to_string()), ..terms(&["rust"]) }), [4]);
+        assert_eq!(search(&archive, terms(&["rust", "ready"])), [1]);
+        assert_eq!(archive.search(&ArchiveQuery { limit: 1, ..terms(&["rust"]) }).unwrap()[0].id(), 3);
+    }
+
+    #[test]
+    fn filters_by_story_type_and_author() {
+        let archive = archive();
+        assert_eq!(search(&archive, ArchiveQuery { story_type: StoryType::Ask, ..ArchiveQuery::default() }), [1]);
+        assert_eq!(search(&archive, ArchiveQuery { story_type: StoryType::Show, ..ArchiveQuery::default() }), [2]);
+        assert!(search(&archive, ArchiveQuery { story_type: StoryType::Job, ..ArchiveQuery::default() }).is_empty());
+        assert_eq!(search(&archive, ArchiveQuery { author: Some("PG".to_string()), ..ArchiveQuery::default() }), [1]);
+    }
+
+    #[test]
+    fn filters_by_time_points_and_comments() {
+        let archive = archive();
+        let query = |query: ArchiveQuery| search(&archive, query);
+        assert_eq!(query(ArchiveQuery { created_after: Some(1_700_000_001), ..ArchiveQuery::default() }), [3, 2]);
+        assert_eq!(query(ArchiveQuery { created_before: Some(1_700_000_003), ..ArchiveQuery::default() }), [2, 1]);
+        assert_eq!(query(ArchiveQuery { min_points: Some(50), ..ArchiveQuery::default() }), [3, 1]);
+        assert_eq!(query(ArchiveQuery { min_points: Some(51), ..ArchiveQuery::default() }), [3]);
+        assert_eq!(query(ArchiveQuery { min_comments: Some(1), ..ArchiveQuery::default() }), [3]);
+    }
+
+    #[test]
+    fn like_wildcards_in_terms_are_literal() {
+        let archive = archive();
+        assert_eq!(search(&archive, terms(&["%"])), [2]);
+        assert_eq!(search(&archive, terms(&["_"])), [2]);
+        assert_eq!(search(&archive, terms(&["100%_"])), [2]);
+    }
+
+    #[test]
+    fn required_clauses_become_sql_filters() {
+        let query = Query::parse(
+            "rust AND \"green threads\" author:pg type:story points>100 points>=150 comments>10 \
+             after:2024-01-01 -crypto",
+        )
+        .unwrap();
+        let archive_query = ArchiveQuery::from_query(&query, false);
+        assert_eq!(archive_query.terms, ["rust", "green", "threads"]);
+        assert_eq!(archive_query.author.as_deref(), Some("pg"));
+        assert_eq!(archive_query.kind.as_deref(), Some("story"));
+        assert_eq!(archive_query.min_points, Some(150));
+        assert_eq!(archive_query.min_comments, Some(11));
+        assert_eq!(archive_query.created_after, Some(1_704_067_200));
+        assert_eq!(archive_query.created_before, None);
+
+        // Alternatives can't narrow the candidates
+        assert!(ArchiveQuery::from_query(&Query::parse("rust go").unwrap(), false).terms.is_empty());
+        // Stemmed terms shrink to the part every form of the word shares
+        let stemmed = ArchiveQuery::from_query(&Query::parse("libraries").unwrap(), true);
+        assert_eq!(stemmed.terms, ["librar"]);
+    }
+
+    #[test]
+    fn failed_fetches_stay_pending_until_settled() {
+        let archive = Archive::in_memory().unwrap();
+        let failed = |id| SkippedItem { id, reason: "HTTP 503".to_string() };
+        archive.update_pending(&[failed(7), failed(3)], &[]).unwrap();
+        assert_eq!(archive.pending().unwrap(), [3, 7]);
+        archive.update_pending(&[failed(9)], &[3]).unwrap();
+        assert_eq!(archive.pending().unwrap(), [7, 9]);
+    }
+}
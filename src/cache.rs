@@ -0,0 +1,341 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::client::{HnClient, StoryType};
+use crate::error::HNError;
+use crate::item::{Item, Updates, User};
+
+// How long each kind of cached data stays fresh
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    pub story_list_ttl: Duration,
+    pub max_item_ttl: Duration,
+    pub updates_ttl: Duration,
+    pub user_ttl: Duration,
+    pub recent_item_ttl: Duration, // items still open to votes and replies
+    pub settled_after: Duration,   // age after which an item is treated as immutable
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            story_list_ttl: Duration::from_secs(5 * 60),
+            max_item_ttl: Duration::from_secs(60),
+            updates_ttl: Duration::from_secs(60),
+            user_ttl: Duration::from_secs(60 * 60),
+            recent_item_ttl: Duration::from_secs(10 * 60),
+            // HN closes voting and replies after two weeks
+            settled_after: Duration::from_secs(14 * 24 * 60 * 60),
+        }
+    }
+}
+
+// Counters for cache lookups, cheap to read while the cache is in use
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64, // includes stale entries that had to be refetched
+}
+
+impl CacheStats {
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+// A single-file SQLite store of JSON values keyed by API path. Lookups run on
+// tokio's blocking threads, so a slow disk never holds up the runtime's workers
+// while a search has many fetches in flight.
+pub struct CacheStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl CacheStore {
+    pub fn open(path: &Path) -> Result<Self, HNError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| HNError::Cache(format!("cannot create {}: {}", dir.display(), e)))?;
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, HNError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, HNError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                fetched_at INTEGER NOT NULL,
+                expires_at INTEGER  -- NULL never expires
+            )",
+        )?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    // Run `query` against the database on a blocking thread
+    async fn with_conn<T, F>(&self, query: F) -> Result<T, HNError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, HNError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || query(&conn.lock().expect("cache connection poisoned")))
+            .await
+            .map_err(|e| HNError::Cache(format!("cache query failed: {}", e)))?
+    }
+
+    // The cached value and whether it is still fresh at `now`
+    async fn get(&self, key: &str, now: i64) -> Result<Option<(String, bool)>, HNError> {
+        let key = key.to_string();
+        self.with_conn(move |conn| {
+            let row = conn
+                .query_row("SELECT value, expires_at FROM entries WHERE key = ?1", params![key], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+                })
+                .optional()?;
+            Ok(row.map(|(value, expires_at)| (value, expires_at.is_none_or(|at| at > now))))
+        })
+        .await
+    }
+
+    async fn put(&self, key: &str, value: String, now: i64, ttl: Option<Duration>) -> Result<(), HNError> {
+        let key = key.to_string();
+        let expires_at = ttl.map(|ttl| now + ttl.as_secs() as i64);
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO entries (key, value, fetched_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+                params![key, value, now, expires_at],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+// Name of the cache database inside the cache directory
+pub const CACHE_FILE_NAME: &str = "cache.sqlite";
+
+// Default directory for the cache database
+pub fn default_cache_dir() -> PathBuf {
+    std::env::var_os("HN_CACHE_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join("rig-hn-assistant")))
+        .unwrap_or_else(|| PathBuf::from(".cache"))
+}
+
+// Default location of the cache database
+pub fn default_cache_path() -> PathBuf {
+    default_cache_dir().join(CACHE_FILE_NAME)
+}
+
+// `HnClient` that answers from a local cache and only goes to `inner` for
+// missing or stale entries. In offline mode `inner` is never called and stale
+// entries are served as they are.
+pub struct CachedClient<C> {
+    inner: C,
+    store: CacheStore,
+    policy: CachePolicy,
+    offline: bool,
+    stats: CacheStats,
+}
+
+impl<C: HnClient> CachedClient<C> {
+    pub fn new(inner: C, store: CacheStore) -> Self {
+        Self { inner, store, policy: CachePolicy::default(), offline: false, stats: CacheStats::default() }
+    }
+
+    pub fn with_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    // Look `key` up, fetching and storing it on a miss. `ttl` decides how long a
+    // freshly fetched value stays fresh; `None` caches it forever.
+    async fn cached<T, F>(&self, key: &str, fetch: F, ttl: impl Fn(&T) -> Option<Duration>) -> Result<T, HNError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: std::future::Future<Output = Result<T, HNError>>,
+    {
+        let now = chrono::Utc::now().timestamp();
+
+        let cached = self.store.get(key, now).await?;
+        if let Some((value, fresh)) = &cached {
+            if *fresh || self.offline {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(serde_json::from_str(value)?);
+            }
+        }
+
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        if self.offline {
+            return Err(HNError::Offline(key.to_string()));
+        }
+
+        let value = fetch.await?;
+        self.store.put(key, serde_json::to_string(&value)?, now, ttl(&value)).await?;
+        Ok(value)
+    }
+
+    fn item_ttl(&self, item: &Option<Item>) -> Option<Duration> {
+        let now = chrono::Utc::now().timestamp();
+        match item {
+            Some(item) if now - item.time() > self.policy.settled_after.as_secs() as i64 => None,
+            _ => Some(self.policy.recent_item_ttl),
+        }
+    }
+}
+
+impl<C: HnClient> HnClient for CachedClient<C> {
+    async fn list_stories(&self, story_type: StoryType) -> Result<Vec<u32>, HNError> {
+        let ttl = self.policy.story_list_ttl;
+        self.cached(story_type.endpoint(), self.inner.list_stories(story_type), |_| Some(ttl)).await
+    }
+
+    async fn get_item(&self, id: u32) -> Result<Option<Item>, HNError> {
+        self.cached(&format!("item/{}", id), self.inner.get_item(id), |item| self.item_ttl(item)).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, HNError> {
+        let ttl = self.policy.user_ttl;
+        self.cached(&format!("user/{}", username), self.inner.get_user(username), |_| Some(ttl)).await
+    }
+
+    async fn get_max_item(&self) -> Result<u32, HNError> {
+        let ttl = self.policy.max_item_ttl;
+        self.cached("maxitem", self.inner.get_max_item(), |_| Some(ttl)).await
+    }
+
+    async fn get_updates(&self) -> Result<Updates, HNError> {
+        let ttl = self.policy.updates_ttl;
+        self.cached("updates", self.inner.get_updates(), |_| Some(ttl)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{story, MemoryClient};
+
+    const NO_TTL: Duration = Duration::ZERO;
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    // A story submitted just now, so it isn't settled yet
+    fn recent(id: u32) -> Item {
+        let mut item = story(id, "pg", "New", &[]);
+        if let Item::Story(story) = &mut item {
+            story.time = now();
+        }
+        item
+    }
+
+    fn inner() -> MemoryClient {
+        MemoryClient::new().with_items([story(1, "pg", "Old", &[]), recent(2)]).with_list(StoryType::Top, vec![1, 2])
+    }
+
+    fn cached(inner: &MemoryClient, policy: CachePolicy) -> CachedClient<MemoryClient> {
+        CachedClient::new(inner.clone(), CacheStore::in_memory().unwrap()).with_policy(policy)
+    }
+
+    #[tokio::test]
+    async fn fresh_entries_are_served_from_the_cache() {
+        let inner = inner();
+        let client = cached(&inner, CachePolicy::default());
+        for _ in 0..3 {
+            assert_eq!(client.list_stories(StoryType::Top).await.unwrap(), [1, 2]);
+            assert_eq!(client.get_item(2).await.unwrap().unwrap().id(), 2);
+        }
+        assert_eq!(inner.requests(), 2);
+        assert_eq!((client.stats().hits(), client.stats().misses()), (4, 2));
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_fetched_again() {
+        let inner = inner();
+        let policy = CachePolicy { story_list_ttl: NO_TTL, recent_item_ttl: NO_TTL, ..CachePolicy::default() };
+        let client = cached(&inner, policy);
+        for _ in 0..3 {
+            client.list_stories(StoryType::Top).await.unwrap();
+            client.get_item(2).await.unwrap();
+        }
+        assert_eq!(inner.requests(), 6);
+        assert_eq!(client.stats().hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn settled_items_never_expire() {
+        let inner = inner();
+        let policy = CachePolicy { recent_item_ttl: NO_TTL, ..CachePolicy::default() };
+        let client = cached(&inner, policy);
+        for _ in 0..3 {
+            assert_eq!(client.get_item(1).await.unwrap().unwrap().title(), Some("Old"));
+        }
+        assert_eq!(inner.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_items_are_cached_as_missing() {
+        let inner = inner();
+        let client = cached(&inner, CachePolicy::default());
+        assert!(client.get_item(99).await.unwrap().is_none());
+        assert!(client.get_item(99).await.unwrap().is_none());
+        assert_eq!(inner.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn offline_serves_stale_entries_without_fetching() {
+        let inner = inner();
+        let store = CacheStore::in_memory().unwrap();
+        store.put("topstories", "[7, 8]".to_string(), now() - 3600, Some(Duration::from_secs(60))).await.unwrap();
+        let client = CachedClient::new(inner.clone(), store).offline(true);
+
+        assert_eq!(client.list_stories(StoryType::Top).await.unwrap(), [7, 8]);
+        assert_eq!(client.stats().hits(), 1);
+        assert_eq!(inner.requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn offline_misses_are_errors() {
+        let inner = inner();
+        let client = cached(&inner, CachePolicy::default()).offline(true);
+        let error = client.get_item(1).await.unwrap_err();
+        assert!(matches!(&error, HNError::Offline(key) if key == "item/1"), "{}", error);
+        assert_eq!(inner.requests(), 0);
+        assert_eq!(client.stats().misses(), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_replaced_online() {
+        let inner = inner();
+        let store = CacheStore::in_memory().unwrap();
+        store.put("topstories", "[7, 8]".to_string(), now() - 3600, Some(Duration::from_secs(60))).await.unwrap();
+        let client = CachedClient::new(inner.clone(), store);
+
+        assert_eq!(client.list_stories(StoryType::Top).await.unwrap(), [1, 2]);
+        assert_eq!(client.list_stories(StoryType::Top).await.unwrap(), [1, 2]);
+        assert_eq!(inner.requests(), 1);
+    }
+}
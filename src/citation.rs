@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::html;
+use crate::item::{Comment, Item};
+use crate::lookup::{ItemContext, UserProfile};
+use crate::rag::ContextPassage;
+use crate::search::SearchOutput;
+use crate::summarize::ThreadSummary;
+
+// Quotes with fewer words are left unchecked: a single word in quotation
+// marks is usually a scare quote or a term, not a quotation
+const MIN_QUOTE_WORDS: usize = 2;
+
+// Shorter usernames are too likely to be ordinary words to count as naming someone
+const MIN_AUTHOR_CHARS: usize = 3;
+
+// What the model was shown of one item
+#[derive(Debug, Clone, Default)]
+struct Source {
+    by: Option<String>,
+    text: String, // its title and text run through `normalize`, padded with spaces
+}
+
+// Every item the model was shown while answering, to check its citations against
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    items: HashMap<u32, Source>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.items.contains_key(&id)
+    }
+
+    pub fn add_item(&mut self, item: &Item) {
+        let text = html::to_text(item.text().unwrap_or_default());
+        let text = match item.title() {
+            Some(title) => format!("{}\n{}", title, text),
+            None => text,
+        };
+        self.add(item.id(), item.by(), &text);
+    }
+
+    pub fn add_comment(&mut self, comment: &Comment) {
+        let text = html::to_text(comment.text.as_deref().unwrap_or_default());
+        self.add(comment.id, comment.by.as_deref(), &text);
+    }
+
+    pub fn add_search(&mut self, search: &SearchOutput) {
+        for result in &search.results {
+            self.add_item(&result.item);
+            result.comments.iter().for_each(|comment| self.add_comment(comment));
+        }
+    }
+
+    pub fn add_item_context(&mut self, context: &ItemContext) {
+        context.parents.iter().chain([&context.item]).chain(&context.children).for_each(|item| self.add_item(item));
+    }
+
+    pub fn add_user_profile(&mut self, profile: &UserProfile) {
+        profile.submissions.iter().for_each(|item| self.add_item(item));
+    }
+
+    // The model only saw the summary of the comments it cites, so words
+    // quoted from them must come from the summary
+    pub fn add_summary(&mut self, summary: &ThreadSummary) {
+        self.add_item(&summary.root);
+        for &id in &summary.cited {
+            self.add(id, None, &summary.summary);
+        }
+    }
+
+    pub fn add_passage(&mut self, passage: &ContextPassage) {
+        let text = match &passage.title {
+            Some(title) if passage.id == passage.story => format!("{}\n{}", title, passage.text),
+            _ => passage.text.clone(),
+        };
+        self.add(passage.id, passage.by.as_deref(), &text);
+    }
+
+    // An item seen more than once, e.g. first in a summary and then in full,
+    // keeps everything it was seen with
+    fn add(&mut self, id: u32, by: Option<&str>, text: &str) {
+        let source = self.items.entry(id).or_default();
+        if source.by.is_none() {
+            source.by = by.map(str::to_string);
+        }
+        // "|" never survives normalizing, so a quote can't match across the seam
+        if source.text.is_empty() {
+            source.text.push(' ');
+        } else {
+            source.text.push_str("| ");
+        }
+        source.text.push_str(&normalize(text));
+        source.text.push(' ');
+    }
+
+    // Whether every part of a quote, split at ellipses, is in the item
+    fn quotes(&self, id: u32, quote: &str) -> bool {
+        let Some(source) = self.items.get(&id) else { return false };
+        fragments(quote).iter().all(|fragment| source.text.contains(&format!(" {} ", fragment)))
+    }
+
+    // Whether the item itself was written by one of `names`. A reply to pg
+    // isn't by pg, so naming the author of the parent doesn't count.
+    fn written_by_any(&self, id: u32, names: &[String]) -> Option<bool> {
+        let by = self.items.get(&id)?.by.as_ref()?;
+        Some(names.iter().any(|name| name == by))
+    }
+
+    fn authors(&self) -> Vec<&str> {
+        let mut authors: Vec<&str> = self
+            .items
+            .values()
+            .filter_map(|source| source.by.as_deref())
+            .filter(|by| by.chars().count() >= MIN_AUTHOR_CHARS)
+            .collect();
+        authors.sort_unstable();
+        authors.dedup();
+        authors
+    }
+}
+
+// Lowercase words separated by single spaces, so a quote matches whatever
+// the punctuation, case or line breaks around it
+fn normalize(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        if !output.is_empty() {
+            output.push(' ');
+        }
+        output.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    output
+}
+
+// The normalized parts of a quote that leaves words out with "..." or "…"
+fn fragments(quote: &str) -> Vec<String> {
+    quote.replace("...", "…").split('…').map(normalize).filter(|fragment| !fragment.is_empty()).collect()
+}
+
+// What checking a citation found
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum CitationStatus {
+    Verified,                           // the model was shown the item and it says what is attributed to it
+    Misquoted { missing: Vec<String> }, // words quoted from the item that it doesn't contain
+    Misattributed { by: String },       // the item's real author, who the answer doesn't name
+    Unsupported,                        // the model was never shown the item, so the citation is made up
+}
+
+// One item ID cited in square brackets, e.g. [8863], and what checking it found
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Citation {
+    pub id: u32,
+    pub start: usize, // byte range of the brackets in the answer, shared by IDs cited together
+    pub end: usize,
+    #[serde(flatten)]
+    pub status: CitationStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quotes: Vec<String>, // words the answer quotes from the item
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub named: Vec<String>, // authors the answer names along with it
+}
+
+impl Citation {
+    pub fn is_verified(&self) -> bool {
+        self.status == CitationStatus::Verified
+    }
+
+    // Citations of items the model never saw are dropped from the answer;
+    // the others stay, flagged if something about them is wrong
+    pub fn is_kept(&self) -> bool {
+        self.status != CitationStatus::Unsupported
+    }
+
+    // What is wrong with the citation, if anything
+    pub fn problem(&self) -> Option<String> {
+        match &self.status {
+            CitationStatus::Verified => None,
+            CitationStatus::Misquoted { missing } => {
+                let quotes: Vec<String> = missing.iter().map(|quote| format!("\"{}\"", quote)).collect();
+                Some(format!("item {} does not contain {}", self.id, quotes.join(" or ")))
+            }
+            CitationStatus::Misattributed { by } => {
+                Some(format!("item {} was written by {}, not {}", self.id, by, self.named.join(" or ")))
+            }
+            CitationStatus::Unsupported => {
+                Some(format!("removed: item {} is not among the items the answer was based on", self.id))
+            }
+        }
+    }
+}
+
+// The citations in an answer, checked against the items the model was shown
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CitationReport {
+    pub citations: Vec<Citation>,      // in the order they appear
+    pub unsourced_quotes: Vec<String>, // quoted without a citation and found in none of the items
+}
+
+// Something a citation vouches for, found in the same sentence as it
+struct Claim {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl CitationReport {
+    // Find the bracketed item IDs in `answer` and check each against `sources`.
+    // Quotes and authors named in a sentence are attributed to the citation
+    // that follows them in the sentence, or else to the one before them.
+    pub fn verify(answer: &str, sources: &Sources) -> Self {
+        let quotes = find_quotes(answer);
+        let brackets = find_brackets(answer, &quotes);
+        let authors = find_authors(answer, &quotes, &sources.authors());
+        let sentences = sentence_ends(answer, &quotes);
+        let sentence = |at: usize| sentences.partition_point(|&end| end <= at);
+
+        // The index of the bracket a claim is attributed to
+        let attribute = |claim: &Claim| {
+            let in_sentence = || {
+                let claim_sentence = sentence(claim.start);
+                brackets.iter().enumerate().filter(move |(_, (start, _, _))| sentence(*start) == claim_sentence)
+            };
+            let after = in_sentence().find(|(_, (start, _, _))| *start >= claim.end);
+            let before = || in_sentence().rfind(|(_, (_, end, _))| *end <= claim.start);
+            after.or_else(before).map(|(index, _)| index)
+        };
+
+        let mut attributed_quotes = vec![Vec::new(); brackets.len()];
+        let mut named = vec![Vec::new(); brackets.len()];
+        let mut report = CitationReport::default();
+
+        for quote in quotes.iter().filter(|quote| quote.text.split_whitespace().count() >= MIN_QUOTE_WORDS) {
+            match attribute(quote) {
+                Some(index) => attributed_quotes[index].push(quote.text.clone()),
+                None => {
+                    if !sources.items.keys().any(|&id| sources.quotes(id, &quote.text)) {
+                        report.unsourced_quotes.push(quote.text.clone());
+                    }
+                }
+            }
+        }
+        for author in &authors {
+            if let Some(index) = attribute(author) {
+                if !named[index].contains(&author.text) {
+                    named[index].push(author.text.clone());
+                }
+            }
+        }
+
+        for (index, (start, end, ids)) in brackets.iter().enumerate() {
+            let quotes = &attributed_quotes[index];
+            // A quote is fine if any of the items cited together has it
+            let missing: Vec<String> =
+                quotes.iter().filter(|quote| !ids.iter().any(|&id| sources.quotes(id, quote))).cloned().collect();
+            for &id in ids {
+                let named = named[index].clone();
+                let status = if !sources.contains(id) {
+                    CitationStatus::Unsupported
+                } else if !missing.is_empty() {
+                    CitationStatus::Misquoted { missing: missing.clone() }
+                } else if !named.is_empty() && sources.written_by_any(id, &named) == Some(false) {
+                    let by = sources.items[&id].by.clone().unwrap_or_default();
+                    CitationStatus::Misattributed { by }
+                } else {
+                    CitationStatus::Verified
+                };
+                report.citations.push(Citation { id, start: *start, end: *end, status, quotes: quotes.clone(), named });
+            }
+        }
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.citations.is_empty() && self.unsourced_quotes.is_empty()
+    }
+
+    // IDs of the verified citations, each once, in order of first appearance
+    pub fn verified_ids(&self) -> Vec<u32> {
+        let mut ids = Vec::new();
+        for citation in self.citations.iter().filter(|citation| citation.is_verified()) {
+            if !ids.contains(&citation.id) {
+                ids.push(citation.id);
+            }
+        }
+        ids
+    }
+
+    // The citations that failed a check, in order
+    pub fn problems(&self) -> impl Iterator<Item = &Citation> {
+        self.citations.iter().filter(|citation| !citation.is_verified())
+    }
+
+    // `answer` with the text between citations passed through `plain` and each
+    // bracketed group of citations replaced by `render`. A group rendered as
+    // nothing takes the space before it along.
+    pub fn rewrite(
+        &self,
+        answer: &str,
+        mut plain: impl FnMut(&str) -> String,
+        mut render: impl FnMut(&[&Citation]) -> String,
+    ) -> String {
+        let mut output = String::with_capacity(answer.len());
+        let mut position = 0;
+        let mut citations = self.citations.iter().peekable();
+        while let Some(first) = citations.next() {
+            let mut group = vec![first];
+            while let Some(next) = citations.next_if(|next| next.start == first.start) {
+                group.push(next);
+            }
+            output.push_str(&plain(&answer[position..first.start]));
+            let rendered = render(&group);
+            if rendered.is_empty() && output.ends_with(' ') {
+                output.pop();
+            }
+            output.push_str(&rendered);
+            position = first.end;
+        }
+        output.push_str(&plain(&answer[position..]));
+        output
+    }
+}
+
+// Text in straight or curly double quotes
+fn find_quotes(text: &str) -> Vec<Claim> {
+    let mut quotes = Vec::new();
+    let mut open: Option<(usize, char)> = None; // where the open quote starts, and the mark closing it
+    for (at, c) in text.char_indices() {
+        match (open, c) {
+            (None, '"') => open = Some((at, '"')),
+            (None, '“') => open = Some((at, '”')),
+            (Some((start, close)), c) if c == close => {
+                let inner = text[start..at].chars().skip(1).collect::<String>();
+                quotes.push(Claim { start, end: at + c.len_utf8(), text: inner.trim().to_string() });
+                open = None;
+            }
+            // Quotes don't run across paragraphs; an unmatched mark is dropped
+            (Some(_), '\n') => open = None,
+            _ => {}
+        }
+    }
+    quotes
+}
+
+fn inside(quotes: &[Claim], at: usize) -> bool {
+    quotes.iter().any(|quote| quote.start <= at && at < quote.end)
+}
+
+// Brackets holding nothing but item IDs, like [8863] or [8863, 8864], outside
+// quotes and not already the label of a Markdown link: (start, end, IDs)
+fn find_brackets(text: &str, quotes: &[Claim]) -> Vec<(usize, usize, Vec<u32>)> {
+    let mut brackets = Vec::new();
+    for (start, _) in text.match_indices('[') {
+        let Some(length) = text[start..].find(']') else { break };
+        let end = start + length + 1;
+        if inside(quotes, start) || text[end..].starts_with('(') {
+            continue;
+        }
+        let ids: Option<Vec<u32>> =
+            text[start + 1..end - 1].split(',').map(|part| part.trim().trim_start_matches('#').parse().ok()).collect();
+        if let Some(ids) = ids.filter(|ids| !ids.is_empty()) {
+            brackets.push((start, end, ids));
+        }
+    }
+    brackets
+}
+
+// Whole-word mentions of the authors of the sources, outside quotes
+fn find_authors(text: &str, quotes: &[Claim], authors: &[&str]) -> Vec<Claim> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut mentions = Vec::new();
+    for author in authors {
+        for (start, _) in text.match_indices(author) {
+            let end = start + author.len();
+            let before = text[..start].chars().next_back();
+            let after = text[end..].chars().next();
+            if before.is_some_and(is_name_char) || after.is_some_and(is_name_char) || inside(quotes, start) {
+                continue;
+            }
+            mentions.push(Claim { start, end, text: author.to_string() });
+        }
+    }
+    mentions
+}
+
+// Byte offsets where sentences end: after a line break, or after ".", "!" or
+// "?" and a space, outside quotes. A citation right after the full stop still
+// belongs to the sentence before it.
+fn sentence_ends(text: &str, quotes: &[Claim]) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let end = match c {
+            '\n' => at + 1,
+            '.' | '!' | '?' if !inside(quotes, at) && chars.peek().is_some_and(|(_, next)| next.is_whitespace()) => {
+                let rest = text[at + 1..].trim_start_matches([' ', '\t']);
+                match rest.strip_prefix('[').and_then(|bracket| bracket.find(']')) {
+                    Some(close) => text.len() - rest.len() + close + 2,
+                    None => at + 1,
+                }
+            }
+            _ => continue,
+        };
+        ends.push(end);
+    }
+    ends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story};
+
+    // A story by pg, dang's reply to it and tptacek's reply to dang
+    fn sources() -> Sources {
+        let mut sources = Sources::new();
+        sources.add_item(&story(100, "pg", "Beating the Averages", &[101]));
+        sources.add_item(&comment(
+            101,
+            "dang",
+            100,
+            "Lisp macros are the <i>real</i> advantage, in my experience.",
+            &[102],
+        ));
+        sources.add_item(&comment(102, "tptacek", 101, "I don&#x27;t think macros matter that much anymore.", &[]));
+        sources
+    }
+
+    fn verify(answer: &str) -> CitationReport {
+        CitationReport::verify(answer, &sources())
+    }
+
+    fn statuses(report: &CitationReport) -> Vec<(u32, CitationStatus)> {
+        report.citations.iter().map(|citation| (citation.id, citation.status.clone())).collect()
+    }
+
+    #[test]
+    fn verified_quote_and_author() {
+        let report = verify("dang said \"macros are the real advantage\" [101].");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Verified)]);
+        assert_eq!(report.citations[0].quotes, ["macros are the real advantage"]);
+        assert_eq!(report.citations[0].named, ["dang"]);
+        assert_eq!(report.verified_ids(), [101]);
+        assert_eq!(report.problems().count(), 0);
+    }
+
+    #[test]
+    fn misquoted() {
+        let report = verify("dang said \"macros are the only advantage\" [101].");
+        let missing = vec!["macros are the only advantage".to_string()];
+        assert_eq!(statuses(&report), [(101, CitationStatus::Misquoted { missing })]);
+        assert!(report.citations[0].is_kept());
+        assert_eq!(
+            report.citations[0].problem().unwrap(),
+            "item 101 does not contain \"macros are the only advantage\""
+        );
+    }
+
+    #[test]
+    fn misattributed() {
+        let report = verify("tptacek said \"macros are the real advantage\" [101].");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Misattributed { by: "dang".to_string() })]);
+        assert_eq!(report.citations[0].problem().unwrap(), "item 101 was written by dang, not tptacek");
+    }
+
+    #[test]
+    fn the_parent_author_is_not_the_author() {
+        let report = verify("dang said \"macros matter that much\" [102].");
+        assert_eq!(statuses(&report), [(102, CitationStatus::Misattributed { by: "tptacek".to_string() })]);
+    }
+
+    #[test]
+    fn unsupported() {
+        let report = verify("Lisp is fast [999], says pg [100].");
+        assert_eq!(statuses(&report), [(999, CitationStatus::Unsupported), (100, CitationStatus::Verified)]);
+        assert!(!report.citations[0].is_kept());
+    }
+
+    #[test]
+    fn grouped_citations() {
+        let report = verify("Some doubt that \"macros matter that much\" [101, 102].");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Verified), (102, CitationStatus::Verified)]);
+        assert_eq!(report.citations[0].start, report.citations[1].start);
+        assert_eq!(report.citations[0].end, report.citations[1].end);
+
+        let report = verify("Nobody said \"macros are useless\" [#101,#102].");
+        assert!(report.citations.iter().all(|citation| matches!(citation.status, CitationStatus::Misquoted { .. })));
+    }
+
+    #[test]
+    fn quotes_with_ellipses() {
+        let report = verify("\"Lisp macros ... in my experience\" [101]");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Verified)]);
+        let report = verify("“Lisp macros … the real advantage” [101]");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Verified)]);
+        let report = verify("\"Lisp macros ... in his experience\" [101]");
+        assert!(matches!(report.citations[0].status, CitationStatus::Misquoted { .. }));
+    }
+
+    #[test]
+    fn citation_after_the_full_stop() {
+        let report = verify("dang said \"macros are the real advantage\". [101] tptacek disagreed [102].");
+        assert_eq!(statuses(&report), [(101, CitationStatus::Verified), (102, CitationStatus::Verified)]);
+        assert_eq!(report.citations[0].named, ["dang"]);
+        assert_eq!(report.citations[1].named, ["tptacek"]);
+        assert!(report.citations[1].quotes.is_empty());
+    }
+
+    #[test]
+    fn claims_go_to_the_citation_in_their_sentence() {
+        // The quote follows the citation, but is in its sentence
+        let report = verify("Per [102], \"macros matter that much\" no more. dang disagreed [101].");
+        assert_eq!(statuses(&report), [(102, CitationStatus::Verified), (101, CitationStatus::Verified)]);
+        assert_eq!(report.citations[0].quotes, ["macros matter that much"]);
+    }
+
+    #[test]
+    fn quotes_without_citations() {
+        let report = verify("Someone said \"nothing like this anywhere\", and \"macros matter that much\".");
+        assert!(report.citations.is_empty());
+        assert_eq!(report.unsourced_quotes, ["nothing like this anywhere"]);
+        // One-word quotes are terms, not quotations
+        assert!(verify("It's \"blub\" [101].").citations[0].is_verified());
+    }
+
+    #[test]
+    fn only_bracketed_ids_are_citations() {
+        let report =
+            verify("See [the essay] and [100](https://news.ycombinator.com/item?id=100), \"not [101] this\" or [1a].");
+        assert!(report.citations.is_empty());
+    }
+
+    #[test]
+    fn rewrite_renders_each_group() {
+        let answer = "Lisp wins [100]. Also [999] and [101, 999].";
+        let report = verify(answer);
+        let rewritten = report.rewrite(answer, str::to_uppercase, |group| {
+            let kept: Vec<String> =
+                group.iter().filter(|citation| citation.is_kept()).map(|citation| citation.id.to_string()).collect();
+            if kept.is_empty() {
+                String::new()
+            } else {
+                format!("<{}>", kept.join(","))
+            }
+        });
+        assert_eq!(rewritten, "LISP WINS <100>. ALSO AND <101>.");
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use rig_hn_assistant::algolia::AlgoliaSort;
+use rig_hn_assistant::client::StoryType;
+use rig_hn_assistant::search::SearchBackend;
+
+use crate::llm::Provider;
+
+#[derive(Debug, Parser)]
+#[command(name = "rig-hn-assistant", version, about = "Search and discuss Hacker News from the terminal")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>, // defaults to `chat`
+
+    /// Config file to read [default: $HN_CONFIG or config.toml in the user config dir]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from the config file [env: HN_PROFILE]
+    #[arg(long, short = 'p', global = true)]
+    pub profile: Option<String>,
+
+    /// LLM provider used by `ask` and `chat` [default: openai]
+    #[arg(long, global = true, value_enum)]
+    pub provider: Option<Provider>,
+
+    /// Chat model [default: depends on the provider]
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+
+    /// Base URL of an OpenAI-compatible server, e.g. http://localhost:11434/v1 for Ollama
+    #[arg(long, global = true)]
+    pub base_url: Option<String>,
+
+    /// Sampling temperature
+    #[arg(long, global = true)]
+    pub temperature: Option<f64>,
+
+    /// Maximum number of tokens in each reply
+    #[arg(long, global = true)]
+    pub max_tokens: Option<u64>,
+
+    /// How to print results [default: text]
+    #[arg(long, short = 'f', global = true, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write results to a file instead of stdout
+    #[arg(long, short = 'o', global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Directory holding the HN cache database [default: $HN_CACHE_DIR or the user cache dir]
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// SQLite archive written by `sync` [default: $HN_ARCHIVE_PATH or the user data dir]
+    #[arg(long, global = true)]
+    pub archive: Option<PathBuf>,
+
+    /// Only answer from the local cache and archive, never the network (the algolia backend is unavailable)
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Pull archived stories and comments related to each prompt into it, and cite them [env: HN_RAG]
+    #[arg(long, global = true)]
+    pub rag: bool,
+
+    /// Print diagnostics such as cache statistics to stderr
+    #[arg(long, short = 'v', global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Ask the assistant a single question
+    Ask {
+        #[arg(required = true, num_args = 1..)]
+        prompt: Vec<String>,
+    },
+    /// Search HN directly, without the LLM
+    Search {
+        query: String,
+        /// Story list to search: top, best, new, ask, show or job
+        #[arg(long = "type", value_parser = parse_lowercase::<StoryType>)]
+        story_type: Option<StoryType>,
+        /// Maximum number of results
+        #[arg(long = "max")]
+        max_results: Option<i32>,
+        /// feed (current story lists), algolia (full history) or archive (local mirror kept by sync)
+        #[arg(long, value_parser = parse_lowercase::<SearchBackend>)]
+        backend: Option<SearchBackend>,
+        /// relevance or date, algolia and archive only
+        #[arg(long, value_parser = parse_lowercase::<AlgoliaSort>)]
+        sort: Option<AlgoliaSort>,
+        #[arg(long)]
+        min_points: Option<u32>,
+        #[arg(long)]
+        min_comments: Option<u32>,
+        /// YYYY-MM-DD, algolia and archive only
+        #[arg(long)]
+        after: Option<String>,
+        /// YYYY-MM-DD, algolia and archive only
+        #[arg(long)]
+        before: Option<String>,
+        /// Result page, algolia only
+        #[arg(long)]
+        page: Option<u32>,
+    },
+    /// Search the local archive by meaning, merged with keyword matches from the index
+    Semantic {
+        query: String,
+        /// Maximum number of results
+        #[arg(long = "max")]
+        max_results: Option<i32>,
+        /// Rank by meaning alone
+        #[arg(long)]
+        no_hybrid: bool,
+    },
+    /// Show an item with the thread above it and its first replies
+    Item {
+        /// Item ID or news.ycombinator.com link
+        item: String,
+        /// Number of direct replies to show [default: max_children in [lookup], or 5]
+        #[arg(long)]
+        children: Option<usize>,
+    },
+    /// Show a user profile and their recent submissions
+    User {
+        /// Username or news.ycombinator.com/user link
+        name: String,
+        /// Number of recent submissions to show per page [default: page_size in [lookup], or 10]
+        #[arg(long)]
+        submissions: Option<usize>,
+        /// Page of submissions to show, starting at 0
+        #[arg(long, default_value_t = 0)]
+        page: usize,
+    },
+    /// Show the discussion under an item
+    Thread {
+        id: u32,
+        /// Levels of replies to fetch
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Comments to fetch in total
+        #[arg(long)]
+        max_comments: Option<usize>,
+        /// Summarize the discussion with the model instead of printing it
+        #[arg(long)]
+        summarize: bool,
+    },
+    /// Mirror HN into the local archive: new items, older ones back to the horizon, then updates
+    Sync {
+        /// How many days back to backfill [default: from the config, 30]
+        #[arg(long)]
+        horizon_days: Option<u64>,
+        /// Stop after this many item IDs; run again to continue
+        #[arg(long)]
+        max_items: Option<usize>,
+        /// Don't refetch the items and profiles HN lists as recently changed
+        #[arg(long)]
+        no_updates: bool,
+        /// Don't embed new stories and comments for semantic search
+        #[arg(long)]
+        no_embed: bool,
+    },
+    /// Chat with the assistant interactively
+    Chat,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration, with secrets redacted
+    Show,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text, // the report shown in the terminal
+    Json,   // one pretty-printed document
+    Ndjson, // one JSON record per line
+    #[value(alias = "md")]
+    #[serde(alias = "md")]
+    Markdown, // GitHub-flavored
+    Html,   // a standalone page
+    Csv,    // item metadata, one row per item
+}
+
+// Parse a value by its serde name, so the library enums don't need to know about clap
+fn parse_lowercase<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase())).map_err(|e| e.to_string())
+}
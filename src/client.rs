@@ -0,0 +1,314 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::error::HNError;
+use crate::item::{Item, Updates, User};
+use crate::policy::RequestPolicy;
+
+pub const HN_API_BASE: &str = "https://hacker-news.firebaseio.com/v0";
+
+// The ranking lists exposed by the HN API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoryType {
+    #[default]
+    Top,
+    Best,
+    New,
+    Ask,
+    Show,
+    Job,
+}
+
+impl StoryType {
+    // Name of the endpoint listing stories of this type, without the .json suffix
+    pub fn endpoint(self) -> &'static str {
+        match self {
+            StoryType::Top => "topstories",
+            StoryType::Best => "beststories",
+            StoryType::New => "newstories",
+            StoryType::Ask => "askstories",
+            StoryType::Show => "showstories",
+            StoryType::Job => "jobstories",
+        }
+    }
+}
+
+// Read access to the HackerNews API. Implemented over HTTP by `ReqwestClient`;
+// tests and alternative backends can provide their own implementation. Futures
+// are `Send + Sync` because rig requires that of tool calls.
+pub trait HnClient: Send + Sync {
+    // IDs of the stories in the given ranking list, in ranking order
+    fn list_stories(&self, story_type: StoryType) -> impl Future<Output = Result<Vec<u32>, HNError>> + Send + Sync;
+
+    // A single item, or `None` if the API has no item with that ID
+    fn get_item(&self, id: u32) -> impl Future<Output = Result<Option<Item>, HNError>> + Send + Sync;
+
+    // A user profile, or `None` if the user does not exist
+    fn get_user(&self, username: &str) -> impl Future<Output = Result<Option<User>, HNError>> + Send + Sync;
+
+    // The largest item ID handed out so far
+    fn get_max_item(&self) -> impl Future<Output = Result<u32, HNError>> + Send + Sync;
+
+    // Items and profiles that changed recently
+    fn get_updates(&self) -> impl Future<Output = Result<Updates, HNError>> + Send + Sync;
+}
+
+// `HnClient` backed by the Firebase HTTP API, or anything that mirrors it
+#[derive(Debug, Clone)]
+pub struct ReqwestClient {
+    http: reqwest::Client,
+    base_url: String,
+    policy: RequestPolicy,
+}
+
+impl ReqwestClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            policy: RequestPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RequestPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Uses `HN_API_BASE_URL` if set, otherwise the public Firebase endpoint
+    pub fn from_env() -> Self {
+        match std::env::var("HN_API_BASE_URL") {
+            Ok(base_url) => Self::new(&base_url),
+            Err(_) => Self::default(),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, HNError> {
+        let url = format!("{}/{}.json", self.base_url, path);
+        self.policy.get_json(&self.http, &url, &[]).await
+    }
+}
+
+impl Default for ReqwestClient {
+    fn default() -> Self {
+        Self::new(HN_API_BASE)
+    }
+}
+
+impl HnClient for ReqwestClient {
+    async fn list_stories(&self, story_type: StoryType) -> Result<Vec<u32>, HNError> {
+        self.get_json(story_type.endpoint()).await
+    }
+
+    async fn get_item(&self, id: u32) -> Result<Option<Item>, HNError> {
+        self.get_json(&format!("item/{}", id)).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, HNError> {
+        self.get_json(&format!("user/{}", username)).await
+    }
+
+    async fn get_max_item(&self) -> Result<u32, HNError> {
+        self.get_json("maxitem").await
+    }
+
+    async fn get_updates(&self) -> Result<Updates, HNError> {
+        self.get_json("updates").await
+    }
+}
+
+// Lets several tools share one client, and its cache, without cloning it
+impl<C: HnClient> HnClient for Arc<C> {
+    async fn list_stories(&self, story_type: StoryType) -> Result<Vec<u32>, HNError> {
+        self.as_ref().list_stories(story_type).await
+    }
+
+    async fn get_item(&self, id: u32) -> Result<Option<Item>, HNError> {
+        self.as_ref().get_item(id).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, HNError> {
+        self.as_ref().get_user(username).await
+    }
+
+    async fn get_max_item(&self) -> Result<u32, HNError> {
+        self.as_ref().get_max_item().await
+    }
+
+    async fn get_updates(&self) -> Result<Updates, HNError> {
+        self.as_ref().get_updates().await
+    }
+}
+
+// A map-backed `HnClient` for tests, which counts the requests it serves and
+// how many of them were in flight at once
+#[cfg(test)]
+pub(crate) mod memory {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::item::{Comment, Story};
+
+    #[derive(Debug, Clone, Default)]
+    pub struct MemoryClient {
+        items: HashMap<u32, Item>,
+        users: HashMap<String, User>,
+        lists: HashMap<StoryType, Vec<u32>>,
+        failing: HashSet<u32>, // items whose fetch fails with a 503
+        updated: Vec<u32>,     // what /updates.json lists
+        updated_profiles: Vec<String>,
+        delay: Duration, // how long each item fetch takes
+        requests: Arc<AtomicUsize>,
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl MemoryClient {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn with_item(mut self, item: Item) -> Self {
+            self.items.insert(item.id(), item);
+            self
+        }
+
+        pub fn with_items(self, items: impl IntoIterator<Item = Item>) -> Self {
+            items.into_iter().fold(self, |client, item| client.with_item(item))
+        }
+
+        pub fn with_user(mut self, user: User) -> Self {
+            self.users.insert(user.id.clone(), user);
+            self
+        }
+
+        pub fn with_list(mut self, story_type: StoryType, ids: Vec<u32>) -> Self {
+            self.lists.insert(story_type, ids);
+            self
+        }
+
+        pub fn with_failure(mut self, id: u32) -> Self {
+            self.failing.insert(id);
+            self
+        }
+
+        pub fn with_updates(mut self, items: Vec<u32>, profiles: Vec<String>) -> Self {
+            self.updated = items;
+            self.updated_profiles = profiles;
+            self
+        }
+
+        pub fn with_delay(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        // Requests served so far, by every clone of this client
+        pub fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+
+        // Most item fetches that were running at the same time
+        pub fn max_in_flight(&self) -> usize {
+            self.max_in_flight.load(Ordering::SeqCst)
+        }
+    }
+
+    impl HnClient for MemoryClient {
+        async fn list_stories(&self, story_type: StoryType) -> Result<Vec<u32>, HNError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(self.lists.get(&story_type).cloned().unwrap_or_default())
+        }
+
+        async fn get_item(&self, id: u32) -> Result<Option<Item>, HNError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let running = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(running, Ordering::SeqCst);
+            if !self.delay.is_zero() {
+                tokio::time::sleep(self.delay).await;
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if self.failing.contains(&id) {
+                return Err(HNError::HttpStatus { url: format!("item/{}", id), status: 503 });
+            }
+            Ok(self.items.get(&id).cloned())
+        }
+
+        async fn get_user(&self, username: &str) -> Result<Option<User>, HNError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(self.users.get(username).cloned())
+        }
+
+        async fn get_max_item(&self) -> Result<u32, HNError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(self.items.keys().copied().max().unwrap_or(0))
+        }
+
+        async fn get_updates(&self) -> Result<Updates, HNError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(Updates { items: self.updated.clone(), profiles: self.updated_profiles.clone() })
+        }
+    }
+
+    pub fn story(id: u32, by: &str, title: &str, kids: &[u32]) -> Item {
+        Item::Story(Story {
+            id,
+            deleted: false,
+            dead: false,
+            title: Some(title.to_string()),
+            url: None,
+            text: None,
+            by: Some(by.to_string()),
+            score: Some(1),
+            descendants: Some(kids.len() as i32),
+            time: 1_700_000_000 + i64::from(id),
+            kids: Some(kids.to_vec()),
+        })
+    }
+
+    pub fn comment(id: u32, by: &str, parent: u32, text: &str, kids: &[u32]) -> Item {
+        Item::Comment(Comment {
+            id,
+            deleted: false,
+            dead: false,
+            text: Some(text.to_string()),
+            by: Some(by.to_string()),
+            time: 1_700_000_000 + i64::from(id),
+            parent,
+            kids: Some(kids.to_vec()),
+        })
+    }
+
+    #[tokio::test]
+    async fn serves_what_it_holds() {
+        let client = MemoryClient::new()
+            .with_items([story(1, "pg", "Hello", &[2]), comment(2, "dang", 1, "Hi", &[])])
+            .with_list(StoryType::Top, vec![1])
+            .with_failure(3);
+
+        assert_eq!(client.list_stories(StoryType::Top).await.unwrap(), vec![1]);
+        assert_eq!(client.get_item(2).await.unwrap().unwrap().by(), Some("dang"));
+        assert!(client.get_item(4).await.unwrap().is_none());
+        assert!(client.get_item(3).await.unwrap_err().is_transient());
+        assert_eq!(client.get_max_item().await.unwrap(), 2);
+        assert_eq!(client.requests(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tracks_concurrent_fetches() {
+        let user = User { id: "pg".to_string(), created: 0, karma: 1, about: None, submitted: None };
+        let client = MemoryClient::new().with_user(user).with_delay(Duration::from_millis(10));
+
+        futures::future::join_all((0..3).map(|id| client.get_item(id))).await;
+        assert_eq!(client.max_in_flight(), 3);
+        client.get_item(0).await.unwrap();
+        assert_eq!(client.max_in_flight(), 3);
+        assert_eq!(client.get_user("pg").await.unwrap().unwrap().karma, 1);
+    }
+}
@@ -0,0 +1,157 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Text measured in terminal columns rather than bytes or chars: emoji and CJK
+// take two columns, combining marks none, and a grapheme is never split.
+
+// Width of the terminal on stdout, if it is one
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+// `text` cut to at most `max_width` columns, ending in "..." if anything was cut
+pub fn truncate(text: &str, max_width: usize) -> String {
+    if width(text) <= max_width {
+        return text.to_string();
+    }
+    let mut output = take_width(text, max_width.saturating_sub(3)).to_string();
+    output.push_str(&".".repeat(max_width.min(3)));
+    output
+}
+
+// `text` cut or padded with spaces to exactly `column` columns
+pub fn pad(text: &str, column: usize) -> String {
+    let mut output = truncate(text, column);
+    let used = width(&output);
+    output.push_str(&" ".repeat(column.saturating_sub(used)));
+    output
+}
+
+// The longest prefix of whole graphemes that fits in `max_width` columns
+fn take_width(text: &str, max_width: usize) -> &str {
+    let mut used = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > max_width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+// Break text into lines of at most `max_width` columns, at spaces where possible.
+// Blank lines are kept, and indented lines (code) are only cut, never rejoined.
+pub fn wrap(text: &str, max_width: usize) -> Vec<String> {
+    let max_width = max_width.max(1);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            lines.push(truncate(line, max_width));
+            continue;
+        }
+
+        let start = lines.len();
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            let needed = if current.is_empty() { width(word) } else { width(&current) + 1 + width(word) };
+            if needed <= max_width {
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.push_str(word);
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            // A word wider than a whole line, such as a long URL, is split across lines
+            let mut rest = word;
+            while width(rest) > max_width {
+                let head = take_width(rest, max_width);
+                let head = if head.is_empty() { rest.graphemes(true).next().unwrap_or(rest) } else { head };
+                lines.push(head.to_string());
+                rest = &rest[head.len()..];
+            }
+            current.push_str(rest);
+        }
+        // Blank lines are kept, but a split that used up the last word leaves nothing over
+        if !current.is_empty() || lines.len() == start {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "👨\u{200d}👩\u{200d}👧"; // one grapheme joined by zero-width joiners
+    const E_ACUTE: &str = "e\u{301}"; // "e" and a combining acute accent
+
+    #[test]
+    fn ascii() {
+        assert_eq!(truncate("hello world", 11), "hello world");
+        assert_eq!(truncate("hello world", 8), "hello...");
+        assert_eq!(pad("hi", 4), "hi  ");
+        assert_eq!(pad("hello world", 8), "hello...");
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(truncate("日本語のテキスト", 7), "日本...");
+        // Half a character doesn't fit, so the cut falls before it
+        assert_eq!(truncate("日本語のテキスト", 8), "日本...");
+        assert_eq!(pad("日本語", 4), "... ");
+        assert_eq!(pad("日本", 5), "日本 ");
+    }
+
+    #[test]
+    fn emoji_sequences_are_never_split() {
+        assert_eq!(width(FAMILY), 2);
+        let text = format!("{}{}{}", FAMILY, FAMILY, FAMILY);
+        assert_eq!(truncate(&text, 5), format!("{}...", FAMILY));
+        assert_eq!(truncate(&text, 4), "...");
+        assert_eq!(wrap(&text, 3), [FAMILY, FAMILY, FAMILY]);
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_letter() {
+        let text = E_ACUTE.repeat(5);
+        assert_eq!(width(&text), 5);
+        assert_eq!(truncate(&text, 5), text);
+        assert_eq!(truncate(&text, 4), format!("{}...", E_ACUTE));
+        assert_eq!(wrap(&text, 2), [E_ACUTE.repeat(2), E_ACUTE.repeat(2), E_ACUTE.to_string()]);
+    }
+
+    #[test]
+    fn tiny_widths() {
+        assert_eq!(truncate("abc", 0), "");
+        assert_eq!(truncate("abc", 1), ".");
+        assert_eq!(truncate("abcd", 2), "..");
+        assert_eq!(truncate("", 0), "");
+        assert_eq!(pad("abc", 0), "");
+        assert_eq!(wrap("ab cd", 0), ["a", "b", "c", "d"]);
+        // A wide character on a one-column line gets a line of its own
+        assert_eq!(wrap("日本", 1), ["日", "本"]);
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("one\n\ntwo", 10), ["one", "", "two"]);
+        assert_eq!(wrap("    let x = a_long_line_of_code;", 12), ["    let x..."]);
+    }
+
+    #[test]
+    fn splits_words_wider_than_a_line() {
+        let lines = wrap("see https://example.com/a/long/path now", 10);
+        assert_eq!(lines, ["see", "https://ex", "ample.com/", "a/long/pat", "h now"]);
+        assert_eq!(wrap("日本語のテキスト", 5), ["日本", "語の", "テキ", "スト"]);
+    }
+}
@@ -0,0 +1,75 @@
+#[derive(Debug, thiserror::Error)]
+pub enum HNError {
+    #[error("Network error while accessing HackerNews API: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("No matching stories found. Try broadening your search terms or searching different story types (top, new, best, etc.)")]
+    NoResults,
+    #[error("API error: {0}")]
+    ApiError(String),
+    #[error("Request to {0} timed out")]
+    Timeout(String),
+    #[error("{url} returned HTTP {status}")]
+    HttpStatus { url: String, status: u16 },
+    #[error("Invalid search query: {0}. Query syntax: {}", crate::query::QUERY_SYNTAX)]
+    InvalidQuery(#[from] crate::query::QueryError),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Failed to decode HackerNews API response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("Cache error: {0}")]
+    Cache(String),
+    #[error("Cache database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Search index error: {0}")]
+    Index(#[from] tantivy::TantivyError),
+    #[error("Embedding error: {0}")]
+    Embedding(#[from] rig::embeddings::EmbeddingError),
+    #[error("Vector store error: {0}")]
+    VectorStore(#[from] rig::vector_store::VectorStoreError),
+    #[error("{0} is not in the local cache and offline mode is enabled")]
+    Offline(String),
+    #[error("{0} does not exist on Hacker News")]
+    NotFound(String),
+    #[error("Model request failed: {0}")]
+    Model(String),
+}
+
+impl HNError {
+    // Errors that only affect a single item, so a search can skip the item and go on
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            HNError::Network(_)
+                | HNError::Timeout(_)
+                | HNError::HttpStatus { .. }
+                | HNError::Decode(_)
+                | HNError::ApiError(_)
+                | HNError::Offline(_)
+        )
+    }
+
+    // Errors worth retrying: timeouts, dropped connections and server-side failures
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HNError::Timeout(_) => true,
+            HNError::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            HNError::Network(e) => e.is_connect() || e.is_request() || e.is_body(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn from_reqwest(error: reqwest::Error, url: &str) -> Self {
+        if error.is_timeout() {
+            HNError::Timeout(url.to_string())
+        } else {
+            HNError::Network(error)
+        }
+    }
+}
+
+// An item a search had to leave out, and why
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SkippedItem {
+    pub id: u32,
+    pub reason: String,
+}
@@ -0,0 +1,543 @@
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use rig_hn_assistant::{
+    archive::ArchiveStats,
+    citation::{Citation, CitationReport, Sources},
+    item::{Comment, Item, User},
+    lookup::{ItemContext, UserProfile},
+    rag::{ContextPassage, CONTEXT_SOURCE},
+    record::ToolCall,
+    search::{SearchOutput, SearchResult},
+    summarize::ThreadSummary,
+    sync::SyncReport,
+    tree::{CommentNode, CommentTree},
+};
+
+use crate::display::{self, pad, truncate, wrap};
+
+// Fixed columns of the results table; the title gets the rest of the width
+const AUTHOR_WIDTH: usize = 15;
+const NUMBER_WIDTH: usize = 8;
+const COMMENTS_WIDTH: usize = 8;
+const MIN_TITLE_WIDTH: usize = 20;
+
+// Narrowest column comment text is wrapped to, however deep the reply
+const MIN_TEXT_WIDTH: usize = 40;
+
+// Width used when the output is not a terminal
+const DEFAULT_WIDTH: usize = 120;
+
+// Column widths for the text output, in terminal columns
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct TableLayout {
+    pub width: usize,       // of rules, headings and wrapped text
+    pub title_width: usize, // of the title column in the results table
+}
+
+impl TableLayout {
+    // Give the title column whatever the other columns leave of `width`
+    pub fn for_width(width: usize) -> Self {
+        let others = AUTHOR_WIDTH + NUMBER_WIDTH + COMMENTS_WIDTH + 3 * " | ".len();
+        Self { width, title_width: width.saturating_sub(others).max(MIN_TITLE_WIDTH) }
+    }
+
+    // Fit the terminal, if the output goes to one
+    pub fn detect() -> Self {
+        Self::for_width(display::terminal_width().unwrap_or(DEFAULT_WIDTH))
+    }
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self::for_width(DEFAULT_WIDTH)
+    }
+}
+
+pub fn format_hn_results(search: &SearchOutput, layout: &TableLayout) -> Result<String> {
+    let results = &search.results;
+    let (width, title_width) = (layout.width, layout.title_width);
+    let mut output = String::new();
+
+    writeln!(&mut output, "\n{:-^width$}", " Hacker News Discussions ")?;
+    writeln!(
+        &mut output,
+        "{} | {} | {} | Comments",
+        pad("Title", title_width),
+        pad("Author", AUTHOR_WIDTH),
+        pad("Points", NUMBER_WIDTH)
+    )?;
+    writeln!(&mut output, "{:-<width$}", "")?;
+
+    for SearchResult { item, .. } in results {
+        writeln!(
+            &mut output,
+            "{} | {} | {} | {}",
+            pad(&display_title(item), title_width),
+            pad(item.by().unwrap_or("[unknown]"), AUTHOR_WIDTH),
+            pad(&item.score().unwrap_or(0).to_string(), NUMBER_WIDTH),
+            item.descendants().unwrap_or(0)
+        )?;
+    }
+
+    writeln!(&mut output, "\n{:-^width$}", " Detailed Discussion View ")?;
+
+    for (i, SearchResult { item, comments, score, highlights }) in results.iter().enumerate() {
+        writeln!(&mut output, "\n{}. {}", i + 1, display_title(item))?;
+        writeln!(
+            &mut output,
+            "Type: {} | By: {} | Points: {} | ID: {} | Relevance: {:.2}",
+            item.kind(),
+            item.by().unwrap_or("[unknown]"),
+            item.score().unwrap_or(0),
+            item.id(),
+            score
+        )?;
+
+        if let Some(url) = item.url() {
+            writeln!(&mut output, "URL: {}", url)?;
+        }
+        if let Some(text) = item.text() {
+            writeln!(&mut output, "\nText:")?;
+            write_wrapped(&mut output, text, "", width)?;
+            writeln!(&mut output)?;
+        }
+        if let Item::Poll(poll) = item {
+            let options = poll.parts.as_deref().unwrap_or_default();
+            writeln!(&mut output, "Poll options: {}", options.len())?;
+        }
+        if !highlights.is_empty() {
+            writeln!(&mut output, "\nMatches:")?;
+            for highlight in highlights {
+                write_wrapped(&mut output, &format!("... {} ...", highlight), "   ", width)?;
+            }
+        }
+
+        if !comments.is_empty() {
+            writeln!(&mut output, "\nTop Comments:")?;
+            for (j, comment) in comments.iter().enumerate() {
+                writeln!(&mut output, "\n{}.{} by {}:", i + 1, j + 1, comment.by.as_deref().unwrap_or("[unknown]"))?;
+                write_wrapped(&mut output, &comment_body(comment), "   ", width)?;
+                writeln!(&mut output)?;
+            }
+        }
+        writeln!(&mut output, "{:-<width$}", "")?;
+    }
+
+    if search.timed_out {
+        writeln!(&mut output, "\nNote: the search hit its time limit, so these results may be incomplete.")?;
+    }
+    if !search.skipped.is_empty() {
+        writeln!(&mut output, "\nSkipped {} item(s) that could not be fetched:", search.skipped.len())?;
+        for skipped in &search.skipped {
+            writeln!(&mut output, "  {}: {}", skipped.id, skipped.reason)?;
+        }
+    }
+
+    Ok(output)
+}
+
+// Title to show for an item, with a marker for deleted and dead items
+pub fn display_title(item: &Item) -> String {
+    let title = item.title().unwrap_or("[untitled]");
+    if item.is_deleted() {
+        "[deleted]".to_string()
+    } else if item.is_dead() {
+        format!("[dead] {}", title)
+    } else {
+        title.to_string()
+    }
+}
+
+// The model's reply to one prompt, together with the tool calls it made on the way
+#[derive(Debug, Serialize)]
+pub struct Answer {
+    pub response: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub citations: CitationReport, // the reply's citations checked against the tools' HN data
+}
+
+impl Answer {
+    pub fn new(response: String, tool_calls: Vec<ToolCall>) -> Result<Self> {
+        let mut answer = Answer { response, tool_calls, citations: CitationReport::default() };
+        if let Some(commentary) = answer.commentary() {
+            answer.citations = CitationReport::verify(commentary, &answer.sources()?);
+        }
+        Ok(answer)
+    }
+
+    // Every item the tools showed the model
+    fn sources(&self) -> Result<Sources> {
+        let mut sources = Sources::new();
+        for call in &self.tool_calls {
+            match ToolOutput::decode(call)? {
+                ToolOutput::Search(search) => sources.add_search(&search),
+                ToolOutput::Item(context) => sources.add_item_context(&context),
+                ToolOutput::User(profile) => sources.add_user_profile(&profile),
+                ToolOutput::Summary(summary) => sources.add_summary(&summary),
+                ToolOutput::Context(passages) => passages.iter().for_each(|passage| sources.add_passage(passage)),
+                ToolOutput::Other(_) => {}
+            }
+        }
+        Ok(sources)
+    }
+
+    // The model's own words, unless the reply is just a tool's output passed through
+    pub fn commentary(&self) -> Option<&str> {
+        let echoed = serde_json::from_str::<serde_json::Value>(&self.response)
+            .is_ok_and(|reply| self.tool_calls.iter().any(|call| call.output == reply));
+        (!echoed && !self.response.trim().is_empty()).then_some(self.response.as_str())
+    }
+}
+
+// A recorded tool call decoded into the tool's own output type
+pub enum ToolOutput {
+    Search(SearchOutput),
+    Item(ItemContext),
+    User(UserProfile),
+    Summary(ThreadSummary),
+    Context(Vec<ContextPassage>), // archived passages pulled into the prompt in RAG mode
+    Other(serde_json::Value),     // from a tool the CLI has no display for
+}
+
+impl ToolOutput {
+    pub fn decode(call: &ToolCall) -> Result<Self> {
+        let output = match call.tool.as_str() {
+            "search_hn" | "semantic_search_hn" => call.output_as().map(ToolOutput::Search),
+            "get_item" => call.output_as().map(ToolOutput::Item),
+            "get_user" => call.output_as().map(ToolOutput::User),
+            "summarize_thread" => call.output_as().map(ToolOutput::Summary),
+            CONTEXT_SOURCE => call.output_as().map(ToolOutput::Context),
+            _ => Ok(ToolOutput::Other(call.output.clone())),
+        };
+        output.with_context(|| format!("unexpected output from the {} tool", call.tool))
+    }
+}
+
+// The HN data each tool returned, followed by the model's commentary on it
+pub fn format_answer(answer: &Answer, layout: &TableLayout) -> Result<String> {
+    let mut output = String::new();
+
+    for call in &answer.tool_calls {
+        match ToolOutput::decode(call)? {
+            ToolOutput::Search(search) => output.push_str(&format_hn_results(&search, layout)?),
+            ToolOutput::Item(context) => output.push_str(&format_item_context(&context, layout)?),
+            ToolOutput::User(profile) => output.push_str(&format_user_profile(&profile)?),
+            ToolOutput::Summary(summary) => output.push_str(&format_thread_summary(&summary, layout)?),
+            ToolOutput::Context(passages) => output.push_str(&format_context(&passages, layout)?),
+            ToolOutput::Other(value) => writeln!(&mut output, "{}", serde_json::to_string_pretty(&value)?)?,
+        }
+    }
+
+    if let Some(commentary) = answer.commentary() {
+        let commentary = answer.citations.rewrite(commentary, str::to_string, |group| {
+            let kept: Vec<String> = group.iter().copied().filter(|citation| citation.is_kept()).map(cite).collect();
+            if kept.is_empty() {
+                String::new()
+            } else {
+                format!("[{}]", kept.join(", "))
+            }
+        });
+        writeln!(&mut output, "\n{}\n", commentary)?;
+        output.push_str(&format_citations(&answer.citations)?);
+    }
+
+    Ok(output)
+}
+
+// A citation's ID, marked with "?" if checking it found a problem
+fn cite(citation: &Citation) -> String {
+    match citation.is_verified() {
+        true => citation.id.to_string(),
+        false => format!("{}?", citation.id),
+    }
+}
+
+// Links to the verified citations, then whatever failed the check
+fn format_citations(report: &CitationReport) -> Result<String> {
+    let mut output = String::new();
+
+    let verified = report.verified_ids();
+    if !verified.is_empty() {
+        writeln!(&mut output, "Sources:")?;
+        for id in verified {
+            writeln!(&mut output, "  [{}] https://news.ycombinator.com/item?id={}", id, id)?;
+        }
+        writeln!(&mut output)?;
+    }
+    let problems: Vec<String> = report.problems().filter_map(|citation| citation.problem()).collect();
+    if !problems.is_empty() || !report.unsourced_quotes.is_empty() {
+        writeln!(&mut output, "Unverified citations:")?;
+        for problem in problems {
+            writeln!(&mut output, "  {}", problem)?;
+        }
+        for quote in &report.unsourced_quotes {
+            writeln!(&mut output, "  \"{}\" is not in any item the answer was based on", quote)?;
+        }
+    }
+
+    Ok(output)
+}
+
+pub fn format_item(item: &Item, layout: &TableLayout) -> Result<String> {
+    let mut output = String::new();
+
+    writeln!(&mut output, "{}", display_title(item))?;
+    writeln!(
+        &mut output,
+        "Type: {} | By: {} | Points: {} | Comments: {} | ID: {} | Posted: {}",
+        item.kind(),
+        item.by().unwrap_or("[unknown]"),
+        item.score().unwrap_or(0),
+        item.descendants().unwrap_or(0),
+        item.id(),
+        display_time(item.time())
+    )?;
+    if let Some(url) = item.url() {
+        writeln!(&mut output, "URL: {}", url)?;
+    }
+    if let Some(text) = item.text() {
+        writeln!(&mut output)?;
+        write_wrapped(&mut output, text, "", layout.width)?;
+    }
+    match item {
+        Item::Comment(comment) => writeln!(&mut output, "\nReply to: {}", comment.parent)?,
+        Item::PollOpt(option) => writeln!(&mut output, "\nOption of poll: {}", option.poll)?,
+        Item::Poll(poll) => {
+            let options = poll.parts.as_deref().unwrap_or_default();
+            writeln!(&mut output, "\nPoll options: {:?}", options)?;
+        }
+        _ => {}
+    }
+
+    Ok(output)
+}
+
+// An item with one line for each item above it and each reply below it
+pub fn format_item_context(context: &ItemContext, layout: &TableLayout) -> Result<String> {
+    let mut output = String::new();
+
+    for (depth, parent) in context.parents.iter().enumerate() {
+        writeln!(&mut output, "{}{}", "  ".repeat(depth), summary(parent))?;
+    }
+    if !context.parents.is_empty() {
+        writeln!(&mut output, "{:-<width$}", "", width = layout.width)?;
+    }
+
+    output.push_str(&format_item(&context.item, layout)?);
+
+    if !context.children.is_empty() {
+        writeln!(&mut output, "\nReplies:")?;
+        for child in &context.children {
+            writeln!(&mut output, "  {}", summary(child))?;
+        }
+    }
+    if !context.skipped.is_empty() {
+        writeln!(&mut output, "\nSkipped {} item(s) that could not be fetched.", context.skipped.len())?;
+    }
+
+    Ok(output)
+}
+
+pub fn format_user(user: &User) -> Result<String> {
+    let mut output = String::new();
+
+    writeln!(&mut output, "{}", user.id)?;
+    writeln!(
+        &mut output,
+        "Karma: {} | Joined: {} | Submissions: {}",
+        user.karma,
+        display_time(user.created),
+        user.submitted.as_ref().map_or(0, Vec::len)
+    )?;
+    if let Some(about) = &user.about {
+        writeln!(&mut output, "\n{}", about)?;
+    }
+
+    Ok(output)
+}
+
+// A profile followed by one line for each submission on the page
+pub fn format_user_profile(profile: &UserProfile) -> Result<String> {
+    let mut output = format_user(&profile.user)?;
+
+    if !profile.submissions.is_empty() {
+        writeln!(&mut output, "\nRecent submissions (page {}):", profile.page)?;
+        for item in &profile.submissions {
+            writeln!(&mut output, "  {} {:<7} {}", display_time(item.time()), item.kind(), summary(item))?;
+        }
+    }
+    if profile.has_more {
+        writeln!(&mut output, "\nMore submissions on page {}.", profile.page + 1)?;
+    }
+    if !profile.skipped.is_empty() {
+        writeln!(&mut output, "\nSkipped {} item(s) that could not be fetched.", profile.skipped.len())?;
+    }
+
+    Ok(output)
+}
+
+// The discussion as an indented outline, in the order it appears on HN
+pub fn format_thread(tree: &CommentTree, layout: &TableLayout) -> Result<String> {
+    let mut output = format_item(&tree.root, layout)?;
+    writeln!(&mut output, "{:-<width$}", "", width = layout.width)?;
+
+    for CommentNode { comment, depth, .. } in tree.iter() {
+        let indent = "  ".repeat(*depth);
+        writeln!(
+            &mut output,
+            "\n{}{} ({}):",
+            indent,
+            comment.by.as_deref().unwrap_or("[unknown]"),
+            display_time(comment.time)
+        )?;
+        write_wrapped(&mut output, &comment_body(comment), &indent, layout.width)?;
+    }
+
+    if tree.truncated {
+        writeln!(&mut output, "\nNote: the discussion is longer than the limits allowed; some replies are not shown.")?;
+    }
+    if !tree.skipped.is_empty() {
+        writeln!(&mut output, "\nSkipped {} comment(s) that could not be fetched.", tree.skipped.len())?;
+    }
+
+    Ok(output)
+}
+
+// The summary under a one-line description of the story, with what it covers
+pub fn format_thread_summary(thread: &ThreadSummary, layout: &TableLayout) -> Result<String> {
+    let mut output = String::new();
+
+    writeln!(&mut output, "{}", summary(&thread.root))?;
+    writeln!(
+        &mut output,
+        "Summary of {} comment(s) in {} part(s), citing {}",
+        thread.comments,
+        thread.chunks,
+        thread.cited.len()
+    )?;
+    writeln!(&mut output, "{:-<width$}", "", width = layout.width)?;
+    writeln!(&mut output, "{}", thread.summary.trim())?;
+
+    if thread.truncated {
+        writeln!(
+            &mut output,
+            "\nNote: the discussion is longer than the limits allowed; only part of it was summarized."
+        )?;
+    }
+    if !thread.skipped.is_empty() {
+        writeln!(&mut output, "\nSkipped {} comment(s) that could not be fetched.", thread.skipped.len())?;
+    }
+
+    Ok(output)
+}
+
+// One line per archived passage the answer could draw on, by ID so citations can be looked up
+pub fn format_context(passages: &[ContextPassage], layout: &TableLayout) -> Result<String> {
+    let mut output = String::new();
+
+    writeln!(&mut output, "\n{:-^width$}", " Archived Context ", width = layout.width)?;
+    for passage in passages {
+        let mut source =
+            format!("[{}] {} by {}", passage.id, passage.kind, passage.by.as_deref().unwrap_or("[unknown]"));
+        if !passage.date.is_empty() {
+            write!(&mut source, " on {}", passage.date)?;
+        }
+        if let Some(title) = &passage.title {
+            write!(&mut source, ": {}", title)?;
+        }
+        writeln!(&mut output, "{}", truncate(&source, layout.width))?;
+    }
+
+    Ok(output)
+}
+
+// What a sync fetched, then what the archive now holds
+pub fn format_sync_report(report: &SyncReport, stats: &ArchiveStats) -> Result<String> {
+    let mut output = String::new();
+
+    writeln!(
+        &mut output,
+        "Synced {} new item(s), {} older item(s), {} updated item(s) and {} updated profile(s)",
+        report.new_items, report.backfilled, report.updated_items, report.updated_users
+    )?;
+    if report.retried > 0 {
+        writeln!(&mut output, "Fetched {} item(s) an earlier sync had skipped.", report.retried)?;
+    }
+    if report.indexed > 0 {
+        writeln!(&mut output, "Indexed {} story(ies) for full-text search.", report.indexed)?;
+    }
+    if report.embedded > 0 {
+        writeln!(&mut output, "Embedded {} passage(s) for semantic search.", report.embedded)?;
+    }
+    if report.missing > 0 {
+        writeln!(&mut output, "{} ID(s) returned no item.", report.missing)?;
+    }
+    if !report.skipped.is_empty() || !report.skipped_users.is_empty() {
+        writeln!(
+            &mut output,
+            "Skipped {} item(s) and {} profile(s) that could not be fetched; the next sync tries the items again.",
+            report.skipped.len(),
+            report.skipped_users.len()
+        )?;
+    }
+
+    writeln!(
+        &mut output,
+        "\nArchive: {} item(s), {} story(ies), {} profile(s)",
+        stats.items, stats.stories, stats.users
+    )?;
+    if let (Some(oldest), Some(newest)) = (stats.oldest_time, stats.newest_time) {
+        writeln!(&mut output, "From {} to {}", display_time(oldest), display_time(newest))?;
+    }
+    if !report.reached_horizon {
+        writeln!(&mut output, "The backfill has not reached the horizon yet; run sync again to continue.")?;
+    }
+
+    Ok(output)
+}
+
+// One line describing an item: its title, or the start of its text
+fn summary(item: &Item) -> String {
+    let body = match (item.title(), item.text()) {
+        _ if item.is_deleted() => "[deleted]".to_string(),
+        (Some(_), _) => display_title(item),
+        (None, Some(text)) => preview(text, 100),
+        (None, None) => String::new(),
+    };
+    format!("[{}] {}: {}", item.id(), item.by().unwrap_or("[unknown]"), body)
+}
+
+// The start of some text, on one line of at most `max_width` columns
+pub fn preview(content: &str, max_width: usize) -> String {
+    let line = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    truncate(&line, max_width)
+}
+
+// A comment's text, or why there is none
+pub fn comment_body(comment: &Comment) -> String {
+    match &comment.text {
+        _ if comment.deleted => "[deleted]".to_string(),
+        Some(text) if comment.dead => format!("[dead] {}", text),
+        Some(text) => text.clone(),
+        None => "[Comment text not available]".to_string(),
+    }
+}
+
+// Text wrapped to fit `width` columns, with every line behind `indent`
+fn write_wrapped(output: &mut String, text: &str, indent: &str, width: usize) -> Result<()> {
+    let available = width.saturating_sub(display::width(indent)).max(MIN_TEXT_WIDTH);
+    for line in wrap(text, available) {
+        writeln!(output, "{}{}", indent, line)?;
+    }
+    Ok(())
+}
+
+pub fn display_time(time: i64) -> String {
+    DateTime::from_timestamp(time, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "[unknown]".to_string())
+}
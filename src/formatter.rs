@@ -0,0 +1,950 @@
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::json;
+
+use rig_hn_assistant::{
+    citation::{Citation, CitationReport},
+    html,
+    item::{Comment, Item},
+    lookup::{ItemContext, UserProfile},
+    rag::ContextPassage,
+    search::{SearchOutput, SearchResult},
+    summarize::ThreadSummary,
+    tree::{CommentNode, CommentTree},
+};
+
+use crate::cli::OutputFormat;
+use crate::format::{
+    comment_body, display_time, display_title, format_answer, format_hn_results, format_item_context, format_thread,
+    format_thread_summary, format_user_profile, Answer, TableLayout, ToolOutput,
+};
+
+// Renders each kind of result the CLI prints
+pub trait Formatter {
+    fn search(&self, search: &SearchOutput) -> Result<String>;
+    fn item(&self, context: &ItemContext) -> Result<String>;
+    fn user(&self, profile: &UserProfile) -> Result<String>;
+    fn thread(&self, tree: &CommentTree) -> Result<String>;
+    fn summary(&self, summary: &ThreadSummary) -> Result<String>;
+    fn answer(&self, answer: &Answer) -> Result<String>;
+
+    // What the HTML in HN text fields should be converted to before it's handed
+    // over. Answers are the exception: their tool output is what the model saw,
+    // which is always plain text.
+    fn text_format(&self) -> fn(&str) -> String {
+        html::to_text
+    }
+}
+
+pub fn formatter(format: OutputFormat, layout: TableLayout) -> Box<dyn Formatter> {
+    match format {
+        OutputFormat::Text => Box::new(Text(layout)),
+        OutputFormat::Json => Box::new(Json),
+        OutputFormat::Ndjson => Box::new(Ndjson),
+        OutputFormat::Markdown => Box::new(Markdown { text: str::to_string }),
+        OutputFormat::Html => Box::new(Html),
+        OutputFormat::Csv => Box::new(Csv),
+    }
+}
+
+fn item_link(id: u32) -> String {
+    format!("https://news.ycombinator.com/item?id={}", id)
+}
+
+fn user_link(user: &str) -> String {
+    format!("https://news.ycombinator.com/user?id={}", user)
+}
+
+// "story by pg · 57 points · 12 comments · 2007-02-19 23:38 UTC"
+fn meta(item: &Item) -> String {
+    let mut meta = format!("{} by {}", item.kind(), item.by().unwrap_or("[unknown]"));
+    if let Some(score) = item.score() {
+        write!(meta, " · {} points", score).expect("writing to a String");
+    }
+    if let Some(comments) = item.descendants() {
+        write!(meta, " · {} comments", comments).expect("writing to a String");
+    }
+    meta + " · " + &display_time(item.time())
+}
+
+// The report the terminal shows
+struct Text(TableLayout);
+
+impl Formatter for Text {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        format_hn_results(search, &self.0)
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        format_item_context(context, &self.0)
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        format_user_profile(profile)
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        format_thread(tree, &self.0)
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        format_thread_summary(summary, &self.0)
+    }
+
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        format_answer(answer, &self.0)
+    }
+}
+
+// The whole result as one pretty-printed JSON document
+struct Json;
+
+fn pretty(value: &impl Serialize) -> Result<String> {
+    Ok(serde_json::to_string_pretty(value)? + "\n")
+}
+
+impl Formatter for Json {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        pretty(search)
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        pretty(context)
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        pretty(profile)
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        pretty(tree)
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        pretty(summary)
+    }
+
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        pretty(answer)
+    }
+}
+
+// One JSON value per line: a line for each story, item or comment, so other
+// tools can process results a record at a time
+struct Ndjson;
+
+fn line(output: &mut String, value: &impl Serialize) -> Result<()> {
+    output.push_str(&serde_json::to_string(value)?);
+    output.push('\n');
+    Ok(())
+}
+
+// A comment of a thread on its own line, with its place in the thread
+#[derive(Serialize)]
+struct ThreadComment<'a> {
+    depth: usize,
+    #[serde(flatten)]
+    comment: &'a Comment,
+}
+
+impl Formatter for Ndjson {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        let mut output = String::new();
+        for result in &search.results {
+            line(&mut output, result)?;
+        }
+        Ok(output)
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        let mut output = String::new();
+        for item in context.parents.iter().chain([&context.item]).chain(&context.children) {
+            line(&mut output, item)?;
+        }
+        Ok(output)
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        let mut output = String::new();
+        line(&mut output, &profile.user)?;
+        for item in &profile.submissions {
+            line(&mut output, item)?;
+        }
+        Ok(output)
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        let mut output = String::new();
+        line(&mut output, &tree.root)?;
+        for CommentNode { comment, depth, .. } in tree.iter() {
+            line(&mut output, &ThreadComment { depth: *depth, comment })?;
+        }
+        Ok(output)
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        let mut output = String::new();
+        line(&mut output, summary)?;
+        Ok(output)
+    }
+
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        let mut output = String::new();
+        for call in &answer.tool_calls {
+            line(&mut output, call)?;
+        }
+        line(&mut output, &json!({ "response": answer.response }))?;
+        if !answer.citations.is_empty() {
+            line(&mut output, &json!({ "citations": answer.citations }))?;
+        }
+        Ok(output)
+    }
+}
+
+// A row of item metadata per story, comment or other item
+struct Csv;
+
+const CSV_HEADER: &str = "id,type,title,by,score,comments,time,url,parent";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv<'a>(items: impl IntoIterator<Item = &'a Item>) -> String {
+    let mut output = String::from(CSV_HEADER);
+    output.push('\n');
+    for item in items {
+        let time = DateTime::from_timestamp(item.time(), 0).map(|time| time.to_rfc3339()).unwrap_or_default();
+        let fields = [
+            item.id().to_string(),
+            item.kind().to_string(),
+            item.title().unwrap_or_default().to_string(),
+            item.by().unwrap_or_default().to_string(),
+            item.score().map(|score| score.to_string()).unwrap_or_default(),
+            item.descendants().map(|comments| comments.to_string()).unwrap_or_default(),
+            time,
+            item.url().unwrap_or_default().to_string(),
+            item.parent().map(|parent| parent.to_string()).unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        output.push_str(&row.join(","));
+        output.push('\n');
+    }
+    output
+}
+
+impl Formatter for Csv {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        Ok(csv(search.results.iter().map(|result| &result.item)))
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        Ok(csv(context.parents.iter().chain([&context.item]).chain(&context.children)))
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        Ok(csv(&profile.submissions))
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        let comments: Vec<Item> = tree.iter().map(|node| Item::Comment(node.comment.clone())).collect();
+        Ok(csv([&tree.root].into_iter().chain(&comments)))
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        Ok(csv([&summary.root]))
+    }
+
+    // The items from every tool call, in the order the model asked for them
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        let mut items = Vec::new();
+        for call in &answer.tool_calls {
+            match ToolOutput::decode(call)? {
+                ToolOutput::Search(search) => items.extend(search.results.into_iter().map(|result| result.item)),
+                ToolOutput::Item(context) => {
+                    items.extend(context.parents);
+                    items.push(context.item);
+                    items.extend(context.children);
+                }
+                ToolOutput::User(profile) => items.extend(profile.submissions),
+                ToolOutput::Summary(summary) => items.push(summary.root),
+                ToolOutput::Context(_) | ToolOutput::Other(_) => {}
+            }
+        }
+        Ok(csv(&items))
+    }
+}
+
+// GitHub-flavored Markdown, with comments as block quotes nested by reply depth.
+// `text` writes HN text fields: as they are once converted by `html::to_markdown`,
+// escaped when they're the plain text of a tool call.
+struct Markdown {
+    text: fn(&str) -> String,
+}
+
+fn md_escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#') {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output
+}
+
+// A search highlight with everything but its **marked** words escaped
+fn md_highlight(text: &str) -> String {
+    text.split("**")
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { format!("**{}**", md_escape(part)) } else { md_escape(part) })
+        .collect()
+}
+
+// Every line of `text`, already Markdown, behind `prefix`, e.g. "> " for a block quote
+fn md_quote(output: &mut String, text: &str, prefix: &str) {
+    for text_line in text.lines() {
+        output.push_str(prefix.trim_end());
+        if !text_line.is_empty() {
+            output.push(' ');
+            output.push_str(text_line);
+        }
+        output.push('\n');
+    }
+}
+
+impl Markdown {
+    fn item_section(&self, output: &mut String, item: &Item, heading: &str) {
+        let title = md_escape(&display_title(item));
+        let link = item.url().map(str::to_string).unwrap_or_else(|| item_link(item.id()));
+        output.push_str(&format!("{} [{}](<{}>)\n\n", heading, title, link));
+        output.push_str(&format!("{} · [discussion]({})\n\n", md_escape(&meta(item)), item_link(item.id())));
+        if let Some(text) = item.text() {
+            output.push_str(&(self.text)(text));
+            output.push_str("\n\n");
+        }
+    }
+
+    fn comment(&self, output: &mut String, comment: &Comment, depth: usize) {
+        let prefix = "> ".repeat(depth + 1);
+        let header = format!(
+            "**{}** · {} · [#{}]({})",
+            md_escape(comment.by.as_deref().unwrap_or("[unknown]")),
+            display_time(comment.time),
+            comment.id,
+            item_link(comment.id)
+        );
+        output.push_str(&format!("{}{}\n{}\n", prefix, header, prefix.trim_end()));
+        md_quote(output, &(self.text)(&comment_body(comment)), &prefix);
+        output.push('\n');
+    }
+
+    fn search_body(&self, search: &SearchOutput) -> String {
+        let mut output = String::from("## Hacker News Discussions\n\n");
+        output.push_str("| # | Title | Author | Points | Comments |\n|---:|---|---|---:|---:|\n");
+        for (i, SearchResult { item, .. }) in search.results.iter().enumerate() {
+            output.push_str(&format!(
+                "| {} | [{}]({}) | {} | {} | {} |\n",
+                i + 1,
+                md_escape(&display_title(item)),
+                item_link(item.id()),
+                md_escape(item.by().unwrap_or("[unknown]")),
+                item.score().unwrap_or(0),
+                item.descendants().unwrap_or(0)
+            ));
+        }
+        output.push('\n');
+
+        for (i, SearchResult { item, comments, highlights, .. }) in search.results.iter().enumerate() {
+            self.item_section(&mut output, item, &format!("### {}.", i + 1));
+            if !highlights.is_empty() {
+                output.push_str(
+                    "#### Matches
+
+",
+                );
+                for highlight in highlights {
+                    output.push_str(&format!("- ... {} ...\n", md_highlight(highlight)));
+                }
+                output.push('\n');
+            }
+            if !comments.is_empty() {
+                output.push_str("#### Top comments\n\n");
+                for comment in comments {
+                    self.comment(&mut output, comment, 0);
+                }
+            }
+        }
+        notes(&mut output, search.timed_out, false, search.skipped.len());
+        output
+    }
+
+    fn item_body(&self, context: &ItemContext) -> String {
+        let mut output = String::new();
+        if !context.parents.is_empty() {
+            output.push_str("In reply to:\n\n");
+            for (depth, parent) in context.parents.iter().enumerate() {
+                output.push_str(&format!(
+                    "{}- [{}]({})\n",
+                    "  ".repeat(depth),
+                    md_escape(&short(parent)),
+                    item_link(parent.id())
+                ));
+            }
+            output.push('\n');
+        }
+        self.item_section(&mut output, &context.item, "##");
+        if !context.children.is_empty() {
+            output.push_str("#### Replies\n\n");
+            for child in &context.children {
+                match child {
+                    Item::Comment(comment) => self.comment(&mut output, comment, 0),
+                    other => {
+                        output.push_str(&format!("- [{}]({})\n\n", md_escape(&short(other)), item_link(other.id())))
+                    }
+                }
+            }
+        }
+        notes(&mut output, false, false, context.skipped.len());
+        output
+    }
+
+    fn user_body(&self, profile: &UserProfile) -> String {
+        let user = &profile.user;
+        let mut output = format!("## [{}]({})\n\n", md_escape(&user.id), user_link(&user.id));
+        output.push_str(&format!(
+            "{} karma · joined {} · {} submissions\n\n",
+            user.karma,
+            display_time(user.created),
+            user.submitted.as_ref().map_or(0, Vec::len)
+        ));
+        if let Some(about) = &user.about {
+            md_quote(&mut output, &(self.text)(about), "> ");
+            output.push('\n');
+        }
+        if !profile.submissions.is_empty() {
+            output.push_str(&format!("#### Recent submissions (page {})\n\n", profile.page));
+            for item in &profile.submissions {
+                output.push_str(&format!(
+                    "- {} · {} · [{}]({})\n",
+                    display_time(item.time()),
+                    item.kind(),
+                    md_escape(&short(item)),
+                    item_link(item.id())
+                ));
+            }
+            output.push('\n');
+        }
+        notes(&mut output, false, false, profile.skipped.len());
+        output
+    }
+
+    fn summary_body(&self, summary: &ThreadSummary) -> String {
+        let mut output = String::new();
+        self.item_section(&mut output, &summary.root, "## Summary:");
+        output.push_str(&format!(
+            "*{} comments in {} parts, citing {}*\n\n",
+            summary.comments,
+            summary.chunks,
+            summary.cited.len()
+        ));
+        // The model writes the summary itself, usually as Markdown already
+        output.push_str(summary.summary.trim());
+        output.push_str("\n\n");
+        notes(&mut output, false, summary.truncated, summary.skipped.len());
+        output
+    }
+
+    fn context_body(&self, passages: &[ContextPassage]) -> String {
+        let mut output = String::from("## Archived Context\n\n");
+        for passage in passages {
+            output.push_str(&format!(
+                "- [\\[{}\\]]({}) {} by {}",
+                passage.id,
+                item_link(passage.id),
+                passage.kind,
+                md_escape(passage.by.as_deref().unwrap_or("[unknown]"))
+            ));
+            if !passage.date.is_empty() {
+                output.push_str(&format!(" on {}", passage.date));
+            }
+            if let Some(title) = &passage.title {
+                output.push_str(&format!(": {}", md_escape(title)));
+            }
+            output.push('\n');
+        }
+        output.push('\n');
+        output
+    }
+}
+
+// A title or the start of the text, for lists of items
+fn short(item: &Item) -> String {
+    match item.title() {
+        Some(_) => display_title(item),
+        None => crate::format::preview(item.text().unwrap_or_default(), 80),
+    }
+}
+
+fn notes(output: &mut String, timed_out: bool, truncated: bool, skipped: usize) {
+    if timed_out {
+        output.push_str("*The search hit its time limit, so these results may be incomplete.*\n\n");
+    }
+    if truncated {
+        output.push_str("*The discussion is longer than the limits allowed; some of it is not included.*\n\n");
+    }
+    if skipped > 0 {
+        output.push_str(&format!("*Skipped {} item(s) that could not be fetched.*\n\n", skipped));
+    }
+}
+
+impl Formatter for Markdown {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        Ok(self.search_body(search))
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        Ok(self.item_body(context))
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        Ok(self.user_body(profile))
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        let mut output = String::new();
+        self.item_section(&mut output, &tree.root, "##");
+        for CommentNode { comment, depth, .. } in tree.iter() {
+            self.comment(&mut output, comment, *depth);
+        }
+        notes(&mut output, false, tree.truncated, tree.skipped.len());
+        Ok(output)
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        Ok(self.summary_body(summary))
+    }
+
+    fn text_format(&self) -> fn(&str) -> String {
+        html::to_markdown
+    }
+
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        let tools = Markdown { text: md_escape };
+        let mut output = String::new();
+        for call in &answer.tool_calls {
+            match ToolOutput::decode(call)? {
+                ToolOutput::Search(search) => output.push_str(&tools.search_body(&search)),
+                ToolOutput::Item(context) => output.push_str(&tools.item_body(&context)),
+                ToolOutput::User(profile) => output.push_str(&tools.user_body(&profile)),
+                ToolOutput::Summary(summary) => output.push_str(&tools.summary_body(&summary)),
+                ToolOutput::Context(passages) => output.push_str(&tools.context_body(&passages)),
+                ToolOutput::Other(value) => {
+                    output.push_str(&format!("```json\n{}\n```\n\n", serde_json::to_string_pretty(&value)?))
+                }
+            }
+        }
+        if let Some(commentary) = answer.commentary() {
+            let commentary = answer.citations.rewrite(commentary, str::to_string, |group| {
+                let kept: Vec<String> =
+                    group.iter().copied().filter(|citation| citation.is_kept()).map(md_cite).collect();
+                kept.join(" ")
+            });
+            output.push_str(commentary.trim());
+            output.push('\n');
+            output.push_str(&md_citations(&answer.citations));
+        }
+        Ok(output)
+    }
+}
+
+// A verified citation links to the item; any other is marked with "?"
+fn md_cite(citation: &Citation) -> String {
+    match citation.is_verified() {
+        true => format!("[\\[{}\\]]({})", citation.id, item_link(citation.id)),
+        false => format!("\\[{}?\\]", citation.id),
+    }
+}
+
+fn md_citations(report: &CitationReport) -> String {
+    let problems: Vec<String> = report.problems().filter_map(|citation| citation.problem()).collect();
+    if problems.is_empty() && report.unsourced_quotes.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("\n#### Unverified citations\n\n");
+    for problem in problems {
+        output.push_str(&format!("- {}\n", md_escape(&problem)));
+    }
+    for quote in &report.unsourced_quotes {
+        output.push_str(&format!("- \"{}\" is not in any item the answer was based on\n", md_escape(quote)));
+    }
+    output
+}
+
+// A standalone HTML page; threads are nested <details> elements, so each
+// reply chain can be collapsed
+struct Html;
+
+const HTML_STYLE: &str =
+    "body { font-family: system-ui, sans-serif; max-width: 60em; margin: auto; padding: 1em; line-height: 1.4; }
+.meta { color: #666; font-size: 0.9em; }
+.text { white-space: pre-wrap; }
+table { border-collapse: collapse; }
+th, td { padding: 0.2em 0.6em; text-align: left; border-bottom: 1px solid #ddd; }
+details { margin: 0.5em 0 0.5em 1em; padding-left: 0.6em; border-left: 2px solid #ddd; }
+summary { cursor: pointer; color: #444; }
+.note { font-style: italic; color: #666; }
+.unverified { color: #b00; cursor: help; }";
+
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        HTML_STYLE,
+        body
+    )
+}
+
+// A search highlight with its **marked** words in <mark>
+fn html_highlight(text: &str) -> String {
+    text.split("**")
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { format!("<mark>{}</mark>", escape(part)) } else { escape(part) })
+        .collect()
+}
+
+fn html_notes(output: &mut String, timed_out: bool, truncated: bool, skipped: usize) {
+    if timed_out {
+        output.push_str("<p class=\"note\">The search hit its time limit, so these results may be incomplete.</p>\n");
+    }
+    if truncated {
+        output.push_str(
+            "<p class=\"note\">The discussion is longer than the limits allowed; some of it is not included.</p>\n",
+        );
+    }
+    if skipped > 0 {
+        output.push_str(&format!("<p class=\"note\">Skipped {} item(s) that could not be fetched.</p>\n", skipped));
+    }
+}
+
+impl Html {
+    fn item_section(&self, output: &mut String, item: &Item, level: u8) {
+        let link = item.url().map(str::to_string).unwrap_or_else(|| item_link(item.id()));
+        output.push_str(&format!(
+            "<h{level}><a href=\"{}\">{}</a></h{level}>\n<p class=\"meta\">{} · <a href=\"{}\">discussion</a></p>\n",
+            escape(&link),
+            escape(&display_title(item)),
+            escape(&meta(item)),
+            escape(&item_link(item.id())),
+        ));
+        if let Some(text) = item.text() {
+            output.push_str(&format!("<div class=\"text\">{}</div>\n", escape(text)));
+        }
+    }
+
+    fn comment_open(&self, output: &mut String, comment: &Comment) {
+        output.push_str(&format!(
+            "<details open>\n<summary>{} · {} · <a href=\"{}\">#{}</a></summary>\n<div class=\"text\">{}</div>\n",
+            escape(comment.by.as_deref().unwrap_or("[unknown]")),
+            escape(&display_time(comment.time)),
+            escape(&item_link(comment.id)),
+            comment.id,
+            escape(&comment_body(comment))
+        ));
+    }
+
+    fn comment_tree(&self, output: &mut String, node: &CommentNode) {
+        self.comment_open(output, &node.comment);
+        for child in &node.children {
+            self.comment_tree(output, child);
+        }
+        output.push_str("</details>\n");
+    }
+
+    fn search_body(&self, search: &SearchOutput) -> String {
+        let mut output = String::from("<h1>Hacker News Discussions</h1>\n<table>\n");
+        output.push_str("<tr><th>#</th><th>Title</th><th>Author</th><th>Points</th><th>Comments</th></tr>\n");
+        for (i, SearchResult { item, .. }) in search.results.iter().enumerate() {
+            output.push_str(&format!(
+                "<tr><td>{}</td><td><a href=\"#item-{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                i + 1,
+                item.id(),
+                escape(&display_title(item)),
+                escape(item.by().unwrap_or("[unknown]")),
+                item.score().unwrap_or(0),
+                item.descendants().unwrap_or(0)
+            ));
+        }
+        output.push_str("</table>\n");
+
+        for SearchResult { item, comments, highlights, .. } in &search.results {
+            output.push_str(&format!("<article id=\"item-{}\">\n", item.id()));
+            self.item_section(&mut output, item, 2);
+            if !highlights.is_empty() {
+                output.push_str("<ul class=\"matches\">\n");
+                for highlight in highlights {
+                    output.push_str(&format!("<li>... {} ...</li>\n", html_highlight(highlight)));
+                }
+                output.push_str("</ul>\n");
+            }
+            for comment in comments {
+                self.comment_open(&mut output, comment);
+                output.push_str("</details>\n");
+            }
+            output.push_str("</article>\n");
+        }
+        html_notes(&mut output, search.timed_out, false, search.skipped.len());
+        output
+    }
+
+    fn item_body(&self, context: &ItemContext) -> String {
+        let mut output = String::new();
+        if !context.parents.is_empty() {
+            output.push_str("<p class=\"meta\">In reply to:</p>\n<ol>\n");
+            for parent in &context.parents {
+                output.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    escape(&item_link(parent.id())),
+                    escape(&short(parent))
+                ));
+            }
+            output.push_str("</ol>\n");
+        }
+        output.push_str("<article>\n");
+        self.item_section(&mut output, &context.item, 2);
+        for child in &context.children {
+            match child {
+                Item::Comment(comment) => {
+                    self.comment_open(&mut output, comment);
+                    output.push_str("</details>\n");
+                }
+                other => output.push_str(&format!(
+                    "<p><a href=\"{}\">{}</a></p>\n",
+                    escape(&item_link(other.id())),
+                    escape(&short(other))
+                )),
+            }
+        }
+        output.push_str("</article>\n");
+        html_notes(&mut output, false, false, context.skipped.len());
+        output
+    }
+
+    fn user_body(&self, profile: &UserProfile) -> String {
+        let user = &profile.user;
+        let mut output = format!(
+            "<h2><a href=\"{}\">{}</a></h2>\n<p class=\"meta\">{} karma · joined {} · {} submissions</p>\n",
+            escape(&user_link(&user.id)),
+            escape(&user.id),
+            user.karma,
+            escape(&display_time(user.created)),
+            user.submitted.as_ref().map_or(0, Vec::len)
+        );
+        if let Some(about) = &user.about {
+            output.push_str(&format!("<div class=\"text\">{}</div>\n", escape(about)));
+        }
+        if !profile.submissions.is_empty() {
+            output.push_str(&format!("<h3>Recent submissions (page {})</h3>\n<ul>\n", profile.page));
+            for item in &profile.submissions {
+                output.push_str(&format!(
+                    "<li>{} · {} · <a href=\"{}\">{}</a></li>\n",
+                    escape(&display_time(item.time())),
+                    item.kind(),
+                    escape(&item_link(item.id())),
+                    escape(&short(item))
+                ));
+            }
+            output.push_str("</ul>\n");
+        }
+        html_notes(&mut output, false, false, profile.skipped.len());
+        output
+    }
+
+    fn summary_body(&self, summary: &ThreadSummary) -> String {
+        let mut output = String::from("<article>\n");
+        self.item_section(&mut output, &summary.root, 2);
+        output.push_str(&format!(
+            "<p class=\"meta\">Summary of {} comments in {} parts, citing {}</p>\n<div class=\"text\">{}</div>\n</article>\n",
+            summary.comments,
+            summary.chunks,
+            summary.cited.len(),
+            escape(summary.summary.trim())
+        ));
+        html_notes(&mut output, false, summary.truncated, summary.skipped.len());
+        output
+    }
+
+    fn context_body(&self, passages: &[ContextPassage]) -> String {
+        let mut output = String::from("<h2>Archived Context</h2>\n<ul>\n");
+        for passage in passages {
+            output.push_str(&format!(
+                "<li><a href=\"{}\">[{}]</a> {} by {}",
+                item_link(passage.id),
+                passage.id,
+                passage.kind,
+                escape(passage.by.as_deref().unwrap_or("[unknown]"))
+            ));
+            if !passage.date.is_empty() {
+                output.push_str(&format!(" on {}", passage.date));
+            }
+            if let Some(title) = &passage.title {
+                output.push_str(&format!(": {}", escape(title)));
+            }
+            output.push_str("</li>\n");
+        }
+        output.push_str("</ul>\n");
+        output
+    }
+}
+
+// A verified citation links to the item; any other is marked, with the problem as its tooltip
+fn html_cite(citation: &Citation) -> String {
+    match citation.problem() {
+        None => format!("<a href=\"{}\">[{}]</a>", item_link(citation.id), citation.id),
+        Some(problem) => format!("<span class=\"unverified\" title=\"{}\">[{}?]</span>", escape(&problem), citation.id),
+    }
+}
+
+fn html_citations(report: &CitationReport) -> String {
+    let problems: Vec<String> = report.problems().filter_map(|citation| citation.problem()).collect();
+    if problems.is_empty() && report.unsourced_quotes.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("<p class=\"note\">Unverified citations:</p>\n<ul class=\"note\">\n");
+    for problem in problems {
+        output.push_str(&format!("<li>{}</li>\n", escape(&problem)));
+    }
+    for quote in &report.unsourced_quotes {
+        output.push_str(&format!("<li>\"{}\" is not in any item the answer was based on</li>\n", escape(quote)));
+    }
+    output.push_str("</ul>\n");
+    output
+}
+
+impl Formatter for Html {
+    fn search(&self, search: &SearchOutput) -> Result<String> {
+        Ok(page("Hacker News Discussions", &self.search_body(search)))
+    }
+
+    fn item(&self, context: &ItemContext) -> Result<String> {
+        Ok(page(&display_title(&context.item), &self.item_body(context)))
+    }
+
+    fn user(&self, profile: &UserProfile) -> Result<String> {
+        Ok(page(&profile.user.id, &self.user_body(profile)))
+    }
+
+    fn thread(&self, tree: &CommentTree) -> Result<String> {
+        let mut body = String::from("<article>\n");
+        self.item_section(&mut body, &tree.root, 1);
+        for node in &tree.comments {
+            self.comment_tree(&mut body, node);
+        }
+        body.push_str("</article>\n");
+        html_notes(&mut body, false, tree.truncated, tree.skipped.len());
+        Ok(page(&display_title(&tree.root), &body))
+    }
+
+    fn summary(&self, summary: &ThreadSummary) -> Result<String> {
+        Ok(page(&display_title(&summary.root), &self.summary_body(summary)))
+    }
+
+    fn answer(&self, answer: &Answer) -> Result<String> {
+        let mut body = String::new();
+        for call in &answer.tool_calls {
+            match ToolOutput::decode(call)? {
+                ToolOutput::Search(search) => body.push_str(&self.search_body(&search)),
+                ToolOutput::Item(context) => body.push_str(&self.item_body(&context)),
+                ToolOutput::User(profile) => body.push_str(&self.user_body(&profile)),
+                ToolOutput::Summary(summary) => body.push_str(&self.summary_body(&summary)),
+                ToolOutput::Context(passages) => body.push_str(&self.context_body(&passages)),
+                ToolOutput::Other(value) => {
+                    body.push_str(&format!("<pre>{}</pre>\n", escape(&serde_json::to_string_pretty(&value)?)))
+                }
+            }
+        }
+        if let Some(commentary) = answer.commentary() {
+            let commentary = answer.citations.rewrite(commentary, escape, |group| {
+                let kept: Vec<String> =
+                    group.iter().copied().filter(|citation| citation.is_kept()).map(html_cite).collect();
+                kept.join(" ")
+            });
+            body.push_str(&format!("<section>\n<div class=\"text\">{}</div>\n", commentary.trim()));
+            body.push_str(&html_citations(&answer.citations));
+            body.push_str("</section>\n");
+        }
+        Ok(page("Hacker News Assistant", &body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig_hn_assistant::html::MapText;
+
+    fn context() -> ItemContext {
+        let item = json!({
+            "type": "story",
+            "id": 1,
+            "by": "pg",
+            "time": 1_700_000_000,
+            "title": "Rust",
+            "url": "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            "text": "Try <i>this</i>:<pre><code>let x = a * b_c;\n</code></pre>See <a href=\"https://example.com/a_(b)\">the docs</a>."
+        });
+        let reply = json!({ "type": "comment", "id": 2, "by": "dang", "time": 1_700_000_100, "parent": 1, "text": "Fine, but <code>*ptr</code>" });
+        ItemContext {
+            item: serde_json::from_value(item).unwrap(),
+            parents: Vec::new(),
+            children: vec![serde_json::from_value(reply).unwrap()],
+            skipped: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn markdown_keeps_code_blocks_and_links() {
+        let formatter = formatter(OutputFormat::Markdown, TableLayout::default());
+        let output = formatter.item(&context().map_text(formatter.text_format())).unwrap();
+        assert!(
+            output.contains("## [Rust](<https://en.wikipedia.org/wiki/Rust_(programming_language)>)"),
+            "{}",
+            output
+        );
+        assert!(output
+            .contains("Try *this*:\n\n```\nlet x = a * b_c;\n```\n\nSee [the docs](<https://example.com/a_(b)>)."));
+        assert!(output.contains("> Fine, but `*ptr`"));
+    }
+
+    #[test]
+    fn markdown_escapes_plain_text() {
+        let formatter = formatter(OutputFormat::Markdown, TableLayout::default());
+        let output = formatter.item(&context().map_text(formatter.text_format())).unwrap();
+        assert!(!output.contains("\\*"), "{}", output);
+
+        let plain = Markdown { text: md_escape }.item_body(&context().map_text(html::to_text));
+        assert!(plain.contains("let x = a \\* b\\_c;"));
+        assert!(plain.contains("> Fine, but \\*ptr"));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::item::{Comment, Item, User};
+
+// Converters for the small subset of HTML that HN uses in text fields:
+// <p> between paragraphs, <a href>, <i>, <pre><code> and character entities.
+// Any other markup is dropped and its text kept.
+
+// Plain text, with blank lines between paragraphs, code blocks kept as they
+// are and links written as "label (url)"
+pub fn to_text(html: &str) -> String {
+    convert(html, Style::Text)
+}
+
+// Markdown, with code in fenced blocks and links as [label](url)
+pub fn to_markdown(html: &str) -> String {
+    convert(html, Style::Markdown)
+}
+
+// Types with HN text fields, which the API returns as HTML
+pub trait MapText: Sized {
+    // Rewrite every text field with `convert`, e.g. `html::to_text`
+    fn map_text(self, convert: fn(&str) -> String) -> Self;
+}
+
+impl MapText for Comment {
+    fn map_text(mut self, convert: fn(&str) -> String) -> Self {
+        self.text = self.text.as_deref().map(convert);
+        self
+    }
+}
+
+impl MapText for Item {
+    fn map_text(mut self, convert: fn(&str) -> String) -> Self {
+        let text = match &mut self {
+            Item::Story(story) => &mut story.text,
+            Item::Comment(comment) => &mut comment.text,
+            Item::Job(job) => &mut job.text,
+            Item::Poll(poll) => &mut poll.text,
+            Item::PollOpt(opt) => &mut opt.text,
+        };
+        *text = text.as_deref().map(convert);
+        self
+    }
+}
+
+impl MapText for User {
+    fn map_text(mut self, convert: fn(&str) -> String) -> Self {
+        self.about = self.about.as_deref().map(convert);
+        self
+    }
+}
+
+impl<T: MapText> MapText for Vec<T> {
+    fn map_text(self, convert: fn(&str) -> String) -> Self {
+        self.into_iter().map(|value| value.map_text(convert)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Text,
+    Markdown,
+}
+
+// A link whose label is still being written
+struct OpenLink {
+    href: String,
+    start: usize,  // where the label starts in the output
+    label: String, // the label as plain text, to compare with the URL
+}
+
+fn convert(html: &str, style: Style) -> String {
+    let mut output = String::new();
+    let mut in_pre = false;
+    let mut in_code = false; // inline code, where Markdown takes every character literally
+    let mut link: Option<OpenLink> = None;
+
+    for token in tokenize(html) {
+        match token {
+            Token::Text(raw) => {
+                let text = decode_entities(raw);
+                if let Some(link) = &mut link {
+                    link.label.push_str(&text);
+                }
+                if in_pre {
+                    output.push_str(&text);
+                } else {
+                    push_inline(&mut output, &text, style == Style::Markdown && !in_code);
+                }
+            }
+            Token::Open("p", _) => paragraph_break(&mut output),
+            Token::Open("pre", _) => {
+                paragraph_break(&mut output);
+                if style == Style::Markdown {
+                    output.push_str("```\n");
+                }
+                in_pre = true;
+            }
+            Token::Close("pre") => {
+                while output.ends_with('\n') {
+                    output.pop();
+                }
+                if style == Style::Markdown {
+                    output.push_str("\n```");
+                }
+                in_pre = false;
+                paragraph_break(&mut output);
+            }
+            Token::Open("code", _) | Token::Close("code") if !in_pre && style == Style::Markdown => {
+                in_code = !in_code;
+                output.push('`');
+            }
+            Token::Open("i" | "em", _) | Token::Close("i" | "em") if style == Style::Markdown => {
+                output.push('*');
+            }
+            Token::Open("b" | "strong", _) | Token::Close("b" | "strong") if style == Style::Markdown => {
+                output.push_str("**");
+            }
+            Token::Open("a", attributes) => {
+                let href = attribute(attributes, "href").map(decode_entities).unwrap_or_default();
+                link = Some(OpenLink { href, start: output.len(), label: String::new() });
+            }
+            Token::Close("a") => {
+                if let Some(link) = link.take() {
+                    finish_link(&mut output, link, style);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    output.trim().to_string()
+}
+
+// HN shortens long URLs in link labels to the first part followed by "..."
+fn finish_link(output: &mut String, link: OpenLink, style: Style) {
+    if link.href.is_empty() {
+        return;
+    }
+    let label = link.label.trim();
+    let is_url = label == link.href || label.strip_suffix("...").is_some_and(|start| link.href.starts_with(start));
+    match (style, is_url) {
+        (Style::Text, true) => {
+            output.truncate(link.start);
+            output.push_str(&link.href);
+        }
+        (Style::Text, false) => output.push_str(&format!(" ({})", link.href)),
+        (Style::Markdown, true) => {
+            output.truncate(link.start);
+            output.push_str(&format!("<{}>", link.href));
+        }
+        (Style::Markdown, false) => {
+            let text = output.split_off(link.start);
+            output.push_str(&format!("[{}](<{}>)", text.trim(), link.href));
+        }
+    }
+}
+
+// Text outside code blocks: runs of whitespace collapse to one space, and with
+// `escape` the characters that would start Markdown formatting are escaped
+fn push_inline(output: &mut String, text: &str, escape: bool) {
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !output.is_empty() && !output.ends_with(char::is_whitespace) {
+                output.push(' ');
+            }
+            continue;
+        }
+        if escape && matches!(c, '\\' | '*' | '_' | '`' | '<') {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+fn paragraph_break(output: &mut String) {
+    let trimmed = output.trim_end_matches(' ').len();
+    output.truncate(trimmed);
+    if output.is_empty() {
+        return;
+    }
+    while !output.ends_with("\n\n") {
+        output.push('\n');
+    }
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Open(&'a str, &'a str), // tag name and the raw attributes
+    Close(&'a str),
+}
+
+// Split HTML into text and tags. A '<' that doesn't start a tag is text.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        let tag = rest[start + 1..].find('>').and_then(|end| parse_tag(&rest[start + 1..start + 1 + end]));
+        match tag {
+            Some(tag) => {
+                if start > 0 {
+                    tokens.push(Token::Text(&rest[..start]));
+                }
+                tokens.push(tag);
+                let end = rest[start..].find('>').expect("tag was closed");
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                tokens.push(Token::Text(&rest[..=start]));
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    tokens
+}
+
+// The inside of a tag, e.g. `a href="..."` or `/i`
+fn parse_tag(inner: &str) -> Option<Token<'_>> {
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+    let name_end = inner.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    let rest = &inner[name_end..];
+    if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if !(rest.is_empty() || rest.starts_with(char::is_whitespace) || rest == "/") {
+        return None;
+    }
+    Some(if closing { Token::Close(name) } else { Token::Open(name, rest) })
+}
+
+// The value of a quoted attribute, still entity-encoded
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    while let Some(position) = rest.find(name) {
+        let after = rest[position + name.len()..].trim_start();
+        let preceded_by_space = rest[..position].ends_with(char::is_whitespace);
+        if let (true, Some(value)) = (preceded_by_space, after.strip_prefix('=')) {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+            return Some(match quote {
+                Some(quote) => value[1..].split(quote).next().unwrap_or_default(),
+                None => value.split(char::is_whitespace).next().unwrap_or_default(),
+            });
+        }
+        rest = &rest[position + name.len()..];
+    }
+    None
+}
+
+// Replace named and numeric character references; unknown ones are left as they are
+pub fn decode_entities(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+            let decoded = decode_entity(&rest[1..1 + end])?;
+            Some((decoded, end + 2))
+        });
+        match decoded {
+            Some((c, length)) => {
+                output.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    let code = match name.strip_prefix('#') {
+        Some(number) => match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        },
+        None => {
+            return match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => None,
+            }
+        }
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(to_text("It&#x27;s &amp; &lt;b&gt; &quot;x&quot; &#8212;"), "It's & <b> \"x\" —");
+        assert_eq!(decode_entities("&#X27;&apos;&nbsp;"), "'' ");
+    }
+
+    #[test]
+    fn leaves_unknown_entities_as_they_are() {
+        assert_eq!(decode_entities("&bogus; & &#xZZ; AT&T; &amp"), "&bogus; & &#xZZ; AT&T; &amp");
+        assert_eq!(to_text("a &verylongentityname; b"), "a &verylongentityname; b");
+    }
+
+    #[test]
+    fn paragraphs_become_blank_lines() {
+        assert_eq!(to_text("First.<p>Second.<p>Third."), "First.\n\nSecond.\n\nThird.");
+        assert_eq!(to_text("<p>Leading   and\nspread out<p>"), "Leading and spread out");
+        assert_eq!(to_markdown("One<p>Two"), "One\n\nTwo");
+    }
+
+    #[test]
+    fn code_blocks_keep_their_layout() {
+        let html = "Try:<pre><code>  fn main() {\n      println!(&quot;*hi*&quot;);\n  }\n</code></pre>Done.";
+        assert_eq!(to_text(html), "Try:\n\n  fn main() {\n      println!(\"*hi*\");\n  }\n\nDone.");
+        assert_eq!(to_markdown(html), "Try:\n\n```\n  fn main() {\n      println!(\"*hi*\");\n  }\n```\n\nDone.");
+    }
+
+    #[test]
+    fn inline_markup_in_markdown() {
+        assert_eq!(to_markdown("<i>really</i> use <code>a*b_c</code>"), "*really* use `a*b_c`");
+        assert_eq!(to_markdown("2 * 3 = snake_case"), "2 \\* 3 = snake\\_case");
+        assert_eq!(to_text("<i>really</i> use <code>a*b_c</code>"), "really use a*b_c");
+    }
+
+    #[test]
+    fn links_with_labels() {
+        let html = "See <a href=\"https://example.com/a_(b)\" rel=\"nofollow\">the docs</a>.";
+        assert_eq!(to_text(html), "See the docs (https://example.com/a_(b)).");
+        assert_eq!(to_markdown(html), "See [the docs](<https://example.com/a_(b)>).");
+    }
+
+    #[test]
+    fn links_with_shortened_urls_as_labels() {
+        let html = "<a href=\"https:&#x2F;&#x2F;example.com&#x2F;a&#x2F;very&#x2F;long&#x2F;path\" \
+                    rel=\"nofollow\">https:&#x2F;&#x2F;example.com&#x2F;a&#x2F;very&#x2F;...</a>";
+        assert_eq!(to_text(html), "https://example.com/a/very/long/path");
+        assert_eq!(to_markdown(html), "<https://example.com/a/very/long/path>");
+
+        // A "..." label that isn't the start of the URL is an ordinary label
+        let html = "<a href=\"https://example.com/x\">wait for it...</a>";
+        assert_eq!(to_text(html), "wait for it... (https://example.com/x)");
+    }
+
+    #[test]
+    fn a_bare_less_than_is_text() {
+        assert_eq!(to_text("if a < b and c <d> 3 <3"), "if a < b and c 3 <3");
+        assert_eq!(to_text("x <1 y"), "x <1 y");
+        assert_eq!(to_markdown("a < b"), "a \\< b");
+    }
+
+    #[test]
+    fn other_markup_is_dropped() {
+        assert_eq!(to_text("<span class=\"x\">kept</span> <br/>text"), "kept text");
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, ConstScoreQuery, EmptyQuery, Occur, PhraseQuery, Query as TantivyQuery,
+    RangeQuery, TermQuery,
+};
+use tantivy::schema::{
+    Field, IndexRecordOption, NumericOptions, Schema, TextFieldIndexing, TextOptions, Value, FAST, INDEXED, STORED,
+    STRING,
+};
+use tantivy::snippet::{Snippet, SnippetGenerator};
+use tantivy::{DocAddress, DocId, Index, IndexReader, IndexWriter, Score, SegmentReader, TantivyDocument, Term};
+
+use crate::archive::Archive;
+use crate::error::HNError;
+use crate::html;
+use crate::item::Item;
+use crate::query::{Comparison, Filter, Query};
+use crate::rank::RankOptions;
+
+// Memory the index writer may use before it flushes a segment
+const WRITER_MEMORY: usize = 50_000_000;
+
+// Stories read from the archive per commit when rebuilding the whole index
+const REBUILD_BATCH: usize = 1000;
+
+// Where the index for the archive at `archive_path` lives: a directory beside it
+pub fn index_dir_for(archive_path: &Path) -> PathBuf {
+    archive_path.with_extension("index")
+}
+
+// An inverted index over archived stories, jobs and polls, one document each,
+// with the text of every archived reply folded into the story's `comments`
+// field. Kept up to date by `sync`; the archive stays the source of truth.
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    fields: Fields,
+}
+
+struct Fields {
+    id: Field,
+    kind: Field,
+    title: Field,
+    text: Field,
+    url: Field,
+    domain: Field, // the host and each domain above it, so domain:example.com matches blog.example.com
+    author: Field, // lowercased
+    score: Field,
+    comment_count: Field,
+    time: Field,
+    comments: Field,
+}
+
+// A story that matched, with the passages that made it match
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IndexHit {
+    pub id: u32,
+    pub score: f32,
+    pub highlights: Vec<String>, // short passages with the matching words in **bold**
+}
+
+// How `SearchIndex::search` picks and orders hits
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IndexSearch {
+    pub limit: usize,
+    pub offset: usize,
+    pub by_date: bool, // newest first instead of best first
+}
+
+fn schema() -> (Schema, Fields) {
+    let stemmed = TextOptions::default().set_stored().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer("en_stem")
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    );
+    let numeric = NumericOptions::from(INDEXED | STORED | FAST);
+
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        id: builder.add_u64_field("id", numeric.clone()),
+        kind: builder.add_text_field("kind", STRING | STORED),
+        title: builder.add_text_field("title", stemmed.clone()),
+        text: builder.add_text_field("text", stemmed.clone()),
+        url: builder.add_text_field("url", tantivy::schema::TEXT | STORED),
+        domain: builder.add_text_field("domain", STRING | STORED),
+        author: builder.add_text_field("author", STRING | STORED),
+        score: builder.add_i64_field("score", numeric.clone()),
+        comment_count: builder.add_i64_field("comment_count", numeric.clone()),
+        time: builder.add_i64_field("time", numeric),
+        comments: builder.add_text_field("comments", stemmed),
+    };
+    (builder.build(), fields)
+}
+
+impl SearchIndex {
+    pub fn open(dir: &Path) -> Result<Self, HNError> {
+        std::fs::create_dir_all(dir).map_err(|e| HNError::Cache(format!("cannot create {}: {}", dir.display(), e)))?;
+        let directory = MmapDirectory::open(dir).map_err(tantivy::TantivyError::from)?;
+        let (schema, fields) = schema();
+        Self::init(Index::open_or_create(directory, schema)?, fields)
+    }
+
+    // The index at `dir` if a sync has built one
+    pub fn open_existing(dir: &Path) -> Result<Option<Self>, HNError> {
+        if dir.exists() {
+            Self::open(dir).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn in_memory() -> Result<Self, HNError> {
+        let (schema, fields) = schema();
+        Self::init(Index::create_in_ram(schema), fields)
+    }
+
+    fn init(index: Index, fields: Fields) -> Result<Self, HNError> {
+        let reader = index.reader()?;
+        Ok(Self { index, reader, fields })
+    }
+
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    // Re-index the stories that `items` belong to, after they have been stored in
+    // the archive. Returns the number of stories written.
+    pub fn update(&self, archive: &Archive, items: &[Item]) -> Result<usize, HNError> {
+        let mut roots = BTreeSet::new();
+        for item in items {
+            match item.parent() {
+                None => roots.insert(item.id()),
+                // A reply whose story isn't archived yet is indexed along with the story
+                Some(_) => archive.root_of(item.id())?.is_some_and(|root| roots.insert(root)),
+            };
+        }
+        if roots.is_empty() {
+            return Ok(0);
+        }
+
+        let writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        let mut written = 0;
+        for id in roots {
+            writer.delete_term(Term::from_field_u64(self.fields.id, id.into()));
+            written += self.add_story(&writer, archive, id)? as usize;
+        }
+        self.commit(writer)?;
+        Ok(written)
+    }
+
+    // Replace the whole index with what the archive holds
+    pub fn rebuild(&self, archive: &Archive) -> Result<usize, HNError> {
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        writer.delete_all_documents()?;
+        let mut written = 0;
+        let mut after = 0;
+        loop {
+            let ids = archive.root_ids(after, REBUILD_BATCH)?;
+            let Some(&last) = ids.last() else { break };
+            for id in ids {
+                written += self.add_story(&writer, archive, id)? as usize;
+            }
+            writer.commit()?;
+            after = last;
+        }
+        self.commit(writer)?;
+        Ok(written)
+    }
+
+    fn commit(&self, mut writer: IndexWriter) -> Result<(), HNError> {
+        writer.commit()?;
+        writer.wait_merging_threads()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    // Add the archived story `id` with its replies; false if it can't be shown
+    fn add_story(&self, writer: &IndexWriter, archive: &Archive, id: u32) -> Result<bool, HNError> {
+        let Some(story) = archive.item(id)? else { return Ok(false) };
+        if story.is_deleted() || story.is_dead() {
+            return Ok(false);
+        }
+        let fields = &self.fields;
+        let mut document = TantivyDocument::default();
+        document.add_u64(fields.id, id.into());
+        document.add_text(fields.kind, story.kind());
+        document.add_text(fields.title, story.title().unwrap_or_default());
+        document.add_text(fields.text, html::to_text(story.text().unwrap_or_default()));
+        if let Some(url) = story.url() {
+            document.add_text(fields.url, url);
+            for domain in domains(url) {
+                document.add_text(fields.domain, domain);
+            }
+        }
+        document.add_text(fields.author, story.by().unwrap_or_default().to_lowercase());
+        document.add_i64(fields.score, story.score().unwrap_or(0).into());
+        document.add_i64(fields.comment_count, story.descendants().unwrap_or(0).into());
+        document.add_i64(fields.time, story.time());
+        for reply in archive.descendants(id)? {
+            if let (false, false, Some(text)) = (reply.is_deleted(), reply.is_dead(), reply.text()) {
+                document.add_text(fields.comments, html::to_text(text));
+            }
+        }
+        writer.add_document(document)?;
+        Ok(true)
+    }
+
+    // The stories matching `query`, with their relevance and highlighted passages.
+    // Words match in the title, text, URL or any comment; field weights come from
+    // `ranking`, with comments weighed like the story text.
+    pub fn search(&self, query: &Query, ranking: &RankOptions, options: IndexSearch) -> Result<Vec<IndexHit>, HNError> {
+        let searcher = self.reader.searcher();
+        let query = self.translate(query, ranking)?;
+        let top = TopDocs::with_limit(options.limit.max(1)).and_offset(options.offset);
+
+        let addresses: Vec<(f32, DocAddress)> = if options.by_date {
+            // Sort on (time, relevance) so each hit keeps its relevance score
+            let by_time = top.tweak_score(|segment: &SegmentReader| {
+                let times = segment.fast_fields().i64("time").ok();
+                move |doc: DocId, score: Score| (times.as_ref().and_then(|times| times.first(doc)).unwrap_or(0), score)
+            });
+            searcher.search(&query, &by_time)?.into_iter().map(|((_, score), address)| (score, address)).collect()
+        } else {
+            searcher.search(&query, &top)?
+        };
+
+        let generators = [self.fields.title, self.fields.text, self.fields.comments]
+            .into_iter()
+            .map(|field| SnippetGenerator::create(&searcher, &*query, field))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut hits = Vec::new();
+        for (score, address) in addresses {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let Some(id) = document.get_first(self.fields.id).and_then(|value| value.as_u64()) else { continue };
+            let highlights = generators
+                .iter()
+                .map(|generator| generator.snippet_from_doc(&document))
+                .filter(|snippet| !snippet.is_empty())
+                .map(|snippet| highlight(&snippet))
+                .collect();
+            hits.push(IndexHit { id: id as u32, score, highlights });
+        }
+        Ok(hits)
+    }
+
+    fn translate(&self, query: &Query, ranking: &RankOptions) -> Result<Box<dyn TantivyQuery>, HNError> {
+        let fields = &self.fields;
+        Ok(match query {
+            Query::Term(term) => {
+                let weighted = [
+                    (fields.title, ranking.title_weight),
+                    (fields.text, ranking.text_weight),
+                    (fields.comments, ranking.text_weight),
+                    (fields.url, ranking.text_weight),
+                ];
+                let mut clauses = Vec::new();
+                for (field, weight) in weighted {
+                    clauses.push((Occur::Should, boost(self.words(field, std::slice::from_ref(term))?, weight)));
+                }
+                let author = Term::from_field_text(fields.author, &term.to_lowercase());
+                clauses.push((Occur::Should, boost(term_query(author), ranking.author_weight)));
+                Box::new(BooleanQuery::new(clauses))
+            }
+            Query::Phrase(words) => {
+                let mut clauses = Vec::new();
+                for (field, weight) in [
+                    (fields.title, ranking.title_weight),
+                    (fields.text, ranking.text_weight),
+                    (fields.comments, ranking.text_weight),
+                ] {
+                    clauses.push((Occur::Should, boost(self.words(field, words)?, weight)));
+                }
+                Box::new(BooleanQuery::new(clauses))
+            }
+            Query::Filter(filter) => self.filter(filter)?,
+            Query::Not(query) => Box::new(BooleanQuery::new(vec![
+                (Occur::Must, Box::new(ConstScoreQuery::new(Box::new(AllQuery), 0.0)) as Box<dyn TantivyQuery>),
+                (Occur::MustNot, self.translate(query, ranking)?),
+            ])),
+            Query::And(queries) => Box::new(BooleanQuery::new(
+                queries
+                    .iter()
+                    .map(|query| Ok((Occur::Must, self.translate(query, ranking)?)))
+                    .collect::<Result<_, HNError>>()?,
+            )),
+            Query::Or(queries) => Box::new(BooleanQuery::new(
+                queries
+                    .iter()
+                    .map(|query| Ok((Occur::Should, self.translate(query, ranking)?)))
+                    .collect::<Result<_, HNError>>()?,
+            )),
+        })
+    }
+
+    // Qualifiers only select stories; they add nothing to the relevance
+    fn filter(&self, filter: &Filter) -> Result<Box<dyn TantivyQuery>, HNError> {
+        let fields = &self.fields;
+        let query: Box<dyn TantivyQuery> = match filter {
+            Filter::Title(words) => return self.words(fields.title, words),
+            Filter::Author(author) => term_query(Term::from_field_text(fields.author, &author.to_lowercase())),
+            Filter::Domain(domain) => term_query(Term::from_field_text(fields.domain, domain)),
+            Filter::Type(kind) => term_query(Term::from_field_text(fields.kind, kind)),
+            Filter::Points(cmp, bound) => range(fields.score, *cmp, *bound),
+            Filter::Comments(cmp, bound) => range(fields.comment_count, *cmp, *bound),
+            Filter::Before(time) => range(fields.time, Comparison::Less, *time),
+            Filter::After(time) => range(fields.time, Comparison::Greater, *time),
+        };
+        Ok(Box::new(ConstScoreQuery::new(query, 0.0)))
+    }
+
+    // Consecutive words in one field, tokenized the way the field was indexed
+    fn words(&self, field: Field, words: &[String]) -> Result<Box<dyn TantivyQuery>, HNError> {
+        let mut analyzer = self.index.tokenizer_for_field(field)?;
+        let mut terms = Vec::new();
+        for word in words {
+            let mut stream = analyzer.token_stream(word);
+            while stream.advance() {
+                terms.push(Term::from_field_text(field, &stream.token().text));
+            }
+        }
+        Ok(match terms.len() {
+            0 => Box::new(EmptyQuery),
+            1 => term_query(terms.remove(0)),
+            _ => Box::new(PhraseQuery::new(terms)),
+        })
+    }
+}
+
+fn term_query(term: Term) -> Box<dyn TantivyQuery> {
+    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))
+}
+
+fn boost(query: Box<dyn TantivyQuery>, weight: f64) -> Box<dyn TantivyQuery> {
+    Box::new(BoostQuery::new(query, weight as f32))
+}
+
+fn range(field: Field, cmp: Comparison, bound: i64) -> Box<dyn TantivyQuery> {
+    let term = Term::from_field_i64(field, bound);
+    let (lower, upper) = match cmp {
+        Comparison::Less => (Bound::Unbounded, Bound::Excluded(term)),
+        Comparison::LessOrEqual => (Bound::Unbounded, Bound::Included(term)),
+        Comparison::Equal => (Bound::Included(term.clone()), Bound::Included(term)),
+        Comparison::GreaterOrEqual => (Bound::Included(term), Bound::Unbounded),
+        Comparison::Greater => (Bound::Excluded(term), Bound::Unbounded),
+    };
+    Box::new(RangeQuery::new(lower, upper))
+}
+
+// "blog.example.com" -> blog.example.com, example.com, com
+fn domains(url: &str) -> Vec<String> {
+    let Some(host) = crate::query::domain_of(url) else { return Vec::new() };
+    let mut domains = vec![host.clone()];
+    let mut rest = host.as_str();
+    while let Some((_, parent)) = rest.split_once('.') {
+        domains.push(parent.to_string());
+        rest = parent;
+    }
+    domains
+}
+
+// The snippet's text with each highlighted range wrapped in **
+fn highlight(snippet: &Snippet) -> String {
+    let fragment = snippet.fragment();
+    let mut output = String::new();
+    let mut start = 0;
+    for range in snippet.highlighted() {
+        if range.start < start {
+            continue;
+        }
+        output.push_str(&fragment[start..range.start]);
+        output.push_str("**");
+        output.push_str(&fragment[range.clone()]);
+        output.push_str("**");
+        start = range.end;
+    }
+    output.push_str(&fragment[start..]);
+    output.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story};
+
+    fn story_at(id: u32, title: &str, url: &str, score: i32, kids: &[u32]) -> Item {
+        let mut item = story(id, "pg", title, kids);
+        if let Item::Story(story) = &mut item {
+            story.url = Some(url.to_string());
+            story.score = Some(score);
+        }
+        item
+    }
+
+    fn indexed(items: &[Item]) -> (Archive, SearchIndex) {
+        let archive = Archive::in_memory().unwrap();
+        archive.put_items(items).unwrap();
+        let index = SearchIndex::in_memory().unwrap();
+        index.rebuild(&archive).unwrap();
+        (archive, index)
+    }
+
+    fn stories() -> Vec<Item> {
+        vec![
+            story_at(1, "Rust green threads explained", "https://blog.example.com/rust", 150, &[]),
+            story_at(2, "Threads are green in Go", "https://golang.org/doc", 10, &[]),
+            story_at(3, "Async Rust", "https://example.org/async", 1, &[4]),
+            comment(4, "dang", 3, "I prefer green threads", &[]),
+        ]
+    }
+
+    fn search(index: &SearchIndex, query: &Query) -> Vec<IndexHit> {
+        index.search(query, &RankOptions::default(), IndexSearch { limit: 10, ..IndexSearch::default() }).unwrap()
+    }
+
+    // Matching IDs in ID order, whatever their relevance
+    fn ids(index: &SearchIndex, query: &str) -> Vec<u32> {
+        let mut ids: Vec<u32> = search(index, &Query::parse(query).unwrap()).iter().map(|hit| hit.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn phrases_need_consecutive_words() {
+        let (_, index) = indexed(&stories());
+        assert_eq!(index.num_docs(), 3);
+        assert_eq!(ids(&index, "\"green threads\""), [1, 3]);
+        assert_eq!(ids(&index, "green threads"), [1, 2, 3]);
+        // Words are stemmed, in the index and in queries
+        assert_eq!(ids(&index, "\"green thread\""), [1, 3]);
+    }
+
+    #[test]
+    fn highlights_the_matching_words() {
+        let (_, index) = indexed(&stories());
+        let hits = search(&index, &Query::parse("\"green threads\"").unwrap());
+        let highlights = |id| hits.iter().find(|hit| hit.id == id).unwrap().highlights.clone();
+        assert_eq!(highlights(1), ["Rust **green** **threads** explained"]);
+        // A story found through a reply shows the reply
+        assert_eq!(highlights(3), ["I prefer **green** **threads**"]);
+    }
+
+    #[test]
+    fn domains_match_the_host_and_the_domains_above_it() {
+        let (_, index) = indexed(&stories());
+        assert_eq!(ids(&index, "domain:example.com"), [1]);
+        assert_eq!(ids(&index, "domain:blog.example.com"), [1]);
+        assert_eq!(ids(&index, "domain:golang.org"), [2]);
+        assert!(ids(&index, "domain:ample.com").is_empty());
+        assert_eq!(domains("https://blog.example.com/rust"), ["blog.example.com", "example.com", "com"]);
+    }
+
+    #[test]
+    fn filters_on_ranges() {
+        let (_, index) = indexed(&stories());
+        assert_eq!(ids(&index, "points>100"), [1]);
+        assert_eq!(ids(&index, "points<=10"), [2, 3]);
+        assert_eq!(ids(&index, "points=10"), [2]);
+        assert_eq!(ids(&index, "comments>=1"), [3]);
+        assert_eq!(ids(&index, "rust points>=1 comments=0"), [1]);
+
+        let time = |filter| search(&index, &Query::Filter(filter)).iter().map(|hit| hit.id).collect::<Vec<_>>();
+        assert_eq!(time(Filter::After(1_700_000_002)), [3]);
+        assert_eq!(time(Filter::Before(1_700_000_002)), [1]);
+    }
+
+    #[test]
+    fn sorts_by_date_on_request() {
+        let (_, index) = indexed(&stories());
+        let options = IndexSearch { limit: 10, by_date: true, ..IndexSearch::default() };
+        let hits = index.search(&Query::parse("rust").unwrap(), &RankOptions::default(), options).unwrap();
+        assert_eq!(hits.iter().map(|hit| hit.id).collect::<Vec<_>>(), [3, 1]);
+    }
+
+    #[test]
+    fn updates_replace_stories_instead_of_adding_them() {
+        let (archive, index) = indexed(&stories());
+
+        let edited = story_at(1, "Rust fibers explained", "https://blog.example.com/rust", 150, &[]);
+        archive.put_items(std::slice::from_ref(&edited)).unwrap();
+        assert_eq!(index.update(&archive, &[edited]).unwrap(), 1);
+        assert_eq!(index.num_docs(), 3);
+        assert_eq!(ids(&index, "fibers"), [1]);
+        assert_eq!(ids(&index, "\"green threads\""), [3]);
+
+        // A new reply re-indexes its story
+        let reply = comment(5, "dang", 2, "tokio rocks", &[]);
+        archive.put_items(std::slice::from_ref(&reply)).unwrap();
+        assert_eq!(index.update(&archive, &[reply]).unwrap(), 1);
+        assert_eq!(index.num_docs(), 3);
+        assert_eq!(ids(&index, "tokio"), [2]);
+    }
+}
@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+
+// Any HN item, tagged on the API's `type` field
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Item {
+    Story(Story),
+    Comment(Comment),
+    Job(Job),
+    Poll(Poll),
+    #[serde(rename = "pollopt")]
+    PollOpt(PollOpt),
+}
+
+// Struct to hold HN story metadata
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Story {
+    pub id: u32,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub dead: bool,
+    pub title: Option<String>, // missing on deleted stories
+    pub url: Option<String>,
+    pub text: Option<String>,
+    pub by: Option<String>,
+    pub score: Option<i32>,
+    pub descendants: Option<i32>, // number of comments
+    #[serde(default)]
+    pub time: i64,
+    pub kids: Option<Vec<u32>>,
+}
+
+// Struct to hold HN comment with optional text
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Comment {
+    pub id: u32,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub dead: bool,
+    pub text: Option<String>, // Made optional to handle deleted/missing comments
+    pub by: Option<String>,
+    #[serde(default)]
+    pub time: i64,
+    pub parent: u32,
+    pub kids: Option<Vec<u32>>,
+}
+
+// Job postings; these usually have no author and no comments
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Job {
+    pub id: u32,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub dead: bool,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub text: Option<String>,
+    pub by: Option<String>,
+    pub score: Option<i32>,
+    #[serde(default)]
+    pub time: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Poll {
+    pub id: u32,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub dead: bool,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    pub by: Option<String>,
+    pub score: Option<i32>,
+    pub descendants: Option<i32>,
+    #[serde(default)]
+    pub time: i64,
+    pub kids: Option<Vec<u32>>,
+    pub parts: Option<Vec<u32>>, // IDs of the poll's options
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PollOpt {
+    pub id: u32,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub dead: bool,
+    pub poll: u32, // ID of the poll this option belongs to
+    pub text: Option<String>,
+    pub by: Option<String>,
+    pub score: Option<i32>,
+    #[serde(default)]
+    pub time: i64,
+}
+
+impl Item {
+    pub fn id(&self) -> u32 {
+        match self {
+            Item::Story(story) => story.id,
+            Item::Comment(comment) => comment.id,
+            Item::Job(job) => job.id,
+            Item::Poll(poll) => poll.id,
+            Item::PollOpt(opt) => opt.id,
+        }
+    }
+
+    // The API's name for this item's type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Item::Story(_) => "story",
+            Item::Comment(_) => "comment",
+            Item::Job(_) => "job",
+            Item::Poll(_) => "poll",
+            Item::PollOpt(_) => "pollopt",
+        }
+    }
+
+    pub fn by(&self) -> Option<&str> {
+        match self {
+            Item::Story(story) => story.by.as_deref(),
+            Item::Comment(comment) => comment.by.as_deref(),
+            Item::Job(job) => job.by.as_deref(),
+            Item::Poll(poll) => poll.by.as_deref(),
+            Item::PollOpt(opt) => opt.by.as_deref(),
+        }
+    }
+
+    pub fn time(&self) -> i64 {
+        match self {
+            Item::Story(story) => story.time,
+            Item::Comment(comment) => comment.time,
+            Item::Job(job) => job.time,
+            Item::Poll(poll) => poll.time,
+            Item::PollOpt(opt) => opt.time,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Item::Story(story) => story.title.as_deref(),
+            Item::Job(job) => job.title.as_deref(),
+            Item::Poll(poll) => poll.title.as_deref(),
+            Item::Comment(_) | Item::PollOpt(_) => None,
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Item::Story(story) => story.text.as_deref(),
+            Item::Comment(comment) => comment.text.as_deref(),
+            Item::Job(job) => job.text.as_deref(),
+            Item::Poll(poll) => poll.text.as_deref(),
+            Item::PollOpt(opt) => opt.text.as_deref(),
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Item::Story(story) => story.url.as_deref(),
+            Item::Job(job) => job.url.as_deref(),
+            Item::Comment(_) | Item::Poll(_) | Item::PollOpt(_) => None,
+        }
+    }
+
+    pub fn score(&self) -> Option<i32> {
+        match self {
+            Item::Story(story) => story.score,
+            Item::Job(job) => job.score,
+            Item::Poll(poll) => poll.score,
+            Item::PollOpt(opt) => opt.score,
+            Item::Comment(_) => None,
+        }
+    }
+
+    pub fn descendants(&self) -> Option<i32> {
+        match self {
+            Item::Story(story) => story.descendants,
+            Item::Poll(poll) => poll.descendants,
+            Item::Comment(_) | Item::Job(_) | Item::PollOpt(_) => None,
+        }
+    }
+
+    // IDs of direct replies, in HN's ranking order
+    pub fn kids(&self) -> &[u32] {
+        let kids = match self {
+            Item::Story(story) => &story.kids,
+            Item::Comment(comment) => &comment.kids,
+            Item::Poll(poll) => &poll.kids,
+            Item::Job(_) | Item::PollOpt(_) => &None,
+        };
+        kids.as_deref().unwrap_or_default()
+    }
+
+    // The item this one hangs off: a comment's parent or a poll option's poll
+    pub fn parent(&self) -> Option<u32> {
+        match self {
+            Item::Comment(comment) => Some(comment.parent),
+            Item::PollOpt(opt) => Some(opt.poll),
+            Item::Story(_) | Item::Job(_) | Item::Poll(_) => None,
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        match self {
+            Item::Story(story) => story.deleted,
+            Item::Comment(comment) => comment.deleted,
+            Item::Job(job) => job.deleted,
+            Item::Poll(poll) => poll.deleted,
+            Item::PollOpt(opt) => opt.deleted,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        match self {
+            Item::Story(story) => story.dead,
+            Item::Comment(comment) => comment.dead,
+            Item::Job(job) => job.dead,
+            Item::Poll(poll) => poll.dead,
+            Item::PollOpt(opt) => opt.dead,
+        }
+    }
+}
+
+// Struct to hold a HN user profile
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct User {
+    pub id: String,
+    pub created: i64,
+    pub karma: i32,
+    pub about: Option<String>, // HTML, only present if the user filled it in
+    pub submitted: Option<Vec<u32>>,
+}
+
+// Recently changed items and profiles, as returned by /updates.json
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Updates {
+    pub items: Vec<u32>,
+    pub profiles: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn parse(payload: Value) -> Item {
+        serde_json::from_value(payload).unwrap()
+    }
+
+    // Serializing and parsing again loses nothing
+    fn assert_round_trips(item: &Item) {
+        let value = serde_json::to_value(item).unwrap();
+        assert_eq!(serde_json::to_value(parse(value.clone())).unwrap(), value);
+    }
+
+    #[test]
+    fn stories() {
+        let item = parse(json!({
+            "by": "dhouston", "descendants": 71, "id": 8863, "kids": [8952, 9224, 8917],
+            "score": 111, "time": 1175714200, "title": "My YC app: Dropbox - Throw away your USB drive",
+            "type": "story", "url": "http://www.getdropbox.com/u/2/screencast.html"
+        }));
+        let Item::Story(story) = &item else { panic!("{:?}", item) };
+        assert_eq!(story.title.as_deref(), Some("My YC app: Dropbox - Throw away your USB drive"));
+        assert_eq!((story.score, story.descendants), (Some(111), Some(71)));
+        assert_eq!(item.kids(), [8952, 9224, 8917]);
+        assert!(!item.is_deleted() && !item.is_dead());
+        assert_eq!(item.kind(), "story");
+        assert_round_trips(&item);
+    }
+
+    #[test]
+    fn comments() {
+        let item = parse(json!({
+            "by": "norvig", "id": 2921983, "kids": [2922097, 2922429], "parent": 2921506,
+            "text": "Aw shucks, guys ... you make me blush with your compliments.", "time": 1314211127,
+            "type": "comment"
+        }));
+        assert!(matches!(item, Item::Comment(_)));
+        assert_eq!(item.parent(), Some(2921506));
+        assert_eq!(item.by(), Some("norvig"));
+        assert_eq!(item.title(), None);
+        assert_round_trips(&item);
+    }
+
+    #[test]
+    fn jobs() {
+        let item = parse(json!({
+            "by": "justin", "id": 192327, "score": 6,
+            "text": "Justin.tv is the biggest live video site online.", "time": 1210981217,
+            "title": "Justin.tv is looking for a Lead Flash Engineer!", "type": "job", "url": ""
+        }));
+        let Item::Job(job) = &item else { panic!("{:?}", item) };
+        assert_eq!(job.title.as_deref(), Some("Justin.tv is looking for a Lead Flash Engineer!"));
+        assert_eq!(item.kind(), "job");
+        assert!(item.kids().is_empty());
+        assert_eq!(item.descendants(), None);
+        assert_round_trips(&item);
+    }
+
+    #[test]
+    fn polls_and_their_options() {
+        let item = parse(json!({
+            "by": "pg", "descendants": 54, "id": 126809, "kids": [126822, 126823], "parts": [126810, 126811, 126812],
+            "score": 46, "text": "", "time": 1204403652,
+            "title": "Poll: What would happen if News.YC had explicit support for polls?", "type": "poll"
+        }));
+        let Item::Poll(poll) = &item else { panic!("{:?}", item) };
+        assert_eq!(poll.parts.as_deref(), Some(&[126810, 126811, 126812][..]));
+        assert_eq!(item.kind(), "poll");
+        assert_round_trips(&item);
+
+        let item = parse(json!({
+            "by": "pg", "id": 160705, "poll": 160704, "score": 335,
+            "text": "Yes, ban them; I'm tired of seeing Valleywag stories on News.YC.", "time": 1207886576,
+            "type": "pollopt"
+        }));
+        assert!(matches!(item, Item::PollOpt(_)));
+        assert_eq!(item.kind(), "pollopt");
+        assert_eq!(item.parent(), Some(160704));
+        assert_eq!(item.score(), Some(335));
+        assert_round_trips(&item);
+    }
+
+    #[test]
+    fn deleted_and_dead_items() {
+        // Deleted items keep little more than their ID, type and place in the thread
+        let item =
+            parse(json!({"deleted": true, "id": 2921984, "parent": 2921983, "time": 1314211300, "type": "comment"}));
+        assert!(matches!(item, Item::Comment(_)));
+        assert!(item.is_deleted() && !item.is_dead());
+        assert_eq!((item.by(), item.text()), (None, None));
+        assert_round_trips(&item);
+
+        let item = parse(json!({"deleted": true, "id": 8864, "time": 1175714300, "type": "story"}));
+        assert!(item.is_deleted());
+        assert_eq!(item.title(), None);
+
+        let item = parse(json!({
+            "by": "spammer", "dead": true, "id": 8865, "time": 1175714400, "title": "Buy now", "type": "story"
+        }));
+        assert!(item.is_dead() && !item.is_deleted());
+        assert_round_trips(&item);
+    }
+
+    #[test]
+    fn unknown_types_are_rejected() {
+        let error = serde_json::from_value::<Item>(json!({"id": 1, "type": "ad"})).unwrap_err();
+        assert!(error.to_string().contains("unknown variant `ad`"), "{}", error);
+        assert!(serde_json::from_value::<Item>(json!({"id": 1})).is_err());
+    }
+
+    #[test]
+    fn users_and_updates() {
+        let user: User = serde_json::from_value(json!({
+            "about": "Bug fixer.", "created": 1160418111, "id": "pg", "karma": 157236, "submitted": [39123, 39122]
+        }))
+        .unwrap();
+        assert_eq!((user.id.as_str(), user.karma), ("pg", 157236));
+        assert_eq!(user.submitted, Some(vec![39123, 39122]));
+
+        let user: User = serde_json::from_value(json!({"created": 1173923446, "id": "jl", "karma": 2937})).unwrap();
+        assert_eq!((user.about, user.submitted), (None, None));
+
+        let updates: Updates =
+            serde_json::from_value(json!({"items": [8423305, 8420805], "profiles": ["thefox", "mdda"]})).unwrap();
+        assert_eq!(updates.items, [8423305, 8420805]);
+        assert_eq!(updates.profiles, ["thefox", "mdda"]);
+    }
+}
@@ -0,0 +1,22 @@
+pub mod algolia;
+pub mod archive;
+pub mod cache;
+pub mod citation;
+pub mod client;
+pub mod error;
+pub mod html;
+pub mod index;
+pub mod item;
+pub mod link;
+pub mod lookup;
+pub mod policy;
+pub mod query;
+pub mod rag;
+pub mod rank;
+pub mod record;
+pub mod search;
+pub mod semantic;
+pub mod summarize;
+pub mod sync;
+mod task;
+pub mod tree;
@@ -0,0 +1,115 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::error::HNError;
+
+// What a pasted HN reference points at
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HnLink {
+    Item(u32),
+    User(String),
+    Site(String), // a from?site= listing of stories from one domain
+}
+
+impl HnLink {
+    // Accepts a bare item ID or any news.ycombinator.com link to an item
+    // (item, context, reply), a user (user, submitted, threads, favorites) or
+    // a site (from?site=). The scheme may be left out.
+    pub fn parse(input: &str) -> Result<Self, HNError> {
+        let input = input.trim();
+        if let Ok(id) = input.parse::<u32>() {
+            return Ok(HnLink::Item(id));
+        }
+
+        let url = Url::parse(input)
+            .ok()
+            .filter(|url| url.has_host())
+            .or_else(|| Url::parse(&format!("https://{}", input)).ok())
+            .ok_or_else(|| invalid(input))?;
+        if !matches!(url.host_str(), Some("news.ycombinator.com" | "ycombinator.com" | "www.ycombinator.com")) {
+            return Err(invalid(input));
+        }
+
+        let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+        let link = match url.path().trim_matches('/') {
+            "item" | "context" | "reply" => param("id").and_then(|id| id.parse().ok()).map(HnLink::Item),
+            "user" | "submitted" | "threads" | "favorites" => param("id").map(HnLink::User),
+            "from" => param("site").map(HnLink::Site),
+            _ => None,
+        };
+        link.ok_or_else(|| invalid(input))
+    }
+}
+
+fn invalid(input: &str) -> HNError {
+    HNError::InvalidArgument(format!(
+        "'{}' is not an item ID or a news.ycombinator.com item, user or from?site= link",
+        input
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> HnLink {
+        HnLink::parse(input).unwrap_or_else(|e| panic!("{}: {}", input, e))
+    }
+
+    #[test]
+    fn bare_ids() {
+        assert_eq!(parse("8863"), HnLink::Item(8863));
+        assert_eq!(parse("  8863\n"), HnLink::Item(8863));
+    }
+
+    #[test]
+    fn item_links() {
+        for link in [
+            "https://news.ycombinator.com/item?id=8863",
+            "http://news.ycombinator.com/item?id=8863",
+            "news.ycombinator.com/item?id=8863",
+            "https://www.ycombinator.com/item?id=8863",
+            "www.ycombinator.com/item?id=8863",
+            "https://news.ycombinator.com/item?id=8863&p=2#8952",
+            "https://news.ycombinator.com/item?goto=news&id=8863",
+            "https://news.ycombinator.com/context?id=8863",
+            "https://news.ycombinator.com/reply?id=8863&goto=item%3Fid%3D8862",
+        ] {
+            assert_eq!(parse(link), HnLink::Item(8863), "{}", link);
+        }
+    }
+
+    #[test]
+    fn user_and_site_links() {
+        assert_eq!(parse("https://news.ycombinator.com/user?id=pg"), HnLink::User("pg".to_string()));
+        assert_eq!(parse("news.ycombinator.com/submitted?id=pg#top"), HnLink::User("pg".to_string()));
+        assert_eq!(parse("https://news.ycombinator.com/threads?id=dang&next=1"), HnLink::User("dang".to_string()));
+        assert_eq!(parse("https://news.ycombinator.com/favorites?id=pg"), HnLink::User("pg".to_string()));
+        assert_eq!(parse("https://news.ycombinator.com/from?site=github.com"), HnLink::Site("github.com".to_string()));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for input in [
+            "",
+            "dropbox",
+            "-1",
+            "4294967296",
+            "https://example.com/item?id=8863",
+            "https://news.ycombinator.com.example.com/item?id=8863",
+            "https://news.ycombinator.com/item",
+            "https://news.ycombinator.com/item?id=abc",
+            "https://news.ycombinator.com/item?uid=8863",
+            "https://news.ycombinator.com/news",
+            "https://news.ycombinator.com/from",
+        ] {
+            assert!(HnLink::parse(input).is_err(), "{} parsed", input);
+        }
+        let error = HnLink::parse("dropbox").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid argument: 'dropbox' is not an item ID or a news.ycombinator.com item, user or from?site= link"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use rig::{
+    agent::Agent,
+    completion::{Chat, Message, Prompt, PromptError},
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    providers::{anthropic, cohere, gemini, openai, perplexity},
+};
+use serde::{Deserialize, Serialize};
+
+use rig_hn_assistant::semantic::HashEmbedding;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    #[value(name = "openai")]
+    OpenAI,
+    Anthropic,
+    Cohere,
+    Perplexity,
+    Gemini,
+}
+
+impl Provider {
+    // Model used when none is configured
+    pub fn default_model(self) -> &'static str {
+        match self {
+            Provider::OpenAI => openai::GPT_4,
+            Provider::Anthropic => "claude-3-5-sonnet-20240620",
+            Provider::Cohere => "command-r",
+            Provider::Perplexity => "llama-3.1-sonar-small-128k-online",
+            Provider::Gemini => "gemini-1.5-flash",
+        }
+    }
+
+    fn api_key_var(self) -> &'static str {
+        match self {
+            Provider::OpenAI => "OPENAI_API_KEY",
+            Provider::Anthropic => "ANTHROPIC_API_KEY",
+            Provider::Cohere => "COHERE_API_KEY",
+            Provider::Perplexity => "PERPLEXITY_API_KEY",
+            Provider::Gemini => "GEMINI_API_KEY",
+        }
+    }
+}
+
+// A client for whichever provider was selected
+pub enum LlmClient {
+    OpenAI(openai::Client),
+    Anthropic(anthropic::Client),
+    Cohere(cohere::Client),
+    Perplexity(perplexity::Client),
+    Gemini(gemini::Client),
+}
+
+impl LlmClient {
+    // Reads the provider's API key from the environment, falling back to
+    // `configured_key` from the config file. With `base_url` the
+    // OpenAI provider talks to any OpenAI-compatible server, such as llama.cpp
+    // or Ollama, which usually need no key.
+    pub fn new(provider: Provider, base_url: Option<&str>, configured_key: Option<&str>) -> Result<Self> {
+        let api_key =
+            std::env::var(provider.api_key_var()).ok().or_else(|| configured_key.map(str::to_string)).ok_or(());
+
+        if let (Provider::OpenAI, Some(base_url)) = (provider, base_url) {
+            let api_key = api_key.unwrap_or_default();
+            return Ok(LlmClient::OpenAI(openai::Client::from_url(&api_key, base_url)));
+        }
+
+        let api_key = api_key.map_err(|_| {
+            anyhow!(
+                "{} or api_key in the config file must be set to use the {:?} provider",
+                provider.api_key_var(),
+                provider
+            )
+        })?;
+        Ok(match (provider, base_url) {
+            (Provider::Anthropic, base_url) => {
+                let mut builder = anthropic::ClientBuilder::new(&api_key);
+                if let Some(base_url) = base_url {
+                    builder = builder.base_url(base_url);
+                }
+                LlmClient::Anthropic(builder.build())
+            }
+            (_, Some(_)) => bail!("a custom base URL is only supported for the openai and anthropic providers"),
+            (Provider::OpenAI, None) => LlmClient::OpenAI(openai::Client::new(&api_key)),
+            (Provider::Cohere, None) => LlmClient::Cohere(cohere::Client::new(&api_key)),
+            (Provider::Perplexity, None) => LlmClient::Perplexity(perplexity::Client::new(&api_key)),
+            (Provider::Gemini, None) => LlmClient::Gemini(gemini::Client::new(&api_key)),
+        })
+    }
+}
+
+// An agent for any provider. rig's agents are generic over the model, so this
+// lets the CLI and REPL hold whichever one was built.
+pub enum AnyAgent {
+    OpenAI(Agent<openai::CompletionModel>),
+    Anthropic(Agent<anthropic::CompletionModel>),
+    Cohere(Agent<cohere::CompletionModel>),
+    Perplexity(Agent<perplexity::CompletionModel>),
+    Gemini(Agent<gemini::CompletionModel>),
+}
+
+impl Prompt for AnyAgent {
+    async fn prompt(&self, prompt: &str) -> Result<String, PromptError> {
+        match self {
+            AnyAgent::OpenAI(agent) => agent.prompt(prompt).await,
+            AnyAgent::Anthropic(agent) => agent.prompt(prompt).await,
+            AnyAgent::Cohere(agent) => agent.prompt(prompt).await,
+            AnyAgent::Perplexity(agent) => agent.prompt(prompt).await,
+            AnyAgent::Gemini(agent) => agent.prompt(prompt).await,
+        }
+    }
+}
+
+impl Chat for AnyAgent {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        match self {
+            AnyAgent::OpenAI(agent) => agent.chat(prompt, chat_history).await,
+            AnyAgent::Anthropic(agent) => agent.chat(prompt, chat_history).await,
+            AnyAgent::Cohere(agent) => agent.chat(prompt, chat_history).await,
+            AnyAgent::Perplexity(agent) => agent.chat(prompt, chat_history).await,
+            AnyAgent::Gemini(agent) => agent.chat(prompt, chat_history).await,
+        }
+    }
+}
+
+// How stories and comments are embedded for semantic search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Embedder {
+    None, // no semantic search
+    #[default]
+    Hash, // local feature hashing: offline and free, but only matches shared words
+    #[serde(rename = "openai")]
+    OpenAI, // OpenAI's embeddings API, or an OpenAI-compatible server
+}
+
+// An embedding model for any embedder, as AnyAgent is for agents
+#[derive(Clone)]
+pub enum AnyEmbeddingModel {
+    Hash(HashEmbedding),
+    OpenAI(openai::EmbeddingModel),
+}
+
+impl AnyEmbeddingModel {
+    // None if `embedder` turns semantic search off. `model` names the OpenAI
+    // model; `dimensions` sizes the hash model's vectors.
+    pub fn new(
+        embedder: Embedder,
+        model: &str,
+        dimensions: usize,
+        base_url: Option<&str>,
+        configured_key: Option<&str>,
+    ) -> Result<Option<Self>> {
+        Ok(match embedder {
+            Embedder::None => None,
+            Embedder::Hash => Some(AnyEmbeddingModel::Hash(HashEmbedding::new(dimensions))),
+            Embedder::OpenAI => match LlmClient::new(Provider::OpenAI, base_url, configured_key)? {
+                LlmClient::OpenAI(client) => Some(AnyEmbeddingModel::OpenAI(client.embedding_model(model))),
+                _ => unreachable!("asked for an OpenAI client"),
+            },
+        })
+    }
+}
+
+impl EmbeddingModel for AnyEmbeddingModel {
+    // OpenAI's limit; the hash model has none
+    const MAX_DOCUMENTS: usize = <openai::EmbeddingModel as EmbeddingModel>::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        match self {
+            AnyEmbeddingModel::Hash(model) => model.ndims(),
+            AnyEmbeddingModel::OpenAI(model) => model.ndims(),
+        }
+    }
+
+    async fn embed_documents(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        match self {
+            AnyEmbeddingModel::Hash(model) => model.embed_documents(documents).await,
+            AnyEmbeddingModel::OpenAI(model) => model.embed_documents(documents).await,
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use futures::{stream, StreamExt};
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::client::HnClient;
+use crate::error::{HNError, SkippedItem};
+use crate::html::{self, MapText};
+use crate::item::{Item, User};
+use crate::link::HnLink;
+use crate::search::DEFAULT_CONCURRENCY;
+
+// Stops the walk up a thread if the data ever loops back on itself
+const MAX_PARENT_DEPTH: usize = 200;
+
+pub const DEFAULT_MAX_CHILDREN: usize = 5;
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+
+// Tool to open a single item by ID or HN link
+pub struct GetItemTool<C> {
+    client: C,
+    max_children: usize, // used when the call doesn't ask for a number
+}
+
+impl<C: HnClient> GetItemTool<C> {
+    pub fn new(client: C) -> Self {
+        Self { client, max_children: DEFAULT_MAX_CHILDREN }
+    }
+
+    pub fn with_max_children(mut self, max_children: usize) -> Self {
+        self.max_children = max_children;
+        self
+    }
+
+    // Fetch an item, every item above it up to the story, and its first few replies
+    pub async fn lookup(&self, id: u32, max_children: usize) -> Result<ItemContext, HNError> {
+        let item = self.client.get_item(id).await?.ok_or_else(|| HNError::NotFound(format!("Item {}", id)))?;
+        let mut skipped = Vec::new();
+
+        // Each parent is only known once its child has been fetched, so walk up one at a time
+        let mut parents = Vec::new();
+        let mut next = item.parent();
+        while let Some(parent_id) = next.filter(|_| parents.len() < MAX_PARENT_DEPTH) {
+            match self.client.get_item(parent_id).await {
+                Ok(Some(parent)) => {
+                    next = parent.parent();
+                    parents.push(parent);
+                }
+                Ok(None) => break,
+                Err(e) if e.is_recoverable() => {
+                    skipped.push(SkippedItem { id: parent_id, reason: e.to_string() });
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        parents.reverse();
+
+        let fetched: Vec<_> = stream::iter(item.kids().iter().take(max_children).copied())
+            .map(|kid| async move { (kid, self.client.get_item(kid).await) })
+            .buffered(DEFAULT_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut children = Vec::new();
+        for (kid, result) in fetched {
+            match result {
+                Ok(Some(child)) => children.push(child),
+                Ok(None) => {}
+                Err(e) if e.is_recoverable() => skipped.push(SkippedItem { id: kid, reason: e.to_string() }),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(ItemContext { item, parents, children, skipped })
+    }
+}
+
+// An item with the discussion around it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ItemContext {
+    pub item: Item,
+    pub parents: Vec<Item>,  // from the story down to the item's direct parent
+    pub children: Vec<Item>, // the first few direct replies, in HN's ranking order
+    pub skipped: Vec<SkippedItem>,
+}
+
+impl MapText for ItemContext {
+    fn map_text(self, convert: fn(&str) -> String) -> Self {
+        ItemContext {
+            item: self.item.map_text(convert),
+            parents: self.parents.map_text(convert),
+            children: self.children.map_text(convert),
+            skipped: self.skipped,
+        }
+    }
+}
+
+// An item ID, or a link that should point at an item
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ItemRef {
+    Id(u32),
+    Link(String),
+}
+
+impl ItemRef {
+    pub fn resolve(&self) -> Result<u32, HNError> {
+        let link = match self {
+            ItemRef::Id(id) => return Ok(*id),
+            ItemRef::Link(link) => link,
+        };
+        match HnLink::parse(link)? {
+            HnLink::Item(id) => Ok(id),
+            HnLink::User(user) => Err(HNError::InvalidArgument(format!(
+                "'{}' is the profile of user {}; use get_user to open it",
+                link, user
+            ))),
+            HnLink::Site(site) => Err(HNError::InvalidArgument(format!(
+                "'{}' lists stories from {}; use search_hn with domain:{} to find them",
+                link, site, site
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetItemArgs {
+    pub item: ItemRef,
+    pub max_children: Option<usize>,
+}
+
+impl<C: HnClient> Tool for GetItemTool<C> {
+    const NAME: &'static str = "get_item";
+    type Error = HNError;
+    type Args = GetItemArgs;
+    type Output = ItemContext;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_item".to_string(),
+            description: "Open a single Hacker News item (story, comment, job or poll) by ID or link, \
+                          with the thread above it and its first few replies"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "item": {
+                        "type": ["integer", "string"],
+                        "description": "Item ID, or a link such as https://news.ycombinator.com/item?id=8863"
+                    },
+                    "max_children": {
+                        "type": "integer",
+                        "description": format!("Number of direct replies to include (default: {})", self.max_children)
+                    }
+                },
+                "required": ["item"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let id = args.item.resolve()?;
+        let context = self.lookup(id, args.max_children.unwrap_or(self.max_children)).await?;
+        Ok(context.map_text(html::to_text))
+    }
+}
+
+// Tool to open a user profile, optionally with a page of their recent submissions
+pub struct GetUserTool<C> {
+    client: C,
+    page_size: usize, // submissions per page when the call doesn't ask for a number
+}
+
+impl<C: HnClient> GetUserTool<C> {
+    pub fn new(client: C) -> Self {
+        Self { client, page_size: DEFAULT_PAGE_SIZE }
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    // Fetch a profile and one page of the stories, comments and polls the user
+    // submitted, newest first. `count` of zero fetches only the profile.
+    pub async fn lookup(&self, username: &str, count: usize, page: usize) -> Result<UserProfile, HNError> {
+        let user =
+            self.client.get_user(username).await?.ok_or_else(|| HNError::NotFound(format!("User {}", username)))?;
+
+        let submitted = user.submitted.as_deref().unwrap_or_default();
+        let start = page.saturating_mul(count).min(submitted.len());
+        let end = start.saturating_add(count).min(submitted.len());
+
+        let fetched: Vec<_> = stream::iter(submitted[start..end].iter().copied())
+            .map(|id| async move { (id, self.client.get_item(id).await) })
+            .buffered(DEFAULT_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut submissions = Vec::new();
+        let mut skipped = Vec::new();
+        for (id, result) in fetched {
+            match result {
+                Ok(Some(item)) => submissions.push(item),
+                Ok(None) => {}
+                Err(e) if e.is_recoverable() => skipped.push(SkippedItem { id, reason: e.to_string() }),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let has_more = end < submitted.len();
+        Ok(UserProfile { user, submissions, page, has_more, skipped })
+    }
+}
+
+// A user profile and one page of their submissions
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserProfile {
+    pub user: User,
+    pub submissions: Vec<Item>, // newest first
+    pub page: usize,
+    pub has_more: bool, // more submissions on later pages
+    pub skipped: Vec<SkippedItem>,
+}
+
+impl MapText for UserProfile {
+    fn map_text(self, convert: fn(&str) -> String) -> Self {
+        UserProfile { user: self.user.map_text(convert), submissions: self.submissions.map_text(convert), ..self }
+    }
+}
+
+// A username as HN allows them, or a link to a user's page
+pub fn resolve_username(input: &str) -> Result<String, HNError> {
+    let input = input.trim();
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Ok(input.to_string());
+    }
+    match HnLink::parse(input)? {
+        HnLink::User(user) => Ok(user),
+        _ => Err(HNError::InvalidArgument(format!("'{}' is not a username or a link to a user", input))),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetUserArgs {
+    pub username: String,
+    pub submissions: Option<usize>,
+    pub page: Option<usize>,
+}
+
+impl<C: HnClient> Tool for GetUserTool<C> {
+    const NAME: &'static str = "get_user";
+    type Error = HNError;
+    type Args = GetUserArgs;
+    type Output = UserProfile;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_user".to_string(),
+            description: "Open a Hacker News user's profile (karma, account age, about text), \
+                          optionally with their recent stories and comments to see what they post about"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "username": {
+                        "type": "string",
+                        "description": "Username, as shown in the 'by' field, or a news.ycombinator.com/user?id= link"
+                    },
+                    "submissions": {
+                        "type": "integer",
+                        "description": format!(
+                            "Number of recent submissions to include, newest first (default: {}, 0 for the profile only)",
+                            self.page_size
+                        )
+                    },
+                    "page": {
+                        "type": "integer",
+                        "description": "Page of submissions to return, starting at 0 (default: 0)"
+                    }
+                },
+                "required": ["username"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let username = resolve_username(&args.username)?;
+        let count = args.submissions.unwrap_or(self.page_size);
+        let profile = self.lookup(&username, count, args.page.unwrap_or(0)).await?;
+        Ok(profile.map_text(html::to_text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story, MemoryClient};
+
+    // pg submitted stories 1 to 10, newest first; story 7 fails to load
+    fn client() -> MemoryClient {
+        let pg = User { id: "pg".to_string(), created: 1160418111, karma: 157236, about: None, submitted: None };
+        let submitted: Vec<u32> = (1..=10).rev().collect();
+        MemoryClient::new()
+            .with_items((1..=10).map(|id| story(id, "pg", "Story", &[])))
+            .with_failure(7)
+            .with_user(User { submitted: Some(submitted), ..pg })
+    }
+
+    fn ids(items: &[Item]) -> Vec<u32> {
+        items.iter().map(Item::id).collect()
+    }
+
+    #[tokio::test]
+    async fn pages_through_submissions() {
+        let tool = GetUserTool::new(client());
+
+        let first = tool.lookup("pg", 4, 0).await.unwrap();
+        assert_eq!(ids(&first.submissions), [10, 9, 8]);
+        assert_eq!(first.skipped.iter().map(|skipped| skipped.id).collect::<Vec<_>>(), [7]);
+        assert!(first.has_more);
+
+        let second = tool.lookup("pg", 4, 1).await.unwrap();
+        assert_eq!(ids(&second.submissions), [6, 5, 4, 3]);
+        assert!(second.has_more);
+
+        // The last page is shorter, and there is nothing after it
+        let last = tool.lookup("pg", 4, 2).await.unwrap();
+        assert_eq!(ids(&last.submissions), [2, 1]);
+        assert!(!last.has_more);
+        let past = tool.lookup("pg", 4, 3).await.unwrap();
+        assert!(past.submissions.is_empty() && !past.has_more);
+        assert_eq!(past.page, 3);
+    }
+
+    #[tokio::test]
+    async fn a_page_ending_on_the_last_submission_has_no_more() {
+        let tool = GetUserTool::new(client());
+        let page = tool.lookup("pg", 5, 1).await.unwrap();
+        assert_eq!(ids(&page.submissions), [5, 4, 3, 2, 1]);
+        assert!(!page.has_more);
+
+        let profile = tool.lookup("pg", 0, 0).await.unwrap();
+        assert!(profile.submissions.is_empty());
+        assert!(profile.has_more);
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_not_found() {
+        let error = GetUserTool::new(client()).lookup("nobody", 4, 0).await.unwrap_err();
+        assert!(matches!(&error, HNError::NotFound(what) if what == "User nobody"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn calls_default_to_the_page_size() {
+        let tool = GetUserTool::new(client()).with_page_size(2);
+        let args = GetUserArgs {
+            username: "https://news.ycombinator.com/user?id=pg".to_string(),
+            submissions: None,
+            page: Some(1),
+        };
+        let profile = tool.call(args).await.unwrap();
+        assert_eq!(ids(&profile.submissions), [8]);
+        assert_eq!(profile.skipped[0].id, 7);
+    }
+
+    #[tokio::test]
+    async fn items_come_with_their_thread_and_replies() {
+        let client = MemoryClient::new().with_items([
+            story(1, "pg", "Story", &[2]),
+            comment(2, "a", 1, "Reply", &[3]),
+            comment(3, "b", 2, "Reply to reply", &[4, 5, 6]),
+            comment(4, "c", 3, "First", &[]),
+            comment(5, "d", 3, "Second", &[]),
+            comment(6, "e", 3, "Third", &[]),
+        ]);
+        let tool = GetItemTool::new(client).with_max_children(2);
+
+        let args =
+            GetItemArgs { item: ItemRef::Link("news.ycombinator.com/item?id=3".to_string()), max_children: None };
+        let context = tool.call(args).await.unwrap();
+        assert_eq!(context.item.id(), 3);
+        assert_eq!(ids(&context.parents), [1, 2]);
+        assert_eq!(ids(&context.children), [4, 5]);
+
+        let error = tool.lookup(99, 2).await.unwrap_err();
+        assert!(matches!(&error, HNError::NotFound(what) if what == "Item 99"), "{}", error);
+    }
+}
@@ -1,290 +1,413 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
 use rig::{
-    completion::{Prompt, ToolDefinition},
-    providers::openai::{self, GPT_4},
+    agent::{Agent, AgentBuilder},
+    completion::{CompletionModel, Prompt},
     tool::Tool,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::fmt::Write as _;
-
-const HN_API_BASE: &str = "https://hacker-news.firebaseio.com/v0";
+use std::path::Path;
+use std::sync::Arc;
+
+use rig_hn_assistant::{
+    algolia::AlgoliaClient,
+    archive::Archive,
+    cache::{CacheStore, CachedClient, CACHE_FILE_NAME},
+    client::{HnClient, ReqwestClient},
+    html::MapText,
+    index::{index_dir_for, SearchIndex},
+    lookup::{resolve_username, GetItemTool, GetUserTool, ItemRef},
+    rag::{rag_preamble, ArchiveContext},
+    record::ToolRecorder,
+    search::{HNSearchTool, SearchArgs},
+    semantic::{embed_archive, SemanticIndex, SemanticSearchArgs, SemanticSearchTool},
+    summarize::{SummarizeThreadTool, SummaryLimits, SUMMARIZER_PREAMBLE},
+    sync::sync,
+    tree::{CommentTree, TreeLimits},
+};
 
-// Struct to hold HN story metadata
-#[derive(Debug, Deserialize, Serialize)]
-struct Story {
-    id: u32,
-    title: String,
-    url: Option<String>,
-    text: Option<String>,
-    by: String,
-    score: Option<i32>,
-    descendants: Option<i32>,  // number of comments
-    time: i64,
-    #[serde(rename = "type")]
-    item_type: String,
-    kids: Option<Vec<u32>>,
+mod cli;
+mod config;
+mod display;
+mod format;
+mod formatter;
+mod llm;
+mod repl;
+
+use cli::{Cli, Command, ConfigCommand, OutputFormat};
+use config::{Config, VectorStorage};
+use format::{format_sync_report, Answer};
+use formatter::formatter;
+use llm::{AnyAgent, AnyEmbeddingModel, LlmClient, Provider};
+use repl::Repl;
+
+// The HN client shared by the CLI and every tool the agent uses
+type SharedClient = Arc<CachedClient<ReqwestClient>>;
+
+// `search_hn` as configured; used by the agent and by the `search` command
+fn search_tool(config: &Config, hn_client: &SharedClient, local: &Local) -> HNSearchTool<SharedClient> {
+    let mut tool = HNSearchTool::new(hn_client.clone())
+        .with_algolia(AlgoliaClient::from_env())
+        .with_limits(config.search.limits())
+        .with_ranking(config.search.ranking())
+        .with_concurrency(config.search.concurrency)
+        .with_deadline(config.search.deadline())
+        .offline(config.offline);
+    if let Some(archive) = &local.archive {
+        tool = tool.with_archive(archive.clone());
+    }
+    if let Some(index) = &local.index {
+        tool = tool.with_index(index.clone());
+    }
+    tool
 }
 
-// Struct to hold HN comment with optional text
-#[derive(Debug, Deserialize, Serialize)]
-struct Comment {
-    id: u32,
-    text: Option<String>,  // Made optional to handle deleted/missing comments
-    by: String,
-    time: i64,
-    parent: u32,
-    #[serde(rename = "type")]
-    item_type: String,
-    kids: Option<Vec<u32>>,
+// `get_item` and `get_user` as configured; used by the agent and by the `item` and `user` commands
+fn item_tool(config: &Config, hn_client: &SharedClient) -> GetItemTool<SharedClient> {
+    GetItemTool::new(hn_client.clone()).with_max_children(config.lookup.max_children)
 }
 
-// Tool to search HN stories
-#[derive(Deserialize, Serialize)]
-struct HNSearchTool;
-
-#[derive(Deserialize)]
-struct SearchArgs {
-    query: String,
-    story_type: Option<String>, // "top", "best", "new", "ask", "show", "job"
-    max_results: Option<i32>,
+fn user_tool(config: &Config, hn_client: &SharedClient) -> GetUserTool<SharedClient> {
+    GetUserTool::new(hn_client.clone()).with_page_size(config.lookup.page_size)
 }
 
-#[derive(Debug, thiserror::Error)]
-enum HNError {
-    #[error("Network error while accessing HackerNews API: {0}")]
-    Network(#[from] reqwest::Error),
-    #[error("No matching stories found. Try broadening your search terms or searching different story types (top, new, best, etc.)")]
-    NoResults,
-    #[error("API error: {0}")]
-    ApiError(String),
+// `semantic_search_hn`, if there are vectors to search
+fn semantic_tool(config: &Config, local: &Local) -> Option<SemanticSearchTool<AnyEmbeddingModel>> {
+    let (Some(archive), Some(vectors)) = (&local.archive, &local.vectors) else { return None };
+    let mut tool = SemanticSearchTool::new(archive.clone(), vectors.clone()).with_limits(config.search.limits());
+    if let Some(index) = &local.index {
+        tool = tool.with_index(index.clone());
+    }
+    Some(tool)
 }
 
-impl Tool for HNSearchTool {
-    const NAME: &'static str = "search_hn";
-    type Error = HNError;
-    type Args = SearchArgs;
-    type Output = Vec<(Story, Vec<Comment>)>;
+// The archive and search index written by `sync`, where they exist yet, and
+// the vectors for semantic search once loaded
+#[derive(Default)]
+struct Local {
+    archive: Option<Arc<Archive>>,
+    index: Option<Arc<SearchIndex>>,
+    vectors: Option<SemanticIndex<AnyEmbeddingModel>>,
+}
 
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: "search_hn".to_string(),
-            description: "Search for discussions on Hacker News".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "query": {
-                        "type": "string",
-                        "description": "Search query for HN stories"
-                    },
-                    "story_type": {
-                        "type": "string",
-                        "description": "Type of stories to search (top, best, new, ask, show, job)",
-                        "enum": ["top", "best", "new", "ask", "show", "job"]
-                    },
-                    "max_results": {
-                        "type": "integer",
-                        "description": "Maximum number of stories to return (default: 5)"
-                    }
-                },
-                "required": ["query"]
-            }),
-        }
+impl Local {
+    fn open_existing(config: &Config) -> Result<Self> {
+        Ok(Self {
+            archive: Archive::open_existing(&config.archive.path)?.map(Arc::new),
+            index: SearchIndex::open_existing(&index_dir_for(&config.archive.path))?.map(Arc::new),
+            vectors: None,
+        })
     }
 
-    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let max_results = args.max_results.unwrap_or(5) as usize;
-        let client = reqwest::Client::new();
-        
-        // Get stories based on type
-        let stories_endpoint = match args.story_type.as_deref() {
-            Some("top") | None => format!("{}/topstories.json", HN_API_BASE),
-            Some("best") => format!("{}/beststories.json", HN_API_BASE),
-            Some("new") => format!("{}/newstories.json", HN_API_BASE),
-            Some("ask") => format!("{}/askstories.json", HN_API_BASE),
-            Some("show") => format!("{}/showstories.json", HN_API_BASE),
-            Some("job") => format!("{}/jobstories.json", HN_API_BASE),
-            Some(_) => return Err(HNError::ApiError("Invalid story type".to_string())),
+    // Fill the vector store from the archive, unless semantic search is off
+    async fn load_vectors(&mut self, config: &Config) -> Result<()> {
+        let Some(archive) = &self.archive else { return Ok(()) };
+        let Some(model) = config.embedding_model()? else { return Ok(()) };
+        let semantic = &config.semantic;
+        let vectors = match semantic.store {
+            VectorStorage::Archive => {
+                SemanticIndex::load(archive, model, &semantic.key(), semantic.max_passages).await?
+            }
+            VectorStorage::Memory => {
+                SemanticIndex::build(archive, model, &semantic.key(), semantic.max_passages).await?
+            }
         };
+        self.vectors = Some(vectors).filter(|vectors| !vectors.is_empty());
+        Ok(())
+    }
+}
 
-        let story_ids: Vec<u32> = client.get(&stories_endpoint)
-            .send()
-            .await?
-            .json()
-            .await?;
+// Everything about the agent that doesn't depend on the provider
+struct AgentSettings<'a> {
+    config: &'a Config,
+    hn_client: &'a SharedClient,
+    local: &'a Local,           // searched by search_hn once `sync` has created it
+    recorder: &'a ToolRecorder, // wraps every tool to keep the HN data the model was given
+}
 
-        if story_ids.is_empty() {
-            return Err(HNError::NoResults);
+impl AgentSettings<'_> {
+    // Give the agent the same preamble and tool set, whatever the provider.
+    // `summarizer` is a second agent on the same model for summarize_thread.
+    // In RAG mode each prompt also brings the archived passages closest to it.
+    fn configure<M: CompletionModel + 'static>(&self, builder: AgentBuilder<M>, summarizer: Agent<M>) -> Agent<M> {
+        let rag = self.local.vectors.as_ref().filter(|_| self.config.rag.enabled);
+        let preamble = match rag {
+            Some(_) => format!("{} {}", self.config.preamble, rag_preamble()),
+            None => self.config.preamble.clone(),
+        };
+        let mut builder = builder
+            .preamble(&preamble)
+            .tool(self.recorder.wrap(search_tool(self.config, self.hn_client, self.local)))
+            .tool(self.recorder.wrap(item_tool(self.config, self.hn_client)))
+            .tool(self.recorder.wrap(user_tool(self.config, self.hn_client)))
+            .tool(self.recorder.wrap(SummarizeThreadTool::new(self.hn_client.clone(), summarizer)));
+        if let Some(tool) = semantic_tool(self.config, self.local) {
+            builder = builder.tool(self.recorder.wrap(tool));
         }
+        if let Some(vectors) = rag {
+            let context = ArchiveContext::new(vectors.clone(), self.recorder.clone());
+            builder = builder.dynamic_context(self.config.rag.passages, context);
+        }
+        self.sampling(builder).build()
+    }
 
-        let mut results = Vec::new();
-        let search_terms: Vec<String> = args.query
-            .to_lowercase()
-            .split_whitespace()
-            .map(String::from)
-            .collect();
-        
-        // Fetch stories and filter by search terms
-        let mut stories_processed = 0;
-        let mut stories_searched = 0;
-        const MAX_STORIES_TO_SEARCH: usize = 100; // Limit how many stories we'll look through
-
-        for &story_id in story_ids.iter() {
-            if stories_searched >= MAX_STORIES_TO_SEARCH {
-                break;
-            }
-
-            stories_searched += 1;
-            
-            let story_url = format!("{}/item/{}.json", HN_API_BASE, story_id);
-            let story: Story = match client.get(&story_url)
-                .send()
-                .await?
-                .json()
-                .await {
-                    Ok(story) => story,
-                    Err(e) => {
-                        println!("Warning: Failed to fetch story {}: {}", story_id, e);
-                        continue;
-                    }
-                };
-
-            // Check if story matches search terms
-            let story_text = format!(
-                "{} {} {}", 
-                story.title.to_lowercase(),
-                story.text.as_ref().map_or("", |s| s).to_lowercase(),
-                story.by.to_lowercase()
-            );
-
-            let matches = search_terms.iter().any(|term| story_text.contains(term));
-            
-            if matches {
-                let mut comments = Vec::new();
-                
-                // Fetch top comments if they exist
-                if let Some(kids) = &story.kids {
-                    for &comment_id in kids.iter().take(3) {
-                        match client.get(&format!("{}/item/{}.json", HN_API_BASE, comment_id))
-                            .send()
-                            .await?
-                            .json::<Comment>()
-                            .await {
-                                Ok(comment) => comments.push(comment),
-                                Err(e) => println!("Warning: Failed to fetch comment {}: {}", comment_id, e),
-                            }
-                    }
-                }
+    // A tool-less agent that writes thread summaries
+    fn summarizer<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> Agent<M> {
+        self.sampling(builder.preamble(SUMMARIZER_PREAMBLE)).build()
+    }
 
-                results.push((story, comments));
-                stories_processed += 1;
+    fn sampling<M: CompletionModel>(&self, mut builder: AgentBuilder<M>) -> AgentBuilder<M> {
+        let config = self.config;
+        if let Some(temperature) = config.temperature {
+            builder = builder.temperature(temperature);
+        }
+        // Anthropic rejects requests without a token limit
+        let max_tokens = config.max_tokens.or((config.provider == Provider::Anthropic).then_some(4096));
+        if let Some(max_tokens) = max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+        builder
+    }
 
-                if stories_processed >= max_results {
-                    break;
-                }
+    fn build_agent(&self, llm: &LlmClient, model: &str) -> AnyAgent {
+        match llm {
+            LlmClient::OpenAI(client) => {
+                AnyAgent::OpenAI(self.configure(client.agent(model), self.summarizer(client.agent(model))))
+            }
+            LlmClient::Anthropic(client) => {
+                AnyAgent::Anthropic(self.configure(client.agent(model), self.summarizer(client.agent(model))))
+            }
+            LlmClient::Cohere(client) => {
+                AnyAgent::Cohere(self.configure(client.agent(model), self.summarizer(client.agent(model))))
+            }
+            LlmClient::Perplexity(client) => {
+                AnyAgent::Perplexity(self.configure(client.agent(model), self.summarizer(client.agent(model))))
+            }
+            LlmClient::Gemini(client) => {
+                AnyAgent::Gemini(self.configure(client.agent(model), self.summarizer(client.agent(model))))
             }
         }
+    }
 
-        if results.is_empty() {
-            return Err(HNError::NoResults);
+    fn build_summarizer(&self, llm: &LlmClient, model: &str) -> AnyAgent {
+        match llm {
+            LlmClient::OpenAI(client) => AnyAgent::OpenAI(self.summarizer(client.agent(model))),
+            LlmClient::Anthropic(client) => AnyAgent::Anthropic(self.summarizer(client.agent(model))),
+            LlmClient::Cohere(client) => AnyAgent::Cohere(self.summarizer(client.agent(model))),
+            LlmClient::Perplexity(client) => AnyAgent::Perplexity(self.summarizer(client.agent(model))),
+            LlmClient::Gemini(client) => AnyAgent::Gemini(self.summarizer(client.agent(model))),
         }
-
-        Ok(results)
     }
 }
 
-fn format_hn_results(results: &[(Story, Vec<Comment>)]) -> Result<String, anyhow::Error> {
-    let mut output = String::new();
-    
-    writeln!(&mut output, "\n{:-^120}", " Hacker News Discussions ")?;
-    writeln!(
-        &mut output,
-        "{:<50} | {:<15} | {:<10} | {:<20}",
-        "Title", "Author", "Points", "Comments"
-    )?;
-    writeln!(&mut output, "{:-<120}", "")?;
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
 
-    for (story, _comments) in results {
-        let title = if story.title.len() > 47 {
-            format!("{}...", &story.title[..47])
-        } else {
-            story.title.clone()
-        };
+    if let Some(Command::Config { command: ConfigCommand::Show }) = &cli.command {
+        return write_output(cli.output.as_deref(), &config.show()?);
+    }
 
-        writeln!(
-            &mut output,
-            "{:<50} | {:<15} | {:<10} | {:<20}",
-            title,
-            story.by,
-            story.score.unwrap_or(0),
-            story.descendants.unwrap_or(0)
-        )?;
+    // Every HN request goes through the local cache; offline mode never touches the
+    // network, so the cache answers for the API and Algolia searches fail
+    let cache_path = config.cache_dir.join(CACHE_FILE_NAME);
+    if cli.verbose > 0 {
+        eprintln!("Cache: {}", cache_path.display());
+    }
+    let cache = CacheStore::open(&cache_path)?;
+    let hn_client = Arc::new(CachedClient::new(ReqwestClient::from_env(), cache).offline(config.offline));
+    let mut local = Local::open_existing(&config)?;
+    // Only the agent and `semantic` search by meaning
+    let command = cli.command.as_ref().unwrap_or(&Command::Chat);
+    if matches!(command, Command::Ask { .. } | Command::Chat | Command::Semantic { .. }) {
+        local.load_vectors(&config).await?;
+        if let (true, Some(vectors)) = (cli.verbose > 0, &local.vectors) {
+            eprintln!("Vectors: {} passage(s) embedded with {}", vectors.len(), config.semantic.key());
+        }
+        if config.rag.enabled && local.vectors.is_none() && !matches!(command, Command::Semantic { .. }) {
+            bail!("RAG needs archived passages to draw on; run `sync` with an embedder set in [semantic]");
+        }
     }
 
-    writeln!(&mut output, "\n{:-^120}", " Detailed Discussion View ")?;
+    let result = run(&cli, &config, &hn_client, &local).await;
 
-    for (i, (story, comments)) in results.iter().enumerate() {
-        writeln!(&mut output, "\n{}. {}", i + 1, story.title)?;
-        writeln!(&mut output, "By: {} | Points: {} | ID: {}", 
-            story.by, 
-            story.score.unwrap_or(0), 
-            story.id
-        )?;
-        
-        if let Some(url) = &story.url {
-            writeln!(&mut output, "URL: {}", url)?;
+    if cli.verbose > 0 {
+        let stats = hn_client.stats();
+        eprintln!("Cache: {} hits, {} misses", stats.hits(), stats.misses());
+    }
+
+    result
+}
+
+async fn run(cli: &Cli, config: &Config, hn_client: &SharedClient, local: &Local) -> Result<()> {
+    // Only `ask`, `chat` and `thread --summarize` need an LLM, and with it an API key
+    let recorder = ToolRecorder::new();
+    let settings = AgentSettings { config, hn_client, local, recorder: &recorder };
+    let model = config.model.as_str();
+    let llm = || LlmClient::new(config.provider, config.base_url.as_deref(), config.api_key.as_deref());
+    let layout = &config.display;
+    let formatter = formatter(config.format, *layout);
+
+    let output = match cli.command.as_ref().unwrap_or(&Command::Chat) {
+        Command::Ask { prompt } => {
+            let agent = settings.build_agent(&llm()?, model);
+            let response = agent.prompt(&prompt.join(" ")).await?;
+            let answer = Answer::new(response, recorder.take())?;
+            formatter.answer(&answer)?
+        }
+        Command::Search {
+            query,
+            story_type,
+            max_results,
+            backend,
+            sort,
+            min_points,
+            min_comments,
+            after,
+            before,
+            page,
+        } => {
+            let args = SearchArgs {
+                query: query.clone(),
+                story_type: *story_type,
+                max_results: *max_results,
+                backend: *backend,
+                sort: *sort,
+                min_points: *min_points,
+                min_comments: *min_comments,
+                created_after: after.clone(),
+                created_before: before.clone(),
+                page: *page,
+            };
+            let results =
+                search_tool(config, hn_client, local).with_text_format(formatter.text_format()).call(args).await?;
+            formatter.search(&results)?
+        }
+        Command::Semantic { query, max_results, no_hybrid } => {
+            let tool = semantic_tool(config, local).ok_or_else(|| {
+                anyhow!("there is nothing to search by meaning; run `sync` with an embedder set in [semantic]")
+            })?;
+            let args = SemanticSearchArgs { query: query.clone(), max_results: *max_results, hybrid: Some(!no_hybrid) };
+            formatter.search(&tool.with_text_format(formatter.text_format()).call(args).await?)?
         }
-        if let Some(text) = &story.text {
-            writeln!(&mut output, "\nText:\n{}\n", text)?;
+        Command::Item { item, children } => {
+            let id = ItemRef::Link(item.clone()).resolve()?;
+            let children = children.unwrap_or(config.lookup.max_children);
+            let context = item_tool(config, hn_client).lookup(id, children).await?.map_text(formatter.text_format());
+            formatter.item(&context)?
+        }
+        Command::User { name, submissions, page } => {
+            let username = resolve_username(name)?;
+            let submissions = submissions.unwrap_or(config.lookup.page_size);
+            let profile = user_tool(config, hn_client)
+                .lookup(&username, submissions, *page)
+                .await?
+                .map_text(formatter.text_format());
+            formatter.user(&profile)?
+        }
+        Command::Thread { id, depth, max_comments, summarize: true } => {
+            let defaults = SummaryLimits::default();
+            let limits = SummaryLimits {
+                tree: TreeLimits {
+                    max_depth: depth.unwrap_or(defaults.tree.max_depth),
+                    max_nodes: max_comments.unwrap_or(defaults.tree.max_nodes),
+                    ..defaults.tree
+                },
+                ..defaults
+            };
+            let summarizer = settings.build_summarizer(&llm()?, model);
+            let summary = SummarizeThreadTool::new(hn_client.clone(), summarizer)
+                .with_limits(limits)
+                .with_text_format(formatter.text_format())
+                .summarize(*id)
+                .await?;
+            formatter.summary(&summary)?
         }
+        Command::Thread { id, depth, max_comments, summarize: false } => {
+            let root = hn_client.get_item(*id).await?.ok_or_else(|| anyhow!("No item with ID {}", id))?;
+            let defaults = TreeLimits::default();
+            let limits = TreeLimits {
+                max_depth: depth.unwrap_or(defaults.max_depth),
+                max_nodes: max_comments.unwrap_or(defaults.max_nodes),
+                ..defaults
+            };
+            let tree = CommentTree::fetch(hn_client, root, limits).await?.map_text(formatter.text_format());
+            formatter.thread(&tree)?
+        }
+        Command::Sync { horizon_days, max_items, no_updates, no_embed } => {
+            if config.offline {
+                bail!("sync needs the network; drop --offline");
+            }
+            let archive = match &local.archive {
+                Some(archive) => archive.clone(),
+                None => Arc::new(Archive::open(&config.archive.path)?),
+            };
+            let index_dir = index_dir_for(&config.archive.path);
+            let index = match &local.index {
+                Some(index) => index.clone(),
+                None => Arc::new(SearchIndex::open(&index_dir)?),
+            };
+            if cli.verbose > 0 {
+                eprintln!("Archive: {}", config.archive.path.display());
+                eprintln!("Index: {}", index_dir.display());
+            }
+            let mut options = config.archive.sync_options(config.search.concurrency);
+            if let Some(days) = horizon_days {
+                options.horizon = std::time::Duration::from_secs(days * 24 * 60 * 60);
+            }
+            options.max_items = *max_items;
+            options.follow_updates = !no_updates;
 
-        if !comments.is_empty() {
-            writeln!(&mut output, "\nTop Comments:")?;
-            for (j, comment) in comments.iter().enumerate() {
-                writeln!(&mut output, "\n{}.{} by {}:", i + 1, j + 1, comment.by)?;
-                if let Some(text) = &comment.text {
-                    writeln!(&mut output, "{}\n", text)?;
-                } else {
-                    writeln!(&mut output, "[Comment text not available]\n")?;
+            // The archive must see fresh items, so sync bypasses the cache
+            let mut report = sync(&ReqwestClient::from_env(), &archive, Some(&index), &options, |report| {
+                eprint!("\rFetched {} new and {} older item(s)", report.new_items, report.backfilled);
+            })
+            .await?;
+            eprintln!();
+
+            // A memory store embeds at startup instead
+            if !no_embed && config.semantic.store == VectorStorage::Archive {
+                if let Some(model) = config.embedding_model()? {
+                    let (key, limit) = (config.semantic.key(), config.semantic.max_passages);
+                    report.embedded = embed_archive(&archive, &model, &key, limit, |count| {
+                        eprint!("\rEmbedded {} passage(s)", count);
+                    })
+                    .await?;
+                    if report.embedded > 0 {
+                        eprintln!();
+                    }
                 }
             }
+            // A status report rather than HN content, so only the JSON formats apply
+            let stats = archive.stats()?;
+            let document = serde_json::json!({ "sync": report, "archive": stats });
+            match config.format {
+                OutputFormat::Json => serde_json::to_string_pretty(&document)? + "\n",
+                OutputFormat::Ndjson => serde_json::to_string(&document)? + "\n",
+                _ => format_sync_report(&report, &stats)?,
+            }
         }
-        writeln!(&mut output, "{:-<120}", "")?;
-    }
+        Command::Chat => {
+            if cli.output.is_some() {
+                bail!("--output is not supported by chat; use ask to save an answer");
+            }
+            let llm = llm()?;
+            Repl::new(model, recorder.clone(), *layout, |model: &str| settings.build_agent(&llm, model)).run().await?;
+            return Ok(());
+        }
+        Command::Config { .. } => unreachable!("handled before the cache is opened"),
+    };
 
-    Ok(output)
+    write_output(cli.output.as_deref(), &output)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    let openai_client = openai::Client::from_env();
-
-    let hn_agent = openai_client
-        .agent(GPT_4)
-        .preamble(
-            "You are a helpful Hacker News discussion assistant that can search and analyze HN discussions. \
-             When asked about a topic, use the search_hn tool to find relevant discussions. \
-             You can search different types of stories (top, best, new, ask, show, job). \
-             When searching, consider using broader search terms and specify the story type when relevant. \
-             For example, for Rust programming discussions, you might search for 'rust lang programming' \
-             in the 'top' stories. Return only the raw JSON response from the tool."
-        )
-        .tool(HNSearchTool)
-        .build();
-
-    // Example usage with more specific instructions
-    let response = hn_agent
-        .prompt(
-            "rust lang programming"
-        )
-        .await?;
-
-    // Parse and format the results
-    let results: Vec<(Story, Vec<Comment>)> = serde_json::from_str(&response)?;
-    match format_hn_results(&results) {
-        Ok(formatted_output) => println!("{}", formatted_output),
-        Err(e) => println!("Error formatting results: {}", e),
+// Print to stdout, or write to `path` if one was given
+fn write_output(path: Option<&Path>, output: &str) -> Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, output).with_context(|| format!("cannot write {}", path.display())),
+        None => {
+            print!("{}", output);
+            Ok(())
+        }
     }
-
-    Ok(())
-}
\ No newline at end of file
+}
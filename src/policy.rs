@@ -0,0 +1,178 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use crate::error::HNError;
+
+pub const DEFAULT_CALL_DEADLINE: Duration = Duration::from_secs(60);
+
+// Timeouts and retries for individual HTTP requests
+#[derive(Debug, Clone)]
+pub struct RequestPolicy {
+    pub timeout: Duration,         // per attempt, including reading the body
+    pub max_retries: u32,          // attempts after the first one
+    pub initial_backoff: Duration, // doubled after each failed attempt
+    pub max_backoff: Duration,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RequestPolicy {
+    // Run `attempt` until it succeeds, fails with a permanent error, or runs out
+    // of retries. Transient failures are retried with exponential backoff and
+    // jitter so concurrent fetches don't retry in lockstep.
+    pub async fn retry<T, F, Fut>(&self, mut attempt: F) -> Result<T, HNError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, HNError>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match attempt().await {
+                Err(e) if e.is_transient() && retries < self.max_retries => {
+                    retries += 1;
+                    tokio::time::sleep(jitter(backoff)).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // GET `url` and decode the JSON body, retrying transient failures
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        http: &reqwest::Client,
+        url: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, HNError> {
+        self.retry(|| async {
+            let response = http
+                .get(url)
+                .query(query)
+                .timeout(self.timeout)
+                .send()
+                .await
+                .map_err(|e| HNError::from_reqwest(e, url))?;
+
+            let status = response.status();
+            if !status.is_success() {
+                return Err(HNError::HttpStatus { url: url.to_string(), status: status.as_u16() });
+            }
+
+            let body = response.bytes().await.map_err(|e| HNError::from_reqwest(e, url))?;
+            Ok(serde_json::from_slice(&body)?)
+        })
+        .await
+    }
+}
+
+// A random duration between half and all of `backoff`
+fn jitter(backoff: Duration) -> Duration {
+    backoff.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::time::Instant;
+
+    use super::*;
+
+    fn policy() -> RequestPolicy {
+        RequestPolicy {
+            timeout: Duration::from_secs(1),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(250),
+        }
+    }
+
+    fn unavailable() -> HNError {
+        HNError::HttpStatus { url: "item/1".to_string(), status: 503 }
+    }
+
+    // An attempt that fails with `error` the first `failures` times it's made
+    async fn flaky(attempts: &AtomicU32, failures: u32, error: fn() -> HNError) -> Result<u32, HNError> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt <= failures {
+            Err(error())
+        } else {
+            Ok(attempt)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_errors_until_one_succeeds() {
+        let attempts = AtomicU32::new(0);
+        let result = policy().retry(|| flaky(&attempts, 2, unavailable)).await;
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_retries() {
+        let attempts = AtomicU32::new(0);
+        let result = policy().retry(|| flaky(&attempts, u32::MAX, unavailable)).await;
+        assert!(matches!(result, Err(HNError::HttpStatus { status: 503, .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        let attempts = AtomicU32::new(0);
+        let once = RequestPolicy { max_retries: 0, ..policy() };
+        once.retry(|| flaky(&attempts, u32::MAX, unavailable)).await.unwrap_err();
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn permanent_errors_are_not_retried() {
+        let errors: [fn() -> HNError; 3] = [
+            || HNError::HttpStatus { url: "item/1".to_string(), status: 404 },
+            || HNError::NotFound("Item 1".to_string()),
+            || HNError::InvalidArgument("bad".to_string()),
+        ];
+        for error in errors {
+            let attempts = AtomicU32::new(0);
+            let start = Instant::now();
+            policy().retry(|| flaky(&attempts, u32::MAX, error)).await.unwrap_err();
+            assert_eq!(attempts.load(Ordering::SeqCst), 1);
+            assert_eq!(start.elapsed(), Duration::ZERO);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_doubles_up_to_the_cap_with_jitter() {
+        let attempts = AtomicU32::new(0);
+        let start = Instant::now();
+        policy().retry(|| flaky(&attempts, u32::MAX, unavailable)).await.unwrap_err();
+        // Sleeps of 100, 200 and 250ms, each cut to somewhere between half and all of it
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_millis(275) && waited <= Duration::from_millis(550), "{:?}", waited);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeouts_are_retried() {
+        let attempts = AtomicU32::new(0);
+        let result = policy().retry(|| flaky(&attempts, 1, || HNError::Timeout("item/1".to_string()))).await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn jitter_stays_within_half_and_all_of_the_backoff() {
+        let backoff = Duration::from_millis(200);
+        for _ in 0..1000 {
+            let delay = jitter(backoff);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?}", delay);
+        }
+    }
+}
@@ -0,0 +1,683 @@
+use chrono::NaiveDate;
+
+use crate::item::Item;
+use crate::rank::{stem_word, tokenize};
+
+// A parsed search query. Adjacent words, phrases and groups are OR'ed, as the
+// bag of words search_hn took before; qualifiers and exclusions next to them
+// apply to all of them. `AND` binds tighter than either.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),        // a single lowercased word in the title, text or author
+    Phrase(Vec<String>), // consecutive lowercased words in the title or text
+    Filter(Filter),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+// Field qualifiers such as `author:pg` or `points>100`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Author(String),     // username as written, compared case-insensitively
+    Domain(String),     // lowercased host, without a leading "www."
+    Title(Vec<String>), // lowercased words that must appear consecutively in the title
+    Type(String),       // item kind: story, comment, job, poll or pollopt
+    Points(Comparison, i64),
+    Comments(Comparison, i64),
+    Before(i64), // unix timestamp, exclusive
+    After(i64),  // unix timestamp, exclusive
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn matches(self, value: i64, bound: i64) -> bool {
+        match self {
+            Comparison::Less => value < bound,
+            Comparison::LessOrEqual => value <= bound,
+            Comparison::Equal => value == bound,
+            Comparison::GreaterOrEqual => value >= bound,
+            Comparison::Greater => value > bound,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "=",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Greater => ">",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{message} (at character {position})")]
+pub struct QueryError {
+    pub message: String,
+    pub position: usize,
+}
+
+// Text that describes the syntax, shared by tool schemas and help output
+pub const QUERY_SYNTAX: &str = "Any of the words can match; join them with AND to require them all. \
+    Use OR to separate alternatives that have qualifiers of their own, NOT or a leading - to exclude, \
+    parentheses to group and \"double quotes\" for exact phrases. Exclusions and qualifiers always apply. \
+    Qualifiers: author:NAME, domain:example.com, title:WORD or title:\"a phrase\", \
+    type:story|comment|job|poll|pollopt, points>N, comments>N (also <, <=, >=, =), \
+    before:YYYY-MM-DD, after:YYYY-MM-DD. Example: rust AND (async OR tokio) -crypto points>100";
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let tokens = lex(input)?;
+        let mut parser = Parser { tokens, pos: 0, end: input.chars().count() };
+        let query = parser.or_expr()?;
+        if let Some((token, position)) = parser.tokens.get(parser.pos) {
+            return Err(QueryError { message: format!("unexpected {}", token.describe()), position: *position });
+        }
+        Ok(query)
+    }
+
+    // Whether the item satisfies the query. With `stem`, words match regardless of
+    // suffix ("databases" matches "database"); pass the same flag used for ranking.
+    pub fn matches(&self, item: &Item, stem: bool) -> bool {
+        self.matches_fields(&MatchFields::new(item, stem))
+    }
+
+    fn matches_fields(&self, fields: &MatchFields) -> bool {
+        match self {
+            Query::Term(term) => {
+                let term = fields.normalize(term);
+                fields.title.contains(&term) || fields.text.contains(&term) || fields.author_tokens.contains(&term)
+            }
+            Query::Phrase(words) => {
+                let words = fields.normalize_all(words);
+                contains_phrase(&fields.title, &words) || contains_phrase(&fields.text, &words)
+            }
+            Query::Filter(filter) => filter.matches(fields),
+            Query::Not(query) => !query.matches_fields(fields),
+            Query::And(queries) => queries.iter().all(|q| q.matches_fields(fields)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches_fields(fields)),
+        }
+    }
+
+    // Words that count towards relevance: every term and phrase outside a NOT
+    pub fn positive_terms(&self) -> Vec<String> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            Query::Term(term) => terms.push(term.clone()),
+            Query::Phrase(words) | Query::Filter(Filter::Title(words)) => terms.extend(words.iter().cloned()),
+            Query::And(queries) | Query::Or(queries) => queries.iter().for_each(|q| q.collect_terms(terms)),
+            Query::Filter(_) | Query::Not(_) => {}
+        }
+    }
+
+    // The clauses that must hold for every match, i.e. the top level of an AND
+    pub fn required_clauses(&self) -> Vec<&Query> {
+        match self {
+            Query::And(queries) => queries.iter().collect(),
+            other => vec![other],
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, fields: &MatchFields) -> bool {
+        match self {
+            Filter::Author(author) => fields.author.eq_ignore_ascii_case(author),
+            Filter::Domain(domain) => {
+                fields.domain.as_deref().is_some_and(|host| host == domain || host.ends_with(&format!(".{}", domain)))
+            }
+            Filter::Title(words) => contains_phrase(&fields.title, &fields.normalize_all(words)),
+            Filter::Type(kind) => fields.kind == kind,
+            Filter::Points(cmp, bound) => fields.points.is_some_and(|points| cmp.matches(points, *bound)),
+            Filter::Comments(cmp, bound) => fields.comments.is_some_and(|comments| cmp.matches(comments, *bound)),
+            Filter::Before(time) => fields.time < *time,
+            Filter::After(time) => fields.time > *time,
+        }
+    }
+}
+
+// An item's fields, tokenized the same way as query terms
+struct MatchFields<'a> {
+    stem: bool,
+    title: Vec<String>,
+    text: Vec<String>,
+    author: String,
+    author_tokens: Vec<String>,
+    domain: Option<String>,
+    kind: &'a str,
+    points: Option<i64>,
+    comments: Option<i64>,
+    time: i64,
+}
+
+impl<'a> MatchFields<'a> {
+    fn new(item: &'a Item, stem: bool) -> Self {
+        let author = item.by().unwrap_or_default();
+        Self {
+            stem,
+            title: tokenize(item.title().unwrap_or_default(), stem),
+            text: tokenize(item.text().unwrap_or_default(), stem),
+            author: author.to_lowercase(),
+            author_tokens: tokenize(author, stem),
+            domain: item.url().and_then(domain_of),
+            kind: item.kind(),
+            points: item.score().map(i64::from),
+            comments: item.descendants().map(i64::from),
+            time: item.time(),
+        }
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        if self.stem {
+            stem_word(word)
+        } else {
+            word.to_string()
+        }
+    }
+
+    fn normalize_all(&self, words: &[String]) -> Vec<String> {
+        words.iter().map(|word| self.normalize(word)).collect()
+    }
+}
+
+fn contains_phrase(haystack: &[String], words: &[String]) -> bool {
+    !words.is_empty() && haystack.windows(words.len()).any(|window| window == words)
+}
+
+// Host part of a URL, lowercased and without "www."
+pub fn domain_of(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?.split(':').next()?.to_lowercase();
+    let host = host.strip_prefix("www.").map(str::to_string).unwrap_or(host);
+    (!host.is_empty()).then_some(host)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Qualified(String, String), // field:"quoted value"
+    And,
+    Or,
+    Not,
+    Minus,
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Quoted(text) => format!("\"{}\"", text),
+            Token::Qualified(field, value) => format!("{}:\"{}\"", field, value),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Minus => "'-'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+        }
+    }
+}
+
+// Split the query into tokens, each paired with its character offset
+fn lex(input: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let read_quoted = |start: usize| -> Result<(String, usize), QueryError> {
+        // `start` is the index of the opening quote
+        let mut end = start + 1;
+        while end < chars.len() && chars[end] != '"' {
+            end += 1;
+        }
+        if end == chars.len() {
+            return Err(QueryError { message: "unterminated quote".to_string(), position: start });
+        }
+        Ok((chars[start + 1..end].iter().collect(), end + 1))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push((Token::LParen, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::RParen, start));
+                i += 1;
+            }
+            '"' => {
+                let (text, next) = read_quoted(i)?;
+                tokens.push((Token::Quoted(text), start));
+                i = next;
+            }
+            '-' if chars.get(i + 1).is_some_and(|next| !next.is_whitespace()) => {
+                tokens.push((Token::Minus, start));
+                i += 1;
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')' | '"') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                if word.ends_with(':') && chars.get(i) == Some(&'"') {
+                    let (value, next) = read_quoted(i)?;
+                    tokens.push((Token::Qualified(word.trim_end_matches(':').to_string(), value), start));
+                    i = next;
+                    continue;
+                }
+
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize, // position reported for errors at the end of input
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, position)| *position)
+    }
+
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError { message: message.into(), position: self.position() }
+    }
+
+    fn or_expr(&mut self) -> Result<Query, QueryError> {
+        let mut alternatives = vec![self.group()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            alternatives.push(self.group()?);
+        }
+        Ok(any_of(alternatives))
+    }
+
+    // Clauses side by side. Words, phrases and groups are alternatives, kept in
+    // place of the first of them; qualifiers and exclusions are required.
+    fn group(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = Vec::new();
+        let mut alternatives = Vec::new();
+        let mut first_alternative = None;
+        loop {
+            match self.peek() {
+                None | Some(Token::Or) | Some(Token::RParen) => break,
+                Some(Token::And) => return Err(self.error("AND needs a clause before it")),
+                _ => {}
+            }
+            match self.and_expr()? {
+                Some(clause @ (Query::Filter(_) | Query::Not(_))) => clauses.push(clause),
+                Some(clause) => {
+                    first_alternative.get_or_insert(clauses.len());
+                    alternatives.push(clause);
+                }
+                None => {}
+            }
+        }
+
+        if let Some(at) = first_alternative {
+            clauses.insert(at, any_of(alternatives));
+        }
+        if clauses.is_empty() {
+            return Err(self.error("expected a search term"));
+        }
+        Ok(all_of(clauses))
+    }
+
+    // Clauses joined by AND, or `None` if none of them has searchable characters
+    fn and_expr(&mut self) -> Result<Option<Query>, QueryError> {
+        let mut clauses: Vec<Query> = self.unary()?.into_iter().collect();
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            clauses.extend(self.unary()?);
+        }
+        Ok((!clauses.is_empty()).then(|| all_of(clauses)))
+    }
+
+    // A clause, or `None` for a word with no searchable characters such as "++"
+    fn unary(&mut self) -> Result<Option<Query>, QueryError> {
+        match self.peek() {
+            Some(Token::Not) | Some(Token::Minus) => {
+                self.pos += 1;
+                match self.unary()? {
+                    Some(query) => Ok(Some(Query::Not(Box::new(query)))),
+                    None => Err(self.error("nothing to exclude")),
+                }
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Option<Query>, QueryError> {
+        let Some((token, position)) = self.tokens.get(self.pos).cloned() else {
+            return Err(self.error("expected a search term"));
+        };
+        self.pos += 1;
+
+        match token {
+            Token::LParen => {
+                let query = self.or_expr()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error("missing closing parenthesis"));
+                }
+                self.pos += 1;
+                Ok(Some(query))
+            }
+            Token::Quoted(text) => Ok(phrase(&text).map(Query::Phrase)),
+            Token::Qualified(field, value) => qualifier(&field, &value, position),
+            Token::Word(word) => word_clause(&word, position),
+            other => Err(QueryError { message: format!("unexpected {}", other.describe()), position }),
+        }
+    }
+}
+
+// The clauses AND'ed, with nested ANDs merged in; a single clause stays as it is
+fn all_of(clauses: Vec<Query>) -> Query {
+    let mut merged = Vec::new();
+    for clause in clauses {
+        match clause {
+            Query::And(inner) => merged.extend(inner),
+            other => merged.push(other),
+        }
+    }
+    if merged.len() == 1 {
+        merged.remove(0)
+    } else {
+        Query::And(merged)
+    }
+}
+
+// The clauses OR'ed, with nested ORs merged in; a single clause stays as it is
+fn any_of(clauses: Vec<Query>) -> Query {
+    let mut merged = Vec::new();
+    for clause in clauses {
+        match clause {
+            Query::Or(inner) => merged.extend(inner),
+            other => merged.push(other),
+        }
+    }
+    if merged.len() == 1 {
+        merged.remove(0)
+    } else {
+        Query::Or(merged)
+    }
+}
+
+fn phrase(text: &str) -> Option<Vec<String>> {
+    let words = tokenize(text, false);
+    (!words.is_empty()).then_some(words)
+}
+
+const COMPARISONS: [(&str, Comparison); 5] = [
+    (">=", Comparison::GreaterOrEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">", Comparison::Greater),
+    ("<", Comparison::Less),
+    ("=", Comparison::Equal),
+];
+
+fn word_clause(word: &str, position: usize) -> Result<Option<Query>, QueryError> {
+    let lower = word.to_lowercase();
+
+    for field in ["points", "comments"] {
+        if let Some(rest) = lower.strip_prefix(field) {
+            if let Some((cmp, number)) =
+                COMPARISONS.iter().find_map(|(op, cmp)| rest.strip_prefix(op).map(|number| (*cmp, number)))
+            {
+                let bound = number.parse().map_err(|_| QueryError {
+                    message: format!("'{}' is not a number in {}", number, word),
+                    position,
+                })?;
+                let filter = match field {
+                    "points" => Filter::Points(cmp, bound),
+                    _ => Filter::Comments(cmp, bound),
+                };
+                return Ok(Some(Query::Filter(filter)));
+            }
+        }
+    }
+
+    if let Some((field, value)) = word.split_once(':') {
+        if is_qualifier(field) {
+            return qualifier(field, value, position);
+        }
+    }
+
+    // Hyphenated and dotted words like "memory-safe" become phrases
+    Ok(phrase(word).map(|mut words| if words.len() == 1 { Query::Term(words.remove(0)) } else { Query::Phrase(words) }))
+}
+
+fn is_qualifier(field: &str) -> bool {
+    matches!(field.to_lowercase().as_str(), "author" | "domain" | "title" | "type" | "before" | "after")
+}
+
+fn qualifier(field: &str, value: &str, position: usize) -> Result<Option<Query>, QueryError> {
+    let error = |message: String| QueryError { message, position };
+    let field = field.to_lowercase();
+
+    if !is_qualifier(&field) {
+        return Err(error(format!("unknown qualifier '{}:'", field)));
+    }
+    if value.trim().is_empty() {
+        return Err(error(format!("expected a value after '{}:'", field)));
+    }
+
+    let filter = match field.as_str() {
+        "author" => Filter::Author(value.to_string()),
+        "domain" => Filter::Domain(domain_of(value).unwrap_or_else(|| value.to_lowercase())),
+        "title" => match phrase(value) {
+            Some(words) => Filter::Title(words),
+            None => return Err(error(format!("'{}' has no searchable words", value))),
+        },
+        "type" => match value.to_lowercase().as_str() {
+            kind @ ("story" | "comment" | "job" | "poll" | "pollopt") => Filter::Type(kind.to_string()),
+            _ => return Err(error(format!("unknown type '{}', expected story, comment, job, poll or pollopt", value))),
+        },
+        "before" | "after" => {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| error(format!("'{}' is not a YYYY-MM-DD date", value)))?;
+            let timestamp = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp();
+            if field == "before" {
+                Filter::Before(timestamp)
+            } else {
+                Filter::After(timestamp)
+            }
+        }
+        _ => unreachable!("checked by is_qualifier"),
+    };
+
+    Ok(Some(Query::Filter(filter)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::story;
+
+    fn term(word: &str) -> Query {
+        Query::Term(word.to_string())
+    }
+
+    fn filter(query: &str) -> Filter {
+        match Query::parse(query) {
+            Ok(Query::Filter(filter)) => filter,
+            other => panic!("{} parsed as {:?}", query, other),
+        }
+    }
+
+    fn error(query: &str) -> (String, usize) {
+        let error = Query::parse(query).unwrap_err();
+        (error.message, error.position)
+    }
+
+    #[test]
+    fn adjacent_words_are_alternatives() {
+        let query = Query::parse("rust lang programming").unwrap();
+        assert_eq!(query, Query::Or(vec![term("rust"), term("lang"), term("programming")]));
+        assert_eq!(Query::parse("A or B").unwrap(), Query::Or(vec![term("a"), term("or"), term("b")]));
+        assert_eq!(Query::parse("a OR b c").unwrap(), Query::Or(vec![term("a"), term("b"), term("c")]));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let query = Query::parse("a b AND c").unwrap();
+        assert_eq!(query, Query::Or(vec![term("a"), Query::And(vec![term("b"), term("c")])]));
+        let query = Query::parse("a AND b OR c").unwrap();
+        assert_eq!(query, Query::Or(vec![Query::And(vec![term("a"), term("b")]), term("c")]));
+        assert_eq!(Query::parse("a AND b AND c").unwrap(), Query::And(vec![term("a"), term("b"), term("c")]));
+    }
+
+    #[test]
+    fn qualifiers_and_exclusions_apply_to_every_word() {
+        let author = Query::Filter(Filter::Author("pg".to_string()));
+        let query = Query::parse("author:pg lisp arc").unwrap();
+        assert_eq!(query, Query::And(vec![author.clone(), Query::Or(vec![term("lisp"), term("arc")])]));
+
+        // OR ends their reach
+        let query = Query::parse("author:pg lisp OR arc").unwrap();
+        assert_eq!(query, Query::Or(vec![Query::And(vec![author, term("lisp")]), term("arc")]));
+    }
+
+    #[test]
+    fn not_and_minus_exclude() {
+        let query = Query::parse("rust go -crypto NOT web3").unwrap();
+        let not = |word| Query::Not(Box::new(term(word)));
+        assert_eq!(query, Query::And(vec![Query::Or(vec![term("rust"), term("go")]), not("crypto"), not("web3")]));
+
+        let query = Query::parse("-(a OR b)").unwrap();
+        assert_eq!(query, Query::Not(Box::new(Query::Or(vec![term("a"), term("b")]))));
+
+        // A dash on its own is punctuation, not NOT
+        assert_eq!(Query::parse("a - b").unwrap(), Query::Or(vec![term("a"), term("b")]));
+    }
+
+    #[test]
+    fn nested_parentheses() {
+        let query = Query::parse("(a OR (b AND c)) AND d").unwrap();
+        let expected = Query::And(vec![Query::Or(vec![term("a"), Query::And(vec![term("b"), term("c")])]), term("d")]);
+        assert_eq!(query, expected);
+        let query = Query::parse("(a (b c)) d").unwrap();
+        assert_eq!(query, Query::Or(vec![term("a"), term("b"), term("c"), term("d")]));
+        assert_eq!(Query::parse("((a))").unwrap(), term("a"));
+    }
+
+    #[test]
+    fn quoted_and_hyphenated_phrases() {
+        let phrase = Query::Phrase(vec!["memory".to_string(), "safe".to_string()]);
+        assert_eq!(Query::parse("\"Memory Safe\" rust").unwrap(), Query::Or(vec![phrase.clone(), term("rust")]));
+        assert_eq!(Query::parse("memory-safe").unwrap(), phrase);
+        assert_eq!(Query::parse("rust \"\"").unwrap(), term("rust"));
+    }
+
+    #[test]
+    fn qualifiers() {
+        assert_eq!(filter("author:PG"), Filter::Author("PG".to_string()));
+        assert_eq!(filter("domain:https://www.Example.com/path"), Filter::Domain("example.com".to_string()));
+        assert_eq!(filter("title:\"Show HN\""), Filter::Title(vec!["show".to_string(), "hn".to_string()]));
+        assert_eq!(filter("title:Rust"), Filter::Title(vec!["rust".to_string()]));
+        assert_eq!(filter("type:Comment"), Filter::Type("comment".to_string()));
+        assert_eq!(filter("points>100"), Filter::Points(Comparison::Greater, 100));
+        assert_eq!(filter("points>=5"), Filter::Points(Comparison::GreaterOrEqual, 5));
+        assert_eq!(filter("Points<=2"), Filter::Points(Comparison::LessOrEqual, 2));
+        assert_eq!(filter("comments<3"), Filter::Comments(Comparison::Less, 3));
+        assert_eq!(filter("comments=0"), Filter::Comments(Comparison::Equal, 0));
+        assert_eq!(filter("after:2024-01-01"), Filter::After(1_704_067_200));
+        assert_eq!(filter("before:2023-12-31"), Filter::Before(1_703_980_800));
+        // Anything else with a colon is just words
+        let words = Query::Phrase(vec!["note".to_string(), "this".to_string()]);
+        assert_eq!(Query::parse("note:this").unwrap(), words);
+    }
+
+    #[test]
+    fn malformed_queries() {
+        assert_eq!(error(""), ("expected a search term".to_string(), 0));
+        assert_eq!(error("rust \"async"), ("unterminated quote".to_string(), 5));
+        assert_eq!(error("(a b"), ("missing closing parenthesis".to_string(), 4));
+        assert_eq!(error("a)"), ("unexpected ')'".to_string(), 1));
+        assert_eq!(error("OR a"), ("expected a search term".to_string(), 0));
+        assert_eq!(error("AND a"), ("AND needs a clause before it".to_string(), 0));
+        assert_eq!(error("a NOT"), ("expected a search term".to_string(), 5));
+        assert_eq!(error("a -++"), ("nothing to exclude".to_string(), 5));
+        assert_eq!(error("rust points>lots"), ("'lots' is not a number in points>lots".to_string(), 5));
+        assert_eq!(error("author:"), ("expected a value after 'author:'".to_string(), 0));
+        assert_eq!(error("bogus:\"x\""), ("unknown qualifier 'bogus:'".to_string(), 0));
+        assert_eq!(error("title:++"), ("'++' has no searchable words".to_string(), 0));
+        assert_eq!(error("after:2024-13-01"), ("'2024-13-01' is not a YYYY-MM-DD date".to_string(), 0));
+        assert_eq!(
+            error("type:link"),
+            ("unknown type 'link', expected story, comment, job, poll or pollopt".to_string(), 0)
+        );
+        let message = Query::parse("a)").unwrap_err().to_string();
+        assert_eq!(message, "unexpected ')' (at character 1)");
+    }
+
+    #[test]
+    fn matches_items() {
+        let item = story(1, "PG", "Databases in Lisp", &[]);
+        let matches = |query: &str, stem| Query::parse(query).unwrap().matches(&item, stem);
+        assert!(matches("database AND lisp", true));
+        assert!(!matches("database AND lisp", false));
+        // Like the bag of words search_hn took before, any word is enough
+        assert!(matches("database lisp", false));
+        assert!(matches("rust lang lisp", false));
+        assert!(!matches("rust lang programming", false));
+        assert!(matches("author:pg -python", true));
+        assert!(matches("\"in lisp\" OR nothing", false));
+        assert!(!matches("title:\"lisp databases\"", false));
+        assert!(matches("type:story points>=1 comments=0", false));
+        assert!(matches("after:2023-01-01 before:2024-01-01", false));
+        assert!(!matches("domain:example.com", false));
+    }
+
+    #[test]
+    fn positive_terms_skip_exclusions() {
+        let query = Query::parse("rust (async OR \"green threads\") -go title:tokio author:pg").unwrap();
+        assert_eq!(query.positive_terms(), ["rust", "async", "green", "threads", "tokio"]);
+    }
+
+    #[test]
+    fn domains() {
+        assert_eq!(domain_of("https://user@www.Example.com:8080/a?b#c").as_deref(), Some("example.com"));
+        assert_eq!(domain_of("example.org/path").as_deref(), Some("example.org"));
+        assert_eq!(domain_of("https://"), None);
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use rig::{
+    embeddings::EmbeddingModel,
+    vector_store::{VectorStoreError, VectorStoreIndex},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::record::{ToolCall, ToolRecorder};
+use crate::semantic::{Passage, SemanticIndex};
+
+// What the retrieved passages are recorded as, next to the agent's tool calls
+pub const CONTEXT_SOURCE: &str = "archive_context";
+
+// Passages pulled into each prompt unless configured otherwise
+pub const DEFAULT_CONTEXT_PASSAGES: usize = 8;
+
+// Appended to the agent's preamble when prompts carry archived passages. The
+// date lets the model tell how recent a discussion is.
+pub fn rag_preamble() -> String {
+    format!(
+        "Each message comes with stories and comments from a local archive of Hacker News that are \
+        close to it in meaning, attached as documents whose ID is the HN item ID. Answer from them and \
+        from what your tools find, and cite every claim with the IDs of the items it comes from in \
+        square brackets, like [8863]; for something a commenter said, cite the comment rather than its \
+        story. Only quote words that appear in the items. If they don't cover the question, say so \
+        rather than guessing. Today is {}.",
+        Utc::now().format("%Y-%m-%d")
+    )
+}
+
+// An archived story or comment as the model sees it in its context
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContextPassage {
+    pub id: u32,
+    pub story: u32, // its own ID for a story
+    pub kind: String,
+    pub by: Option<String>,
+    pub date: String, // YYYY-MM-DD; models read dates more reliably than timestamps
+    pub title: Option<String>,
+    pub text: String,
+}
+
+impl From<Passage> for ContextPassage {
+    fn from(passage: Passage) -> Self {
+        let date = DateTime::from_timestamp(passage.time, 0)
+            .map(|time| time.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        Self {
+            id: passage.id,
+            story: passage.story,
+            kind: passage.kind,
+            by: passage.by,
+            date,
+            title: passage.title,
+            text: passage.text,
+        }
+    }
+}
+
+// The agent's dynamic context: rig asks it for the passages closest to each
+// prompt and attaches them to the request. The passages handed over are
+// recorded like a tool call, so the answer can show what it was based on.
+#[derive(Clone)]
+pub struct ArchiveContext<M: EmbeddingModel> {
+    vectors: SemanticIndex<M>,
+    recorder: ToolRecorder,
+}
+
+impl<M: EmbeddingModel + 'static> ArchiveContext<M> {
+    pub fn new(vectors: SemanticIndex<M>, recorder: ToolRecorder) -> Self {
+        Self { vectors, recorder }
+    }
+
+    // Up to `n` passages related to `query`; unrelated ones are left out
+    // rather than filling the context
+    async fn passages(&self, query: &str, n: usize) -> Result<Vec<(f64, ContextPassage)>, VectorStoreError> {
+        let hits = self.vectors.nearest(query, n).await?;
+        Ok(hits
+            .into_iter()
+            .filter(|(similarity, _)| *similarity > 0.0)
+            .map(|(similarity, passage)| (similarity, passage.into()))
+            .collect())
+    }
+}
+
+impl<M: EmbeddingModel + 'static> VectorStoreIndex for ArchiveContext<M> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let passages = self.passages(query, n).await?;
+        if !passages.is_empty() {
+            let shown: Vec<&ContextPassage> = passages.iter().map(|(_, passage)| passage).collect();
+            self.recorder.record(ToolCall { tool: CONTEXT_SOURCE.to_string(), output: serde_json::to_value(&shown)? });
+        }
+
+        let mut documents = Vec::with_capacity(passages.len());
+        for (similarity, passage) in passages {
+            let id = passage.id.to_string();
+            documents.push((similarity, id, serde_json::from_value(serde_json::to_value(passage)?)?));
+        }
+        Ok(documents)
+    }
+
+    async fn top_n_ids(&self, query: &str, n: usize) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let passages = self.passages(query, n).await?;
+        Ok(passages.into_iter().map(|(similarity, passage)| (similarity, passage.id.to_string())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Archive;
+    use crate::client::memory::story;
+    use crate::semantic::HashEmbedding;
+
+    async fn context(recorder: &ToolRecorder) -> ArchiveContext<HashEmbedding> {
+        let archive = Archive::in_memory().unwrap();
+        archive
+            .put_items(&[
+                story(1, "pg", "Rust ownership", &[]),
+                story(2, "dang", "Rust ownership and the borrow checker", &[]),
+                story(3, "pg", "Growing tomatoes", &[]),
+            ])
+            .unwrap();
+        let vectors = SemanticIndex::build(&archive, HashEmbedding::new(4096), "hash/4096", 100).await.unwrap();
+        ArchiveContext::new(vectors, recorder.clone())
+    }
+
+    #[tokio::test]
+    async fn hands_over_related_passages_and_records_them() {
+        let recorder = ToolRecorder::new();
+        let context = context(&recorder).await;
+
+        let documents = context.top_n::<ContextPassage>("rust ownership", 3).await.unwrap();
+        // Story 3 shares no words with the query, so its similarity is 0
+        let ids: Vec<&str> = documents.iter().map(|(_, id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["1", "2"]);
+        assert!(documents.iter().all(|(similarity, _, _)| *similarity > 0.0));
+        assert_eq!(documents[1].2.by.as_deref(), Some("dang"));
+
+        let calls = recorder.take();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].tool, CONTEXT_SOURCE);
+        let shown: Vec<ContextPassage> = calls[0].output_as().unwrap();
+        assert_eq!(shown.iter().map(|passage| passage.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(shown[0].date, "2023-11-14");
+    }
+
+    #[tokio::test]
+    async fn unrelated_prompts_get_no_context() {
+        let recorder = ToolRecorder::new();
+        let context = context(&recorder).await;
+
+        assert!(context.top_n::<ContextPassage>("violin", 3).await.unwrap().is_empty());
+        assert!(recorder.take().is_empty());
+        // Only passages handed to the model are recorded
+        assert_eq!(context.top_n_ids("tomatoes", 3).await.unwrap().len(), 1);
+        assert!(recorder.take().is_empty());
+    }
+}
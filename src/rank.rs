@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::item::Item;
+
+// How `score_documents` weighs fields and blends in popularity and recency
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RankOptions {
+    pub enabled: bool, // false keeps each backend's own order, e.g. the feed's
+    pub stem: bool,
+    pub title_weight: f64,
+    pub text_weight: f64,
+    pub author_weight: f64,
+    pub k1: f64,                // BM25 term frequency saturation
+    pub b: f64,                 // BM25 length normalization
+    pub popularity_weight: f64, // added per ln(1 + points), 0 disables
+    pub recency_weight: f64,    // added for a brand-new item, decaying with age, 0 disables
+    pub recency_half_life_days: f64,
+}
+
+impl Default for RankOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stem: true,
+            title_weight: 3.0,
+            text_weight: 1.0,
+            author_weight: 0.5,
+            k1: 1.2,
+            b: 0.75,
+            popularity_weight: 0.0,
+            recency_weight: 0.0,
+            recency_half_life_days: 30.0,
+        }
+    }
+}
+
+// Split text into lowercase words at non-alphanumeric boundaries, optionally stemmed
+pub fn tokenize(text: &str, stem: bool) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let word = word.to_lowercase();
+            if stem {
+                stem_word(&word)
+            } else {
+                word
+            }
+        })
+        .collect()
+}
+
+// A light English suffix stripper; enough to match "databases" with "database"
+// and "running" with "run" without pulling in a full Porter stemmer. The plural
+// goes first, so "strings" and "string" stem alike, then -ing and -ed, but only
+// when what's left still has a vowel: "string" and "speed" aren't inflected.
+pub fn stem_word(word: &str) -> String {
+    const MIN_STEM: usize = 3;
+
+    let strip = |word: &str, suffix: &str, replacement: &str| -> Option<String> {
+        let stem = word.strip_suffix(suffix)?;
+        (stem.chars().count() >= MIN_STEM).then(|| format!("{}{}", stem, replacement))
+    };
+
+    let singular = strip(word, "ies", "y")
+        .or_else(|| strip(word, "sses", "ss"))
+        .or_else(|| {
+            let keeps_s = word.ends_with("ss") || word.ends_with("us") || word.ends_with("is");
+            if keeps_s {
+                None
+            } else {
+                strip(word, "s", "")
+            }
+        })
+        .unwrap_or_else(|| word.to_string());
+
+    if singular.ends_with("eed") {
+        return singular;
+    }
+    let inflected = strip(&singular, "ing", "").or_else(|| strip(&singular, "ed", ""));
+    match inflected {
+        Some(stem) if stem.contains(['a', 'e', 'i', 'o', 'u', 'y']) => {
+            // "running" -> "runn" -> "run", but "called" -> "call"
+            let mut chars: Vec<char> = stem.chars().collect();
+            let last = chars[chars.len() - 1];
+            if chars.len() > MIN_STEM && last == chars[chars.len() - 2] && !matches!(last, 'l' | 's' | 'z') {
+                chars.pop();
+            }
+            chars.into_iter().collect()
+        }
+        _ => singular,
+    }
+}
+
+// The searchable fields of a document, plus the signals used for blending
+#[derive(Debug, Clone, Default)]
+pub struct RankFields<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub author: &'a str,
+    pub points: Option<i32>,
+    pub time: i64,
+}
+
+impl<'a> RankFields<'a> {
+    pub fn from_item(item: &'a Item) -> Self {
+        Self {
+            title: item.title().unwrap_or_default(),
+            text: item.text().unwrap_or_default(),
+            author: item.by().unwrap_or_default(),
+            points: item.score(),
+            time: item.time(),
+        }
+    }
+}
+
+struct TokenizedField {
+    counts: HashMap<String, usize>,
+    len: usize,
+}
+
+impl TokenizedField {
+    fn new(text: &str, stem: bool) -> Self {
+        let tokens = tokenize(text, stem);
+        let mut counts = HashMap::new();
+        for token in &tokens {
+            *counts.entry(token.clone()).or_insert(0) += 1;
+        }
+        Self { counts, len: tokens.len() }
+    }
+}
+
+// Score every document against the query with field-weighted BM25, using the
+// documents themselves as the corpus for IDF. Documents that contain none of the
+// query terms score 0 and get no popularity or recency boost.
+pub fn score_documents(query: &str, docs: &[RankFields], options: &RankOptions, now: i64) -> Vec<f64> {
+    let terms: HashSet<String> = tokenize(query, options.stem).into_iter().collect();
+    if docs.is_empty() || terms.is_empty() {
+        return vec![0.0; docs.len()];
+    }
+
+    let fields: Vec<[TokenizedField; 3]> = docs
+        .iter()
+        .map(|doc| {
+            [
+                TokenizedField::new(doc.title, options.stem),
+                TokenizedField::new(doc.text, options.stem),
+                TokenizedField::new(doc.author, options.stem),
+            ]
+        })
+        .collect();
+    let weights = [options.title_weight, options.text_weight, options.author_weight];
+
+    let n = docs.len() as f64;
+    let avg_len: Vec<f64> = (0..3).map(|f| fields.iter().map(|doc| doc[f].len as f64).sum::<f64>() / n).collect();
+
+    let idf: HashMap<&String, f64> = terms
+        .iter()
+        .map(|term| {
+            let containing =
+                fields.iter().filter(|doc| doc.iter().any(|field| field.counts.contains_key(term))).count() as f64;
+            (term, (1.0 + (n - containing + 0.5) / (containing + 0.5)).ln())
+        })
+        .collect();
+
+    docs.iter()
+        .zip(&fields)
+        .map(|(doc, doc_fields)| {
+            let mut score = 0.0;
+            for (f, field) in doc_fields.iter().enumerate() {
+                let norm =
+                    if avg_len[f] > 0.0 { 1.0 - options.b + options.b * field.len as f64 / avg_len[f] } else { 1.0 };
+                for term in &terms {
+                    let tf = *field.counts.get(term).unwrap_or(&0) as f64;
+                    if tf > 0.0 {
+                        score += weights[f] * idf[term] * tf * (options.k1 + 1.0) / (tf + options.k1 * norm);
+                    }
+                }
+            }
+
+            blend(score, doc.points, doc.time, options, now)
+        })
+        .collect()
+}
+
+// Add the popularity and recency boosts to a text relevance score. Scores of 0,
+// which matched nothing, stay 0.
+pub fn blend(score: f64, points: Option<i32>, time: i64, options: &RankOptions, now: i64) -> f64 {
+    if score <= 0.0 {
+        return score;
+    }
+    let points = points.unwrap_or(0).max(0) as f64;
+    let age_days = (now - time).max(0) as f64 / 86_400.0;
+    let half_life = options.recency_half_life_days.max(f64::EPSILON);
+    score + options.popularity_weight * (1.0 + points).ln() + options.recency_weight * 0.5f64.powf(age_days / half_life)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const DAY: i64 = 86_400;
+
+    fn doc<'a>(title: &'a str, text: &'a str) -> RankFields<'a> {
+        RankFields { title, text, author: "someone", points: Some(10), time: NOW }
+    }
+
+    #[test]
+    fn singular_and_plural_stem_alike() {
+        for (a, b) in [
+            ("string", "strings"),
+            ("speed", "speeds"),
+            ("running", "run"),
+            ("runs", "run"),
+            ("databases", "database"),
+            ("libraries", "library"),
+            ("passed", "pass"),
+            ("called", "call"),
+        ] {
+            assert_eq!(stem_word(a), stem_word(b), "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn stems_keep_uninflected_endings() {
+        assert_eq!(stem_word("string"), "string");
+        assert_eq!(stem_word("speed"), "speed");
+        assert_eq!(stem_word("class"), "class");
+        assert_eq!(stem_word("status"), "status");
+        assert_eq!(stem_word("bus"), "bus");
+        assert_eq!(stem_word("sing"), "sing");
+    }
+
+    #[test]
+    fn plural_query_matches_singular_title() {
+        let docs = [doc("Fast string search", ""), doc("Unrelated", "")];
+        let scores = score_documents("strings", &docs, &RankOptions::default(), NOW);
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[test]
+    fn title_matches_outweigh_text_matches() {
+        let docs = [doc("rust compiler", "notes on a release"), doc("notes on a release", "rust compiler")];
+        let scores = score_documents("rust", &docs, &RankOptions::default(), NOW);
+        assert!(scores[0] > scores[1]);
+
+        let even = RankOptions { title_weight: 1.0, text_weight: 1.0, ..RankOptions::default() };
+        let scores = score_documents("rust", &docs, &even, NOW);
+        assert!((scores[0] - scores[1]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn author_field_is_searched() {
+        let mut by_pg = doc("Essays", "");
+        by_pg.author = "pg";
+        let docs = [by_pg, doc("Essays", "")];
+        let scores = score_documents("pg", &docs, &RankOptions::default(), NOW);
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[test]
+    fn popularity_breaks_otherwise_equal_matches() {
+        let mut popular = doc("rust compiler", "");
+        popular.points = Some(500);
+        let docs = [doc("rust compiler", ""), popular];
+
+        let scores = score_documents("rust", &docs, &RankOptions::default(), NOW);
+        assert_eq!(scores[0], scores[1]);
+
+        let options = RankOptions { popularity_weight: 1.0, ..RankOptions::default() };
+        let scores = score_documents("rust", &docs, &options, NOW);
+        assert!(scores[1] > scores[0]);
+        assert!((scores[1] - scores[0] - ((501f64).ln() - (11f64).ln())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recency_boost_halves_every_half_life() {
+        let options = RankOptions { recency_weight: 2.0, recency_half_life_days: 10.0, ..RankOptions::default() };
+        assert!((blend(1.0, None, NOW, &options, NOW) - 3.0).abs() < 1e-9);
+        assert!((blend(1.0, None, NOW - 10 * DAY, &options, NOW) - 2.0).abs() < 1e-9);
+        assert!((blend(1.0, None, NOW - 20 * DAY, &options, NOW) - 1.5).abs() < 1e-9);
+        // a timestamp from the future counts as brand new
+        assert!((blend(1.0, None, NOW + DAY, &options, NOW) - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn documents_that_match_nothing_get_no_boost() {
+        let options = RankOptions { popularity_weight: 1.0, recency_weight: 1.0, ..RankOptions::default() };
+        let docs = [doc("rust compiler", ""), doc("go compiler", "")];
+        let scores = score_documents("rust", &docs, &options, NOW);
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+        assert_eq!(score_documents("", &docs, &options, NOW), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn identical_documents_tie_exactly() {
+        let docs = [doc("rust compiler", "fast builds"), doc("rust compiler", "fast builds"), doc("go", "")];
+        let scores = score_documents("rust builds", &docs, &RankOptions::default(), NOW);
+        assert_eq!(scores[0], scores[1]);
+        assert!(scores[0] > scores[2]);
+    }
+
+    #[test]
+    fn rarer_terms_count_for_more() {
+        let docs = [doc("rust", ""), doc("rust", ""), doc("zig", ""), doc("other", "")];
+        let scores = score_documents("rust zig", &docs, &RankOptions::default(), NOW);
+        assert!(scores[2] > scores[0]);
+    }
+}
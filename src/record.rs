@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+// The output of one successful tool call, exactly as it was handed to the model
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub tool: String,
+    pub output: serde_json::Value,
+}
+
+impl ToolCall {
+    // Decode the output back into the tool's own output type
+    pub fn output_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_value(self.output.clone())
+    }
+}
+
+// Collects the output of every tool call an agent makes, so callers can use the
+// HN data straight from the tools instead of parsing it back out of the model's
+// reply. Clones share the same list.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRecorder {
+    calls: Arc<Mutex<Vec<ToolCall>>>,
+}
+
+impl ToolRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Wrap `tool` so each of its successful calls is recorded here
+    pub fn wrap<T: Tool>(&self, tool: T) -> Recorded<T> {
+        Recorded { tool, recorder: self.clone() }
+    }
+
+    // Remove and return the calls recorded so far, oldest first
+    pub fn take(&self) -> Vec<ToolCall> {
+        std::mem::take(&mut *self.calls.lock().expect("tool recorder poisoned"))
+    }
+
+    pub(crate) fn record(&self, call: ToolCall) {
+        self.calls.lock().expect("tool recorder poisoned").push(call);
+    }
+}
+
+// A tool that behaves exactly like `T` but records its output
+pub struct Recorded<T> {
+    tool: T,
+    recorder: ToolRecorder,
+}
+
+impl<T: Tool> Tool for Recorded<T> {
+    const NAME: &'static str = T::NAME;
+
+    type Error = T::Error;
+    type Args = T::Args;
+    type Output = T::Output;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let output = self.tool.call(args).await?;
+        // Tool outputs are plain data, so serializing them cannot fail in practice
+        if let Ok(value) = serde_json::to_value(&output) {
+            self.recorder.record(ToolCall { tool: T::NAME.to_string(), output: value });
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HNError;
+
+    // Doubles its argument, and fails on negative ones
+    struct Double;
+
+    impl Tool for Double {
+        const NAME: &'static str = "double";
+        type Error = HNError;
+        type Args = i64;
+        type Output = i64;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Double a number".to_string(),
+                parameters: serde_json::json!({"type": "integer"}),
+            }
+        }
+
+        async fn call(&self, args: i64) -> Result<i64, HNError> {
+            if args < 0 {
+                return Err(HNError::InvalidArgument(format!("{} is negative", args)));
+            }
+            Ok(args * 2)
+        }
+    }
+
+    fn outputs(calls: &[ToolCall]) -> Vec<i64> {
+        calls.iter().map(|call| call.output_as().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn records_outputs_in_call_order() {
+        let recorder = ToolRecorder::new();
+        let tool = recorder.wrap(Double);
+        assert_eq!(tool.call(1).await.unwrap(), 2);
+        assert!(tool.call(-1).await.is_err());
+        assert_eq!(tool.call(3).await.unwrap(), 6);
+
+        let calls = recorder.take();
+        assert!(calls.iter().all(|call| call.tool == "double"));
+        assert_eq!(outputs(&calls), [2, 6]);
+        assert_eq!(tool.definition(String::new()).await.name, "double");
+    }
+
+    #[tokio::test]
+    async fn take_starts_the_next_prompt_afresh() {
+        let recorder = ToolRecorder::new();
+        let tool = recorder.wrap(Double);
+        tool.call(1).await.unwrap();
+        assert_eq!(outputs(&recorder.take()), [2]);
+        assert!(recorder.take().is_empty());
+
+        // Tools wrapped by a clone record into the same list
+        let other = recorder.clone().wrap(Double);
+        other.call(5).await.unwrap();
+        tool.call(4).await.unwrap();
+        assert_eq!(outputs(&recorder.take()), [10, 8]);
+    }
+}
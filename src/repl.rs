@@ -0,0 +1,195 @@
+use anyhow::Result;
+use rig::completion::{Chat, Message};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+
+use rig_hn_assistant::record::ToolRecorder;
+
+use crate::format::{format_answer, preview, Answer, TableLayout};
+
+const HELP: &str = "\
+Commands:
+  /reset          forget the conversation so far
+  /history        show the conversation so far
+  /save <file>    save the conversation as JSON
+  /load <file>    replace the conversation with one saved by /save
+  /model [name]   show or switch the chat model, keeping the conversation
+  /help           show this help
+  /quit           leave (Ctrl-D works too)
+
+End a line with \\ to continue on the next one, or put \"\"\" on its own line
+to start and end a multiline message. Ctrl-C cancels the current request.";
+
+// An interactive chat with the HN agent that keeps history across turns
+pub struct Repl<A: Chat, F: Fn(&str) -> A> {
+    build_agent: F, // builds the agent for a model name, used by /model
+    model: String,
+    agent: A,
+    recorder: ToolRecorder, // the one `build_agent` wraps the agent's tools with
+    layout: TableLayout,
+    history: Vec<Message>,
+}
+
+impl<A: Chat, F: Fn(&str) -> A> Repl<A, F> {
+    pub fn new(model: &str, recorder: ToolRecorder, layout: TableLayout, build_agent: F) -> Self {
+        Self { agent: build_agent(model), build_agent, model: model.to_string(), recorder, layout, history: Vec::new() }
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        println!("Hacker News assistant using {}. Type /help for commands.", self.model);
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Some(input) = read_input(&mut lines).await? {
+            let input = input.trim();
+            if input.is_empty() {
+                continue;
+            }
+
+            match input.strip_prefix('/') {
+                Some(command) => {
+                    if !self.command(command) {
+                        break;
+                    }
+                }
+                None => self.send(input).await,
+            }
+        }
+
+        Ok(())
+    }
+
+    // Send one message, unless Ctrl-C cancels it first. Only completed turns
+    // are added to the history.
+    async fn send(&mut self, input: &str) {
+        let request = self.agent.chat(input, self.history.clone());
+
+        let response = tokio::select! {
+            response = request => response,
+            _ = tokio::signal::ctrl_c() => {
+                self.recorder.take();
+                println!("\nCancelled.");
+                return;
+            }
+        };
+        let tool_calls = self.recorder.take();
+
+        match response {
+            Ok(response) => {
+                let answer = Answer::new(response.clone(), tool_calls);
+                match answer.and_then(|answer| format_answer(&answer, &self.layout)) {
+                    Ok(formatted_output) => println!("{}", formatted_output),
+                    Err(e) => println!("Error: {:#}\n", e),
+                }
+
+                self.history.push(Message { role: "user".into(), content: input.into() });
+                self.history.push(Message { role: "assistant".into(), content: response });
+            }
+            Err(e) => println!("Error: {}\n", e),
+        }
+    }
+
+    // Run a slash command; returns false when the REPL should exit
+    fn command(&mut self, command: &str) -> bool {
+        let (name, arg) = match command.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (command, ""),
+        };
+
+        match (name, arg) {
+            ("quit" | "exit", _) => return false,
+            ("help", _) => println!("{}", HELP),
+            ("reset", _) => {
+                self.history.clear();
+                println!("Conversation cleared.");
+            }
+            ("history", _) => {
+                if self.history.is_empty() {
+                    println!("No messages yet.");
+                }
+                for message in &self.history {
+                    println!("[{}] {}", message.role, preview(&message.content, 200));
+                }
+            }
+            ("save" | "load", "") => println!("Usage: /{} <file>", name),
+            ("save", path) => match save_history(path, &self.history) {
+                Ok(()) => println!("Saved {} messages to {}.", self.history.len(), path),
+                Err(e) => println!("Error: {}", e),
+            },
+            ("load", path) => match load_history(path) {
+                Ok(history) => {
+                    self.history = history;
+                    println!("Loaded {} messages from {}.", self.history.len(), path);
+                }
+                Err(e) => println!("Error: {}", e),
+            },
+            ("model", "") => println!("Using {}.", self.model),
+            ("model", model) => {
+                self.agent = (self.build_agent)(model);
+                self.model = model.to_string();
+                println!("Switched to {}.", self.model);
+            }
+            _ => println!("Unknown command /{}. Type /help for commands.", name),
+        }
+
+        true
+    }
+}
+
+// Read one message, joining continuation lines and """ blocks. Returns `None`
+// at end of input. Ctrl-C while typing discards the message.
+async fn read_input(lines: &mut Lines<BufReader<Stdin>>) -> Result<Option<String>> {
+    let mut input = String::new();
+    let mut in_block = false;
+    prompt("> ")?;
+
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line?,
+            _ = tokio::signal::ctrl_c() => {
+                println!("\nDiscarded. Type /quit or press Ctrl-D to leave.");
+                input.clear();
+                in_block = false;
+                prompt("> ")?;
+                continue;
+            }
+        };
+
+        let Some(line) = line else {
+            // Ctrl-D: send what was typed, or leave if there is nothing
+            return Ok((!input.trim().is_empty()).then_some(input));
+        };
+
+        if line.trim() == "\"\"\"" {
+            if in_block {
+                return Ok(Some(input));
+            }
+            in_block = true;
+        } else if in_block {
+            input.push_str(&line);
+            input.push('\n');
+        } else if let Some(line) = line.strip_suffix('\\') {
+            input.push_str(line);
+            input.push('\n');
+        } else {
+            input.push_str(&line);
+            return Ok(Some(input));
+        }
+
+        prompt("... ")?;
+    }
+}
+
+fn prompt(text: &str) -> Result<()> {
+    use std::io::Write;
+    print!("{}", text);
+    std::io::stdout().flush()?;
+    Ok(())
+}
+
+fn save_history(path: &str, history: &[Message]) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(history)?)?;
+    Ok(())
+}
+
+fn load_history(path: &str) -> Result<Vec<Message>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
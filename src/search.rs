@@ -0,0 +1,852 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use futures::{stream, Stream, StreamExt};
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::Instant;
+
+use crate::algolia::{self, AlgoliaClient, AlgoliaQuery, AlgoliaSort};
+use crate::archive::{Archive, ArchiveQuery};
+use crate::client::{HnClient, StoryType};
+use crate::error::{HNError, SkippedItem};
+use crate::html::{self, MapText};
+use crate::index::{IndexSearch, SearchIndex};
+use crate::item::{Comment, Item};
+use crate::policy::DEFAULT_CALL_DEADLINE;
+use crate::query::{Comparison, Filter, Query, QUERY_SYNTAX};
+use crate::rank::{self, RankFields, RankOptions};
+
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+// Most Algolia pages one search reads while hits are being filtered out locally
+const MAX_ALGOLIA_PAGES: u32 = 5;
+
+// How much work a single search does
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SearchLimits {
+    pub max_candidates: usize,         // stories fetched from a feed before ranking
+    pub max_archive_candidates: usize, // newest matching stories read from the archive before ranking
+    pub comments_per_result: usize,    // top-level comments fetched for each result
+    pub default_max_results: i32,      // used when the call doesn't ask for a number
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self { max_candidates: 100, max_archive_candidates: 2000, comments_per_result: 3, default_max_results: 5 }
+    }
+}
+
+// Tool to search HN stories
+pub struct HNSearchTool<C> {
+    client: C,
+    algolia: AlgoliaClient,
+    archive: Option<Arc<Archive>>,   // local mirror kept by `sync`, if there is one
+    index: Option<Arc<SearchIndex>>, // full-text index over the archive
+    ranking: RankOptions,
+    limits: SearchLimits,
+    concurrency: usize,              // max number of stories fetched at once
+    deadline: Duration,              // overall time budget for one call
+    text_format: fn(&str) -> String, // what HN's HTML text fields are converted to
+    offline: bool,                   // Algolia can't be cached, so offline it isn't asked
+}
+
+impl<C: HnClient> HNSearchTool<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            algolia: AlgoliaClient::default(),
+            archive: None,
+            index: None,
+            ranking: RankOptions::default(),
+            limits: SearchLimits::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            deadline: DEFAULT_CALL_DEADLINE,
+            text_format: html::to_text,
+            offline: false,
+        }
+    }
+
+    pub fn with_algolia(mut self, algolia: AlgoliaClient) -> Self {
+        self.algolia = algolia;
+        self
+    }
+
+    pub fn with_archive(mut self, archive: Arc<Archive>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    pub fn with_index(mut self, index: Arc<SearchIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn with_ranking(mut self, ranking: RankOptions) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: SearchLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    // Return text as e.g. `html::to_markdown` rather than plain text
+    pub fn with_text_format(mut self, convert: fn(&str) -> String) -> Self {
+        self.text_format = convert;
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+}
+
+// A matching item, its top comments and its relevance score
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SearchResult {
+    pub item: Item,
+    pub comments: Vec<Comment>,
+    pub score: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<String>, // matching passages, index backend only
+}
+
+// Everything a search returns. Items that could not be fetched, even after
+// retries, are listed in `skipped` instead of failing the whole search.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SearchOutput {
+    pub results: Vec<SearchResult>,
+    pub skipped: Vec<SkippedItem>,
+    pub timed_out: bool, // the call deadline cut the search short
+}
+
+// Where `HNSearchTool` looks for matching stories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    #[default]
+    Feed, // scan the current ranking list for the story type
+    Algolia, // full-text search over all of HN via hn.algolia.com
+    Archive, // the local SQLite mirror kept by `sync`
+    Index,   // the full-text index over the archive, including comments
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchArgs {
+    pub query: String,
+    pub story_type: Option<StoryType>,
+    pub max_results: Option<i32>,
+    pub backend: Option<SearchBackend>,
+    // The remaining arguments only apply to the algolia, archive and index backends
+    pub sort: Option<AlgoliaSort>,
+    pub min_points: Option<u32>,
+    pub min_comments: Option<u32>,
+    pub created_after: Option<String>,  // YYYY-MM-DD
+    pub created_before: Option<String>, // YYYY-MM-DD
+    pub page: Option<u32>,
+}
+
+impl<C: HnClient> Tool for HNSearchTool<C> {
+    const NAME: &'static str = "search_hn";
+    type Error = HNError;
+    type Args = SearchArgs;
+    type Output = SearchOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        // The local backends are only offered once `sync` has created them
+        let mut backends = vec!["feed", "algolia"];
+        let mut backend_description = "Where to search: 'feed' scans the current story list (default), \
+            'algolia' does full-text search over all of HN history"
+            .to_string();
+        if self.archive.is_some() {
+            backends.push("archive");
+            backend_description.push_str(", 'archive' filters the local mirror of recent months, offline");
+        }
+        if self.archive.is_some() && self.index.is_some() {
+            backends.push("index");
+            backend_description.push_str(
+                ", 'index' does ranked full-text search of the local mirror, comments included, \
+                and returns highlighted passages",
+            );
+        }
+        // Ranking needs every candidate, so only an unranked feed search gets
+        // cheaper with fewer results
+        let feed_cost = if self.ranking.enabled {
+            format!(
+                "; the 'feed' backend reads its first {} stories to rank them however few are asked for",
+                self.limits.max_candidates
+            )
+        } else {
+            "; the 'feed' backend returns the first matches in feed order and stops reading there".to_string()
+        };
+        ToolDefinition {
+            name: "search_hn".to_string(),
+            description: "Search for discussions on Hacker News".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": format!("Search query for HN stories. {}", QUERY_SYNTAX)
+                    },
+                    "story_type": {
+                        "type": "string",
+                        "description": "Type of stories to search (top, best, new, ask, show, job)",
+                        "enum": ["top", "best", "new", "ask", "show", "job"]
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!(
+                            "Maximum number of stories to return (default: {}){}",
+                            self.limits.default_max_results,
+                            feed_cost
+                        )
+                    },
+                    "backend": {
+                        "type": "string",
+                        "description": backend_description,
+                        "enum": backends
+                    },
+                    "sort": {
+                        "type": "string",
+                        "description": "Algolia and local backends only: order by relevance (default) or date, newest first",
+                        "enum": ["relevance", "date"]
+                    },
+                    "min_points": {
+                        "type": "integer",
+                        "description": "Algolia and local backends only: minimum story points"
+                    },
+                    "min_comments": {
+                        "type": "integer",
+                        "description": "Algolia and local backends only: minimum number of comments"
+                    },
+                    "created_after": {
+                        "type": "string",
+                        "description": "Algolia and local backends only: only stories submitted after this date (YYYY-MM-DD)"
+                    },
+                    "created_before": {
+                        "type": "string",
+                        "description": "Algolia and local backends only: only stories submitted before this date (YYYY-MM-DD)"
+                    },
+                    "page": {
+                        "type": "integer",
+                        "description": "Algolia and index only: page of results to return, starting at 0"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let query = Query::parse(&args.query)?;
+        let deadline = Instant::now() + self.deadline;
+
+        let mut output = SearchOutput::default();
+        match args.backend.unwrap_or_default() {
+            SearchBackend::Feed => self.search_feed(&args, &query, deadline, &mut output).await?,
+            SearchBackend::Algolia => self.search_algolia(&args, &query, deadline, &mut output).await?,
+            SearchBackend::Archive => self.search_archive(&args, &query, deadline, &mut output).await?,
+            SearchBackend::Index => self.search_index(&args, &query, deadline, &mut output).await?,
+        }
+
+        if output.results.is_empty() {
+            return Err(if output.timed_out {
+                HNError::Timeout(format!("{} (call deadline of {:?})", Self::NAME, self.deadline))
+            } else {
+                HNError::NoResults
+            });
+        }
+
+        Ok(output)
+    }
+}
+
+impl<C: HnClient> HNSearchTool<C> {
+    async fn search_feed(
+        &self,
+        args: &SearchArgs,
+        query: &Query,
+        deadline: Instant,
+        output: &mut SearchOutput,
+    ) -> Result<(), HNError> {
+        // Get stories based on type
+        let story_ids =
+            before_deadline(deadline, self.client.list_stories(args.story_type.unwrap_or_default())).await?;
+
+        let max_results = args.max_results.unwrap_or(self.limits.default_max_results).max(0) as usize;
+
+        // Ranking needs every candidate. Without it the feed's order stands, so the
+        // first `max_results` matches are the results and reading stops there.
+        let wanted = if self.ranking.enabled { usize::MAX } else { max_results };
+        let matching = {
+            // Fetch stories concurrently, keeping story ID order so ties rank by feed position
+            let mut fetches = std::pin::pin!(stream::iter(story_ids.into_iter().take(self.limits.max_candidates))
+                .map(|story_id| async move { (story_id, self.client.get_item(story_id).await) })
+                .buffered(self.concurrency.max(1)));
+
+            // Text is matched, ranked and returned converted rather than as HN's HTML;
+            // Markdown's markup is punctuation the tokenizer skips like any other
+            let mut matching = Vec::new();
+            while matching.len() < wanted {
+                let Some((story_id, result)) = next_before(deadline, output, &mut fetches).await else { break };
+                match result {
+                    Ok(Some(item)) => {
+                        let item = item.map_text(self.text_format);
+                        if query.matches(&item, self.ranking.stem) {
+                            matching.push(item);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) if e.is_recoverable() => output.skip(story_id, &e),
+                    Err(e) => return Err(e),
+                }
+            }
+            // Dropping the stream here cancels the fetches still in flight
+            matching
+        };
+
+        let mut scored = self.score(query, matching);
+        if self.ranking.enabled {
+            scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        }
+        scored.truncate(max_results);
+
+        self.with_comments(&self.client, scored, deadline, output).await
+    }
+
+    async fn search_algolia(
+        &self,
+        args: &SearchArgs,
+        query: &Query,
+        deadline: Instant,
+        output: &mut SearchOutput,
+    ) -> Result<(), HNError> {
+        if self.offline {
+            return Err(HNError::Offline("Algolia search".to_string()));
+        }
+        let pushdown = algolia::Pushdown::from_query(query);
+
+        let mut numeric_filters = pushdown.numeric_filters;
+        if let Some(points) = args.min_points {
+            numeric_filters.push(format!("points>={}", points));
+        }
+        if let Some(comments) = args.min_comments {
+            numeric_filters.push(format!("num_comments>={}", comments));
+        }
+        if let Some(date) = &args.created_after {
+            numeric_filters.push(format!("created_at_i>{}", parse_date(date)?));
+        }
+        if let Some(date) = &args.created_before {
+            numeric_filters.push(format!("created_at_i<{}", parse_date(date)?));
+        }
+
+        let mut tags = pushdown.tags;
+        if !pushdown.restricts_type {
+            tags.push(algolia::story_type_tag(args.story_type.unwrap_or_default()).to_string());
+        }
+
+        let sort = args.sort.unwrap_or_default();
+        let wanted = args.max_results.unwrap_or(self.limits.default_max_results).max(1) as usize;
+        let mut algolia_query = AlgoliaQuery {
+            query: pushdown.text,
+            optional_words: pushdown.optional_words,
+            sort,
+            tags,
+            numeric_filters,
+            page: args.page.unwrap_or(0),
+            hits_per_page: wanted as u32,
+        };
+
+        // Only part of the query can be expressed to Algolia, so check the rest here.
+        // Hits it rules out are made up for from the pages that follow, until enough
+        // match, the pages run out or MAX_ALGOLIA_PAGES have been read.
+        let mut matching = Vec::new();
+        for _ in 0..MAX_ALGOLIA_PAGES {
+            let response = match before_deadline(deadline, self.algolia.search(&algolia_query)).await {
+                Ok(response) => response,
+                Err(HNError::Timeout(_)) if !matching.is_empty() => {
+                    output.timed_out = true;
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            for hit in response.hits {
+                let item = hit.into_item()?.map_text(self.text_format);
+                if query.matches(&item, self.ranking.stem) {
+                    matching.push(item);
+                }
+            }
+            algolia_query.page += 1;
+            if matching.len() >= wanted || algolia_query.page >= response.nb_pages {
+                break;
+            }
+        }
+        matching.truncate(wanted);
+
+        // Re-rank unless the hits were asked for by date
+        let mut scored = self.score(query, matching);
+        if self.ranking.enabled && sort == AlgoliaSort::Relevance {
+            scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        }
+
+        // Algolia hits carry no child IDs, so look the items up to find their comments
+        let ids: Vec<u32> = scored.iter().map(|(item, _)| item.id()).collect();
+        let lookups = collect_until(
+            deadline,
+            output,
+            stream::iter(ids).map(|id| self.client.get_item(id)).buffered(self.concurrency.max(1)),
+        )
+        .await;
+
+        for ((item, _), lookup) in scored.iter_mut().zip(lookups) {
+            let kids = match lookup {
+                Ok(Some(full)) => full.kids().to_vec(),
+                Ok(None) => continue,
+                Err(e) if e.is_recoverable() => {
+                    output.skip(item.id(), &e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match item {
+                Item::Story(story) => story.kids = Some(kids),
+                Item::Comment(comment) => comment.kids = Some(kids),
+                _ => {}
+            }
+        }
+
+        self.with_comments(&self.client, scored, deadline, output).await
+    }
+
+    async fn search_archive(
+        &self,
+        args: &SearchArgs,
+        query: &Query,
+        deadline: Instant,
+        output: &mut SearchOutput,
+    ) -> Result<(), HNError> {
+        let archive = self.archive.as_deref().ok_or_else(|| {
+            HNError::InvalidArgument("there is no local archive; run `sync` to create one".to_string())
+        })?;
+
+        let mut archive_query = ArchiveQuery::from_query(query, self.ranking.stem);
+        archive_query.story_type = args.story_type.unwrap_or_default();
+        archive_query.limit = self.limits.max_archive_candidates;
+        if let Some(points) = args.min_points {
+            archive_query.min_points = archive_query.min_points.max(Some(points.into()));
+        }
+        if let Some(comments) = args.min_comments {
+            archive_query.min_comments = archive_query.min_comments.max(Some(comments.into()));
+        }
+        if let Some(date) = &args.created_after {
+            archive_query.created_after = archive_query.created_after.max(Some(parse_date(date)?));
+        }
+        if let Some(date) = &args.created_before {
+            let before = parse_date(date)?;
+            archive_query.created_before = Some(archive_query.created_before.map_or(before, |time| time.min(before)));
+        }
+
+        let matching = archive
+            .search(&archive_query)?
+            .into_iter()
+            .map(|item| item.map_text(self.text_format))
+            .filter(|item| query.matches(item, self.ranking.stem))
+            .collect();
+        // The archive returns the newest first, which is what a date sort wants
+        let mut scored = self.score(query, matching);
+        if self.ranking.enabled && args.sort.unwrap_or_default() == AlgoliaSort::Relevance {
+            scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        }
+        scored.truncate(args.max_results.unwrap_or(self.limits.default_max_results).max(0) as usize);
+
+        // Comments come from the archive too, so an archive search works offline
+        self.with_comments(archive, scored, deadline, output).await
+    }
+
+    async fn search_index(
+        &self,
+        args: &SearchArgs,
+        query: &Query,
+        deadline: Instant,
+        output: &mut SearchOutput,
+    ) -> Result<(), HNError> {
+        let (Some(archive), Some(index)) = (self.archive.as_deref(), self.index.as_deref()) else {
+            return Err(HNError::InvalidArgument(
+                "there is no local search index; run `sync` to build one".to_string(),
+            ));
+        };
+
+        // The call's filters become qualifiers, so the index applies them all at once
+        let mut clauses = vec![query.clone()];
+        if !mentions_type(query) {
+            clauses.push(story_type_clause(args.story_type.unwrap_or_default()));
+        }
+        if let Some(points) = args.min_points {
+            clauses.push(Query::Filter(Filter::Points(Comparison::GreaterOrEqual, points.into())));
+        }
+        if let Some(comments) = args.min_comments {
+            clauses.push(Query::Filter(Filter::Comments(Comparison::GreaterOrEqual, comments.into())));
+        }
+        if let Some(date) = &args.created_after {
+            clauses.push(Query::Filter(Filter::After(parse_date(date)?)));
+        }
+        if let Some(date) = &args.created_before {
+            clauses.push(Query::Filter(Filter::Before(parse_date(date)?)));
+        }
+
+        let limit = args.max_results.unwrap_or(self.limits.default_max_results).max(1) as usize;
+        let by_date = args.sort == Some(AlgoliaSort::Date);
+        let options = IndexSearch { limit, offset: args.page.unwrap_or(0) as usize * limit, by_date };
+        let hits = index.search(&Query::And(clauses), &self.ranking, options)?;
+
+        let now = chrono::Utc::now().timestamp();
+        let mut scored = Vec::new();
+        let mut highlights = Vec::new();
+        for hit in hits {
+            // The index may be a batch ahead of or behind the archive while a sync runs
+            let Some(item) = archive.item(hit.id)? else { continue };
+            let score = rank::blend(hit.score.into(), item.score(), item.time(), &self.ranking, now);
+            scored.push((item.map_text(self.text_format), score));
+            highlights.push(hit.highlights);
+        }
+
+        self.with_comments(archive, scored, deadline, output).await?;
+        for (result, highlights) in output.results.iter_mut().zip(highlights) {
+            result.highlights = highlights;
+        }
+        if !by_date {
+            output.results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        Ok(())
+    }
+
+    // Pair each item with its combined relevance score
+    fn score(&self, query: &Query, items: Vec<Item>) -> Vec<(Item, f64)> {
+        let fields: Vec<RankFields> = items.iter().map(RankFields::from_item).collect();
+        let now = chrono::Utc::now().timestamp();
+        let terms = query.positive_terms().join(" ");
+        let scores = rank::score_documents(&terms, &fields, &self.ranking, now);
+        items.into_iter().zip(scores).collect()
+    }
+
+    // Fetch top comments for the final results, keeping their order. The
+    // comments of every result share one stream, so no more than `concurrency`
+    // fetches are in flight; those not done by the deadline are left out.
+    async fn with_comments(
+        &self,
+        client: &impl HnClient,
+        scored: Vec<(Item, f64)>,
+        deadline: Instant,
+        output: &mut SearchOutput,
+    ) -> Result<(), HNError> {
+        let count = self.limits.comments_per_result;
+        let wanted: Vec<(usize, u32)> = scored
+            .iter()
+            .enumerate()
+            .flat_map(|(result, (item, _))| item.kids().iter().take(count).map(move |&id| (result, id)))
+            .collect();
+        let fetched = collect_until(
+            deadline,
+            output,
+            stream::iter(wanted)
+                .map(|(result, id)| async move { (result, id, client.get_item(id).await) })
+                .buffered(self.concurrency.max(1)),
+        )
+        .await;
+
+        let mut comments = vec![Vec::new(); scored.len()];
+        for (result, id, fetch) in fetched {
+            add_comment(id, fetch, self.text_format, &mut comments[result], &mut output.skipped)?;
+        }
+        for ((item, score), comments) in scored.into_iter().zip(comments) {
+            output.results.push(SearchResult { item, comments, score, highlights: Vec::new() });
+        }
+
+        Ok(())
+    }
+}
+
+impl SearchOutput {
+    fn skip(&mut self, id: u32, error: &HNError) {
+        self.skipped.push(SkippedItem { id, reason: error.to_string() });
+    }
+}
+
+// Await `future`, failing with a timeout if the deadline passes first
+async fn before_deadline<T>(deadline: Instant, future: impl Future<Output = Result<T, HNError>>) -> Result<T, HNError> {
+    tokio::time::timeout_at(deadline, future)
+        .await
+        .map_err(|_| HNError::Timeout("search_hn (call deadline)".to_string()))?
+}
+
+// Drain `stream` until it ends or the deadline passes, noting a timeout in `output`
+async fn collect_until<S: Stream>(deadline: Instant, output: &mut SearchOutput, stream: S) -> Vec<S::Item> {
+    let mut stream = std::pin::pin!(stream);
+    let mut items = Vec::new();
+    while let Some(item) = next_before(deadline, output, &mut stream).await {
+        items.push(item);
+    }
+    items
+}
+
+// The next item of `stream`, or None once it ends or the deadline passes,
+// noting a timeout in `output`
+async fn next_before<S: Stream + Unpin>(
+    deadline: Instant,
+    output: &mut SearchOutput,
+    stream: &mut S,
+) -> Option<S::Item> {
+    match tokio::time::timeout_at(deadline, stream.next()).await {
+        Ok(item) => item,
+        Err(_) => {
+            output.timed_out = true;
+            None
+        }
+    }
+}
+
+// Whether the query picks item types itself with type:
+fn mentions_type(query: &Query) -> bool {
+    match query {
+        Query::Filter(Filter::Type(_)) => true,
+        Query::And(queries) | Query::Or(queries) => queries.iter().any(mentions_type),
+        _ => false,
+    }
+}
+
+// The stories a ranking list would show, as a query. Ask and Show HN are
+// recognized by their title, as HN does.
+fn story_type_clause(story_type: StoryType) -> Query {
+    let title = |words: &[&str]| Query::Filter(Filter::Title(words.iter().map(|word| word.to_string()).collect()));
+    let kind = |kind: &str| Query::Filter(Filter::Type(kind.to_string()));
+    match story_type {
+        StoryType::Ask => Query::And(vec![kind("story"), title(&["ask", "hn"])]),
+        StoryType::Show => Query::And(vec![kind("story"), title(&["show", "hn"])]),
+        StoryType::Job => kind("job"),
+        StoryType::Top | StoryType::Best | StoryType::New => Query::Or(vec![kind("story"), kind("poll")]),
+    }
+}
+
+// Unix timestamp of midnight UTC on a YYYY-MM-DD date
+fn parse_date(date: &str) -> Result<i64, HNError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp())
+        .map_err(|_| HNError::InvalidArgument(format!("'{}' is not a YYYY-MM-DD date", date)))
+}
+
+// Fetch the first few top-level comments one at a time, along with any that
+// had to be skipped. Meant for the local archive, where fetches don't wait on
+// the network; searches over the API fetch through `with_comments`.
+pub(crate) async fn fetch_top_comments<C: HnClient>(
+    client: &C,
+    comment_ids: &[u32],
+    count: usize,
+    convert: fn(&str) -> String,
+) -> Result<(Vec<Comment>, Vec<SkippedItem>), HNError> {
+    let mut comments = Vec::new();
+    let mut skipped = Vec::new();
+    for &comment_id in comment_ids.iter().take(count) {
+        add_comment(comment_id, client.get_item(comment_id).await, convert, &mut comments, &mut skipped)?;
+    }
+    Ok((comments, skipped))
+}
+
+// File a fetched comment under `comments`, or under `skipped` if it can't be shown
+fn add_comment(
+    comment_id: u32,
+    fetch: Result<Option<Item>, HNError>,
+    convert: fn(&str) -> String,
+    comments: &mut Vec<Comment>,
+    skipped: &mut Vec<SkippedItem>,
+) -> Result<(), HNError> {
+    match fetch {
+        Ok(Some(Item::Comment(comment))) => comments.push(comment.map_text(convert)),
+        Ok(Some(other)) => {
+            skipped.push(SkippedItem { id: comment_id, reason: format!("item is a {}, not a comment", other.kind()) })
+        }
+        Ok(None) => {}
+        Err(e) if e.is_recoverable() => skipped.push(SkippedItem { id: comment_id, reason: e.to_string() }),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algolia::fake::{story_hit, FakeAlgolia};
+    use crate::client::memory::{comment, story, MemoryClient};
+    use crate::policy::RequestPolicy;
+
+    // Five results with three comments each, one of which fails to load
+    fn client() -> MemoryClient {
+        let mut client = MemoryClient::new().with_delay(Duration::from_millis(10)).with_failure(52);
+        for id in 1..=5 {
+            let kids = [id * 10 + 1, id * 10 + 2, id * 10 + 3];
+            client = client.with_item(story(id, "pg", "Story", &kids));
+            for kid in kids {
+                client = client.with_item(comment(kid, "dang", id, "Comment", &[]));
+            }
+        }
+        client
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn comment_fetches_share_the_concurrency_cap() {
+        let client = client();
+        let tool = HNSearchTool::new(client.clone()).with_concurrency(2);
+        let mut scored = Vec::new();
+        for id in 1..=5 {
+            scored.push((client.get_item(id).await.unwrap().unwrap(), 1.0));
+        }
+
+        let mut output = SearchOutput::default();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        tool.with_comments(&client, scored, deadline, &mut output).await.unwrap();
+
+        assert_eq!(client.max_in_flight(), 2);
+        let counts: Vec<usize> = output.results.iter().map(|result| result.comments.len()).collect();
+        assert_eq!(counts, [3, 3, 3, 3, 2]);
+        assert_eq!(output.results[0].comments[0].id, 11);
+        assert_eq!(output.skipped.iter().map(|skipped| skipped.id).collect::<Vec<_>>(), [52]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn comments_past_the_deadline_are_left_out() {
+        let client = client();
+        let tool = HNSearchTool::new(client.clone()).with_concurrency(1);
+        let scored = vec![(client.get_item(1).await.unwrap().unwrap(), 1.0)];
+
+        let mut output = SearchOutput::default();
+        let deadline = Instant::now() + Duration::from_millis(25);
+        tool.with_comments(&client, scored, deadline, &mut output).await.unwrap();
+
+        assert!(output.timed_out);
+        assert_eq!(output.results.len(), 1);
+        assert_eq!(output.results[0].comments.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn offline_searches_never_ask_algolia() {
+        let client = client();
+        let tool = HNSearchTool::new(client.clone()).offline(true);
+        let args =
+            SearchArgs { query: "story".to_string(), backend: Some(SearchBackend::Algolia), ..SearchArgs::default() };
+        let error = tool.call(args).await.unwrap_err();
+        assert!(matches!(error, HNError::Offline(_)), "{}", error);
+        assert_eq!(client.requests(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_call_deadline_is_a_timeout() {
+        let policy = RequestPolicy { max_retries: u32::MAX, ..RequestPolicy::default() };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let retrying =
+            policy.retry(|| async { Err::<(), _>(HNError::HttpStatus { url: "item/1".to_string(), status: 503 }) });
+        let error = before_deadline(deadline, retrying).await.unwrap_err();
+        assert!(matches!(error, HNError::Timeout(_)), "{}", error);
+        assert_eq!(Instant::now(), deadline);
+    }
+
+    // Twenty stories on the top list, every other one about Rust
+    fn feed() -> MemoryClient {
+        let stories = (1..=20).map(|id| story(id, "pg", if id % 2 == 0 { "Rust news" } else { "Go news" }, &[]));
+        MemoryClient::new()
+            .with_delay(Duration::from_millis(10))
+            .with_items(stories)
+            .with_list(StoryType::Top, (1..=20).collect())
+    }
+
+    fn feed_search(max_results: i32) -> SearchArgs {
+        SearchArgs { query: "rust".to_string(), max_results: Some(max_results), ..SearchArgs::default() }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unranked_feed_searches_stop_at_max_results() {
+        let client = feed();
+        let ranking = RankOptions { enabled: false, ..RankOptions::default() };
+        let tool = HNSearchTool::new(client.clone()).with_ranking(ranking).with_concurrency(2);
+
+        let output = tool.call(feed_search(3)).await.unwrap();
+        let ids: Vec<u32> = output.results.iter().map(|result| result.item.id()).collect();
+        assert_eq!(ids, [2, 4, 6]);
+        // The list, six stories read and no more than the concurrency cap started
+        // past them, which were cancelled
+        assert!(client.requests() <= 1 + 6 + 2, "{} requests", client.requests());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ranked_feed_searches_read_every_candidate() {
+        let client = feed();
+        let tool = HNSearchTool::new(client.clone()).with_concurrency(2);
+
+        let output = tool.call(feed_search(3)).await.unwrap();
+        assert_eq!(output.results.len(), 3);
+        assert_eq!(client.requests(), 1 + 20);
+
+        let client = feed();
+        let limits = SearchLimits { max_candidates: 8, ..SearchLimits::default() };
+        let tool = HNSearchTool::new(client.clone()).with_limits(limits);
+        tool.call(feed_search(3)).await.unwrap();
+        assert_eq!(client.requests(), 1 + 8);
+    }
+
+    #[tokio::test]
+    async fn algolia_searches_read_on_until_enough_hits_match() {
+        // Half the hits on each page are ruled out by domain:, which Algolia isn't told about
+        let pages = (0..4)
+            .map(|page| {
+                let github = story_hit(page * 2 + 1, "Rust crate", "https://github.com/rust-lang/rust");
+                let elsewhere = story_hit(page * 2 + 2, "Rust talk", "https://youtube.com/watch");
+                vec![github, elsewhere]
+            })
+            .collect();
+        let algolia = FakeAlgolia::start(pages).await;
+        let tool = HNSearchTool::new(MemoryClient::new()).with_algolia(algolia.client());
+        let args = SearchArgs {
+            query: "rust AND domain:github.com".to_string(),
+            backend: Some(SearchBackend::Algolia),
+            sort: Some(AlgoliaSort::Date),
+            max_results: Some(2),
+            ..SearchArgs::default()
+        };
+
+        let output = tool.call(args.clone()).await.unwrap();
+        let ids: Vec<u32> = output.results.iter().map(|result| result.item.id()).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(algolia.requests().len(), 2);
+
+        // With too few matches on every page, reading stops at the last one
+        let args = SearchArgs { max_results: Some(10), ..args };
+        let output = tool.call(args).await.unwrap();
+        assert_eq!(output.results.len(), 4);
+        assert_eq!(algolia.requests().len(), 2 + 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn searches_with_nothing_by_the_deadline_time_out() {
+        let client = client().with_delay(Duration::from_secs(10)).with_list(StoryType::Top, vec![1, 2, 3]);
+        let tool = HNSearchTool::new(client).with_deadline(Duration::from_secs(1));
+        let args =
+            SearchArgs { query: "story".to_string(), backend: Some(SearchBackend::Feed), ..SearchArgs::default() };
+        let error = tool.call(args).await.unwrap_err();
+        assert!(matches!(error, HNError::Timeout(_)), "{}", error);
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rig::{
+    completion::ToolDefinition,
+    embeddings::{DocumentEmbeddings, Embedding, EmbeddingError, EmbeddingModel, EmbeddingsBuilder},
+    tool::Tool,
+    vector_store::{
+        in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+        VectorStore, VectorStoreError, VectorStoreIndex,
+    },
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::archive::Archive;
+use crate::error::HNError;
+use crate::html::{self, MapText};
+use crate::index::{IndexSearch, SearchIndex};
+use crate::item::Item;
+use crate::query::Query;
+use crate::rank::{tokenize, RankOptions};
+use crate::search::{fetch_top_comments, SearchLimits, SearchOutput, SearchResult};
+use crate::task::run_as_task;
+
+// Size of `HashEmbedding` vectors unless configured otherwise
+pub const DEFAULT_HASH_DIMENSIONS: usize = 256;
+
+// Newest archived stories and comments embedded and searched, unless configured
+// otherwise; the in-memory store holds every vector
+pub const DEFAULT_MAX_PASSAGES: usize = 20_000;
+
+// Longest text embedded for one item; the rest of a long comment is left out
+const MAX_PASSAGE_CHARS: usize = 2000;
+
+// Items read from the archive and embedded per request
+const EMBED_BATCH: usize = 256;
+
+// Passages fetched from the vector store per result asked for, since several
+// may belong to the same story
+const PASSAGES_PER_RESULT: usize = 8;
+
+// Matching passages shown with each result
+const MAX_HIGHLIGHTS: usize = 3;
+
+// Reciprocal rank fusion constant: a story ranked r-th in one list scores
+// 1 / (RRF_K + r), so larger values flatten the difference between ranks
+const RRF_K: f64 = 60.0;
+
+// A deterministic embedding computed locally by feature hashing: every stemmed
+// word and each of its character trigrams adds ±1 to a dimension picked by
+// its hash. It only sees shared vocabulary, not meaning, but needs no API key
+// or network, so it works offline and gives the same vectors on every run.
+#[derive(Debug, Clone, Copy)]
+pub struct HashEmbedding {
+    ndims: usize,
+}
+
+impl HashEmbedding {
+    pub fn new(ndims: usize) -> Self {
+        Self { ndims: ndims.max(1) }
+    }
+
+    // A unit vector, or all zeros for text without words
+    pub fn embed(&self, text: &str) -> Vec<f64> {
+        let mut vector = vec![0.0; self.ndims];
+        for word in tokenize(text, true) {
+            self.add(&mut vector, &word, 1.0);
+            // Trigrams let "rustlang" land near "rust"
+            let padded: Vec<char> = format!("<{}>", word).chars().collect();
+            for trigram in padded.windows(3) {
+                self.add(&mut vector, &trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+        let norm = vector.iter().map(|value| value * value).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|value| *value /= norm);
+        }
+        vector
+    }
+
+    fn add(&self, vector: &mut [f64], feature: &str, weight: f64) {
+        let hash = fnv1a(feature.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % self.ndims as u64) as usize] += sign * weight;
+    }
+}
+
+impl Default for HashEmbedding {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_DIMENSIONS)
+    }
+}
+
+impl EmbeddingModel for HashEmbedding {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    async fn embed_documents(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(documents.into_iter().map(|document| Embedding { vec: self.embed(&document), document }).collect())
+    }
+}
+
+// 64-bit FNV-1a, which unlike std's hasher is guaranteed stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+// An archived story or comment as the vector store holds it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Passage {
+    pub id: u32,
+    pub story: u32, // the story it belongs to; its own ID for a story
+    pub kind: String,
+    pub by: Option<String>,
+    pub time: i64,
+    pub title: Option<String>, // the story's title, for comments too
+    pub text: String,          // plain text, cut to MAX_PASSAGE_CHARS
+}
+
+impl Passage {
+    // The passage for an archived item, or None if it has no text. A reply
+    // whose story isn't archived yet counts as its own story.
+    pub fn from_archive(archive: &Archive, item: &Item) -> Result<Option<Self>, HNError> {
+        let story = match item.parent() {
+            None => None,
+            Some(_) => match archive.root_of(item.id())? {
+                Some(root) => archive.item(root)?,
+                None => None,
+            },
+        };
+        let story = story.as_ref().unwrap_or(item);
+        let text: String = html::to_text(item.text().unwrap_or_default()).chars().take(MAX_PASSAGE_CHARS).collect();
+        if text.trim().is_empty() && item.title().is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Passage {
+            id: item.id(),
+            story: story.id(),
+            kind: item.kind().to_string(),
+            by: item.by().map(str::to_string),
+            time: item.time(),
+            title: story.title().map(str::to_string),
+            text,
+        }))
+    }
+
+    // What gets embedded: a story's title and text, or a comment's text
+    fn embedded_text(&self) -> String {
+        match (&self.title, self.id == self.story) {
+            (Some(title), true) if self.text.is_empty() => title.clone(),
+            (Some(title), true) => format!("{}\n\n{}", title, self.text),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+// Embed the passages among the newest `limit` archived items that `model`
+// hasn't embedded yet, newest first, and persist them in the archive under
+// `key`, which names the model. Returns the number of passages embedded.
+pub async fn embed_archive<M: EmbeddingModel>(
+    archive: &Archive,
+    model: &M,
+    key: &str,
+    limit: usize,
+    progress: impl Fn(usize),
+) -> Result<usize, HNError> {
+    let mut embedded = 0;
+    embed_newest(archive, model, key, limit, |documents| {
+        archive.put_embeddings(key, &documents)?;
+        embedded += documents.len();
+        progress(embedded);
+        Ok(())
+    })
+    .await?;
+    Ok(embedded)
+}
+
+// Embed the newest `limit` archived items not yet embedded under `key` in
+// batches, passing each batch to `store`
+async fn embed_newest<M: EmbeddingModel>(
+    archive: &Archive,
+    model: &M,
+    key: &str,
+    limit: usize,
+    mut store: impl FnMut(Vec<DocumentEmbeddings>) -> Result<(), HNError>,
+) -> Result<(), HNError> {
+    let start = archive.text_window_start(limit)?;
+    let mut before = u32::MAX;
+    loop {
+        let items = archive.items_to_embed(key, start, before, EMBED_BATCH)?;
+        let Some(last) = items.last() else { break };
+        before = last.id();
+
+        let mut builder = EmbeddingsBuilder::new(model.clone());
+        let mut count = 0;
+        for item in &items {
+            let Some(passage) = Passage::from_archive(archive, item)? else { continue };
+            let text = passage.embedded_text();
+            builder = builder.json_document(&passage.id.to_string(), serde_json::to_value(&passage)?, vec![text]);
+            count += 1;
+        }
+        if count > 0 {
+            store(builder.build().await?)?;
+        }
+    }
+    Ok(())
+}
+
+// The vector store `semantic_search_hn` searches: rig's in-memory store, filled
+// from the embeddings persisted in the archive, either by `sync` or by
+// embedding what is missing on the spot. Cloning shares the store.
+#[derive(Clone)]
+pub struct SemanticIndex<M: EmbeddingModel> {
+    index: Arc<InMemoryVectorIndex<M>>,
+    len: usize,
+}
+
+impl<M: EmbeddingModel + 'static> SemanticIndex<M> {
+    // The newest `limit` embeddings persisted under `key`
+    pub async fn load(archive: &Archive, model: M, key: &str, limit: usize) -> Result<Self, HNError> {
+        Self::from_documents(model, archive.embeddings(key, limit)?).await
+    }
+
+    // The newest `limit` archived items, embedding and persisting under `key`
+    // only those no earlier run or sync has embedded
+    pub async fn build(archive: &Archive, model: M, key: &str, limit: usize) -> Result<Self, HNError> {
+        embed_archive(archive, &model, key, limit, |_| {}).await?;
+        Self::load(archive, model, key, limit).await
+    }
+
+    async fn from_documents(model: M, documents: Vec<DocumentEmbeddings>) -> Result<Self, HNError> {
+        let len = documents.len();
+        let mut store = InMemoryVectorStore::default();
+        store.add_documents(documents).await?;
+        Ok(Self { index: Arc::new(store.index(model)), len })
+    }
+
+    // Number of passages in the store
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The `n` passages closest in meaning to `query`, closest first
+    pub async fn search(&self, query: &str, n: usize) -> Result<Vec<(f64, Passage)>, HNError> {
+        Ok(self.nearest(query, n).await?)
+    }
+
+    pub(crate) async fn nearest(&self, query: &str, n: usize) -> Result<Vec<(f64, Passage)>, VectorStoreError> {
+        let index = self.index.clone();
+        let query = query.to_string();
+        let lookup = async move { index.top_n::<Passage>(&query, n).await };
+        let hits = run_as_task(lookup, |e| VectorStoreError::DatastoreError(Box::new(e))).await?;
+        Ok(hits.into_iter().map(|(score, _, passage)| (score, passage)).collect())
+    }
+}
+
+// Tool to find archived stories and comments by meaning rather than by their
+// exact words. With the full-text index the ranking is hybrid: the vector and
+// keyword rankings are merged by reciprocal rank fusion, so a story both find
+// comes first.
+pub struct SemanticSearchTool<M: EmbeddingModel> {
+    archive: Arc<Archive>,
+    vectors: SemanticIndex<M>,
+    index: Option<Arc<SearchIndex>>, // the keyword side of hybrid ranking
+    ranking: RankOptions,
+    limits: SearchLimits,
+    text_format: fn(&str) -> String, // what HN's HTML text fields are converted to
+}
+
+impl<M: EmbeddingModel + 'static> SemanticSearchTool<M> {
+    pub fn new(archive: Arc<Archive>, vectors: SemanticIndex<M>) -> Self {
+        Self {
+            archive,
+            vectors,
+            index: None,
+            ranking: RankOptions::default(),
+            limits: SearchLimits::default(),
+            text_format: html::to_text,
+        }
+    }
+
+    pub fn with_index(mut self, index: Arc<SearchIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn with_ranking(mut self, ranking: RankOptions) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: SearchLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Return text as e.g. `html::to_markdown` rather than plain text
+    pub fn with_text_format(mut self, convert: fn(&str) -> String) -> Self {
+        self.text_format = convert;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SemanticSearchArgs {
+    pub query: String,
+    pub max_results: Option<i32>,
+    pub hybrid: Option<bool>, // merge in keyword matches; on by default when there is an index
+}
+
+// A story's standing while the rankings are merged
+#[derive(Default)]
+struct Fused {
+    score: f64,
+    highlights: Vec<String>,
+}
+
+impl<M: EmbeddingModel + 'static> Tool for SemanticSearchTool<M> {
+    const NAME: &'static str = "semantic_search_hn";
+    type Error = HNError;
+    type Args = SemanticSearchArgs;
+    type Output = SearchOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let mut properties = json!({
+            "query": {
+                "type": "string",
+                "description": "What to look for, in plain words, e.g. 'memory-safe systems language'"
+            },
+            "max_results": {
+                "type": "integer",
+                "description": format!(
+                    "Maximum number of stories to return (default: {})",
+                    self.limits.default_max_results
+                )
+            }
+        });
+        if self.index.is_some() {
+            properties["hybrid"] = json!({
+                "type": "boolean",
+                "description": "Also rank by matching words and merge both rankings (default: true); \
+                    turn off to rank by meaning alone"
+            });
+        }
+        ToolDefinition {
+            name: "semantic_search_hn".to_string(),
+            description: "Find stories and comments in the local mirror of Hacker News by meaning rather than \
+                exact words, so a discussion is found even when it is phrased differently from the query. \
+                Returns the stories with the passages that matched."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": properties,
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let max_results = args.max_results.unwrap_or(self.limits.default_max_results).max(1) as usize;
+        let candidates = max_results * PASSAGES_PER_RESULT;
+        let mut order = Vec::new();
+        let mut fused: HashMap<u32, Fused> = HashMap::new();
+
+        // Passages by meaning, each story ranked by its closest passage. The store
+        // returns as many as asked for, so unrelated ones are dropped.
+        let mut rank = 0;
+        let passages = self.vectors.search(&args.query, candidates).await?;
+        for (_, passage) in passages.into_iter().filter(|(similarity, _)| *similarity > 0.0) {
+            let story = fused.entry(passage.story).or_insert_with(|| {
+                order.push(passage.story);
+                rank += 1;
+                Fused { score: 1.0 / (RRF_K + rank as f64), highlights: Vec::new() }
+            });
+            if passage.id != passage.story && story.highlights.len() < MAX_HIGHLIGHTS {
+                story.highlights.push(excerpt(&passage.text));
+            }
+        }
+
+        // Stories by their words, from the full-text index
+        let keywords = keyword_query(&args.query);
+        if let (Some(index), Some(query), true) = (&self.index, keywords, args.hybrid.unwrap_or(true)) {
+            let options = IndexSearch { limit: candidates, offset: 0, by_date: false };
+            for (rank, hit) in index.search(&query, &self.ranking, options)?.into_iter().enumerate() {
+                let story = fused.entry(hit.id).or_insert_with(|| {
+                    order.push(hit.id);
+                    Fused::default()
+                });
+                story.score += 1.0 / (RRF_K + (rank + 1) as f64);
+                // A passage both found is shown once, with the matching words marked
+                for highlight in hit.highlights {
+                    let plain = highlight.replace("**", "");
+                    match story.highlights.iter().position(|shown| *shown == plain) {
+                        Some(shown) => story.highlights[shown] = highlight,
+                        None if story.highlights.len() < MAX_HIGHLIGHTS => story.highlights.push(highlight),
+                        None => {}
+                    }
+                }
+            }
+        }
+
+        // Stable, so stories that tie keep the vector ranking's order
+        order.sort_by(|a, b| fused[b].score.total_cmp(&fused[a].score));
+        let mut output = SearchOutput::default();
+        for id in order {
+            if output.results.len() == max_results {
+                break;
+            }
+            let Some(item) = self.archive.item(id)? else { continue };
+            if item.is_deleted() || item.is_dead() {
+                continue;
+            }
+            let (comments, skipped) =
+                fetch_top_comments(&*self.archive, item.kids(), self.limits.comments_per_result, self.text_format)
+                    .await?;
+            output.skipped.extend(skipped);
+            let Fused { score, highlights } = fused.remove(&id).unwrap_or_default();
+            output.results.push(SearchResult { item: item.map_text(self.text_format), comments, score, highlights });
+        }
+
+        if output.results.is_empty() {
+            return Err(HNError::NoResults);
+        }
+        Ok(output)
+    }
+}
+
+// The query's words joined with OR, since a question in plain words rarely has
+// every word in one story. None if it has no words.
+fn keyword_query(text: &str) -> Option<Query> {
+    let terms = match Query::parse(text) {
+        Ok(query) => query.positive_terms(),
+        Err(_) => text.split_whitespace().map(str::to_string).collect(),
+    };
+    (!terms.is_empty()).then(|| Query::Or(terms.into_iter().map(Query::Term).collect()))
+}
+
+// The start of a passage, cut at a word boundary
+fn excerpt(text: &str) -> String {
+    const MAX_CHARS: usize = 240;
+
+    let mut excerpt = String::new();
+    for word in text.split_whitespace() {
+        if excerpt.chars().count() + word.chars().count() + 1 > MAX_CHARS {
+            excerpt.push('…');
+            break;
+        }
+        if !excerpt.is_empty() {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::story;
+
+    fn cosine(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    fn norm(vector: &[f64]) -> f64 {
+        vector.iter().map(|value| value * value).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn hash_embeddings_are_deterministic() {
+        let text = "Rust's borrow checker, explained";
+        assert_eq!(HashEmbedding::new(64).embed(text), HashEmbedding::new(64).embed(text));
+        assert_eq!(HashEmbedding::default().embed(text).len(), DEFAULT_HASH_DIMENSIONS);
+        assert_eq!(HashEmbedding::new(0).ndims(), 1);
+        // Pinned, so a change to tokenizing or hashing is noticed: it would
+        // leave every persisted vector incomparable with new ones
+        assert_eq!(fnv1a(b"rust"), 0xbffe_df1f_6f66_c727);
+    }
+
+    #[test]
+    fn hash_embeddings_are_unit_vectors() {
+        let embedding = HashEmbedding::default();
+        for text in ["rust", "The borrow checker, explained at length", "ünïcödé wörds"] {
+            assert!((norm(&embedding.embed(text)) - 1.0).abs() < 1e-9, "{}", text);
+        }
+    }
+
+    #[test]
+    fn text_without_words_embeds_as_zeros() {
+        let embedding = HashEmbedding::new(32);
+        assert_eq!(embedding.embed(""), vec![0.0; 32]);
+        assert_eq!(embedding.embed("  ++ -- !? "), vec![0.0; 32]);
+    }
+
+    #[test]
+    fn shared_trigrams_bring_words_together() {
+        let embedding = HashEmbedding::new(1024);
+        let rust = embedding.embed("rust");
+        assert!(cosine(&rust, &embedding.embed("rustlang")) > cosine(&rust, &embedding.embed("python")));
+        assert!(cosine(&rust, &embedding.embed("rustlang")) > 0.3);
+        assert!((cosine(&rust, &embedding.embed("Rust")) - 1.0).abs() < 1e-9);
+    }
+
+    // Story 1 is what the vectors find first but has no "rust" for the index to
+    // match; story 2 matches "rust" but is second by meaning, its title diluted
+    // with other words
+    async fn tool() -> SemanticSearchTool<HashEmbedding> {
+        let archive = Arc::new(Archive::in_memory().unwrap());
+        archive
+            .put_items(&[
+                story(1, "steveklabnik", "Rustacean", &[]),
+                story(
+                    2,
+                    "pg",
+                    "Rust news about compilers databases kernels browsers editors shells games servers \
+                     terminals networks and more",
+                    &[],
+                ),
+                story(3, "dang", "Growing tomatoes", &[]),
+            ])
+            .unwrap();
+        let index = Arc::new(SearchIndex::in_memory().unwrap());
+        index.rebuild(&archive).unwrap();
+        let vectors = SemanticIndex::build(&archive, HashEmbedding::new(4096), "hash/4096", 100).await.unwrap();
+        SemanticSearchTool::new(archive, vectors).with_index(index)
+    }
+
+    // HashEmbedding, counting the documents it is asked to embed
+    #[derive(Clone, Default)]
+    struct Counting {
+        embedded: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl EmbeddingModel for Counting {
+        const MAX_DOCUMENTS: usize = 1024;
+
+        fn ndims(&self) -> usize {
+            DEFAULT_HASH_DIMENSIONS
+        }
+
+        async fn embed_documents(
+            &self,
+            documents: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            let embeddings = HashEmbedding::default().embed_documents(documents).await?;
+            self.embedded.fetch_add(embeddings.len(), std::sync::atomic::Ordering::SeqCst);
+            Ok(embeddings)
+        }
+    }
+
+    #[tokio::test]
+    async fn building_embeds_only_what_the_archive_lacks() {
+        let archive = Archive::in_memory().unwrap();
+        archive.put_items(&[story(1, "pg", "Rust", &[]), story(2, "pg", "Go", &[])]).unwrap();
+        let model = Counting::default();
+        let embedded = || model.embedded.load(std::sync::atomic::Ordering::SeqCst);
+
+        assert_eq!(SemanticIndex::build(&archive, model.clone(), "counting", 100).await.unwrap().len(), 2);
+        assert_eq!(embedded(), 2);
+        assert_eq!(archive.embeddings("counting", 100).unwrap().len(), 2);
+
+        // The next start embeds only the new story, and a changed one again
+        archive.put_items(&[story(3, "pg", "Zig", &[]), story(1, "pg", "Rust 2024", &[])]).unwrap();
+        assert_eq!(SemanticIndex::build(&archive, model.clone(), "counting", 100).await.unwrap().len(), 3);
+        assert_eq!(embedded(), 4);
+        // Vectors from another model are not reused
+        SemanticIndex::build(&archive, model.clone(), "other", 100).await.unwrap();
+        assert_eq!(embedded(), 7);
+    }
+
+    fn ids(output: &SearchOutput) -> Vec<u32> {
+        output.results.iter().map(|result| result.item.id()).collect()
+    }
+
+    #[tokio::test]
+    async fn stories_both_rankings_find_come_first() {
+        let tool = tool().await;
+        let args = SemanticSearchArgs { query: "rust".to_string(), max_results: Some(2), hybrid: None };
+        let output = tool.call(args).await.unwrap();
+        assert_eq!(ids(&output), [2, 1]);
+        // Second by meaning and first by words
+        let expected = 1.0 / (RRF_K + 2.0) + 1.0 / (RRF_K + 1.0);
+        assert!((output.results[0].score - expected).abs() < 1e-12);
+        assert!((output.results[1].score - 1.0 / (RRF_K + 1.0)).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn without_hybrid_only_meaning_counts() {
+        let tool = tool().await;
+        let args = SemanticSearchArgs { query: "rust".to_string(), max_results: Some(2), hybrid: Some(false) };
+        let output = tool.call(args).await.unwrap();
+        assert_eq!(ids(&output), [1, 2]);
+        assert!((output.results[0].score - 1.0 / (RRF_K + 1.0)).abs() < 1e-12);
+    }
+}
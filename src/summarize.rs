@@ -0,0 +1,382 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use futures::{stream, StreamExt};
+use rig::{
+    completion::{Prompt, ToolDefinition},
+    tool::Tool,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::client::HnClient;
+use crate::error::{HNError, SkippedItem};
+use crate::html::{self, MapText};
+use crate::item::Item;
+use crate::lookup::ItemRef;
+use crate::task::run_as_task;
+use crate::tree::{CommentNode, CommentTree, TreeLimits};
+
+// Preamble for the model that writes the summaries; each prompt carries its own instructions
+pub const SUMMARIZER_PREAMBLE: &str = "You summarize Hacker News discussions accurately and concisely. \
+    Only report what the comments say, and cite comments by their ID in square brackets, like [8863].";
+
+// Chunks summarized at the same time
+const MAX_CONCURRENT_SUMMARIES: usize = 4;
+
+// Bounds on how much of a discussion gets summarized, and how it is split up
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SummaryLimits {
+    pub tree: TreeLimits,
+    pub chunk_tokens: usize, // rough size of the comments sent to the model in one request
+}
+
+impl Default for SummaryLimits {
+    fn default() -> Self {
+        Self { tree: TreeLimits { max_depth: 32, max_children: 1000, max_nodes: 3000 }, chunk_tokens: 6000 }
+    }
+}
+
+// Tool to summarize a whole discussion, however long, with map-reduce:
+// the thread is cut into chunks of whole subtrees, each chunk is summarized,
+// and the chunk summaries are merged into one
+pub struct SummarizeThreadTool<C, P> {
+    client: C,
+    model: Arc<P>,
+    limits: SummaryLimits,
+    text_format: fn(&str) -> String, // what the root's HTML text is converted to
+}
+
+impl<C: HnClient, P: Prompt + 'static> SummarizeThreadTool<C, P> {
+    pub fn new(client: C, model: P) -> Self {
+        Self { client, model: Arc::new(model), limits: SummaryLimits::default(), text_format: html::to_text }
+    }
+
+    pub fn with_limits(mut self, limits: SummaryLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Return the root's text as e.g. `html::to_markdown` rather than plain text
+    pub fn with_text_format(mut self, convert: fn(&str) -> String) -> Self {
+        self.text_format = convert;
+        self
+    }
+
+    pub async fn summarize(&self, id: u32) -> Result<ThreadSummary, HNError> {
+        let root = self.client.get_item(id).await?.ok_or_else(|| HNError::NotFound(format!("Item {}", id)))?;
+        let tree = CommentTree::fetch(&self.client, root, self.limits.tree).await?;
+        let title = describe_root(&tree.root);
+
+        let chunks = chunk_tree(&tree, self.limits.chunk_tokens);
+        let summary = match chunks.as_slice() {
+            [] => "There are no comments to summarize.".to_string(),
+            [chunk] => self.ask(final_prompt(&title, "comments", chunk)).await?,
+            _ => {
+                let prompts = chunks.iter().map(|chunk| map_prompt(&title, chunk)).collect();
+                let mut notes = self.ask_all(prompts).await?;
+
+                // Merge notes in batches until they all fit in one request
+                while notes.len() > 1 && estimate_tokens(&notes.concat()) > self.limits.chunk_tokens {
+                    let count = notes.len();
+                    let batches = pack(notes, self.limits.chunk_tokens);
+                    // Stop if merging can't shrink the notes any further
+                    if batches.len() == 1 || batches.len() == count {
+                        notes = batches.into_iter().flatten().collect();
+                        break;
+                    }
+                    let prompts = batches.iter().map(|batch| merge_prompt(&title, &batch.join("\n\n"))).collect();
+                    notes = self.ask_all(prompts).await?;
+                }
+                self.ask(final_prompt(&title, "notes on different parts of the discussion", &notes.join("\n\n")))
+                    .await?
+            }
+        };
+
+        let ids: HashSet<u32> = tree.iter().map(|node| node.comment.id).collect();
+        Ok(ThreadSummary {
+            cited: cited_ids(&summary).into_iter().filter(|id| ids.contains(id)).collect(),
+            comments: ids.len(),
+            chunks: chunks.len(),
+            truncated: tree.truncated,
+            skipped: tree.skipped,
+            root: tree.root.map_text(self.text_format),
+            summary,
+        })
+    }
+
+    async fn ask(&self, prompt: String) -> Result<String, HNError> {
+        let model = self.model.clone();
+        let request = async move { model.prompt(&prompt).await.map_err(|e| HNError::Model(e.to_string())) };
+        run_as_task(request, |e| HNError::Model(e.to_string())).await
+    }
+
+    async fn ask_all(&self, prompts: Vec<String>) -> Result<Vec<String>, HNError> {
+        stream::iter(prompts)
+            .map(|prompt| async move { self.ask(prompt).await })
+            .buffered(MAX_CONCURRENT_SUMMARIES)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+// The summary of a discussion and what went into it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ThreadSummary {
+    pub root: Item,
+    pub summary: String,
+    pub cited: Vec<u32>, // comment IDs the summary cites that are in the thread
+    pub comments: usize, // comments summarized
+    pub chunks: usize,   // model requests the comments were split into
+    pub truncated: bool, // true if the thread was too long to fetch completely
+    pub skipped: Vec<SkippedItem>,
+}
+
+// Rough token count; about four characters per token for English text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+// Split the discussion into chunks of roughly `max_tokens` each. Whole subtrees
+// stay together where they fit; a subtree that doesn't is split into its first
+// comment and the subtrees of its replies.
+pub fn chunk_tree(tree: &CommentTree, max_tokens: usize) -> Vec<String> {
+    let mut units = Vec::new();
+    for node in &tree.comments {
+        split_subtree(node, max_tokens, &mut units);
+    }
+    pack(units, max_tokens).into_iter().map(|chunk| chunk.concat()).collect()
+}
+
+fn split_subtree(node: &CommentNode, max_tokens: usize, units: &mut Vec<String>) {
+    let whole = render_subtree(node, max_tokens);
+    if estimate_tokens(&whole) <= max_tokens || node.children.is_empty() {
+        units.push(whole);
+        return;
+    }
+    units.push(render_comment(node, max_tokens));
+    for child in &node.children {
+        split_subtree(child, max_tokens, units);
+    }
+}
+
+// Group pieces of text, in order, so each group stays within `max_tokens`
+fn pack(pieces: Vec<String>, max_tokens: usize) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut used = 0;
+    for piece in pieces {
+        let tokens = estimate_tokens(&piece);
+        match groups.last_mut() {
+            Some(group) if used + tokens <= max_tokens => group.push(piece),
+            _ => {
+                groups.push(vec![piece]);
+                used = 0;
+            }
+        }
+        used += tokens;
+    }
+    groups
+}
+
+fn render_subtree(node: &CommentNode, max_tokens: usize) -> String {
+    let mut text = render_comment(node, max_tokens);
+    for child in &node.children {
+        text.push_str(&render_subtree(child, max_tokens));
+    }
+    text
+}
+
+// One comment as the model sees it: indented by depth, with its ID, author and
+// parent so replies can be told apart. Very long comments are cut to fit a chunk.
+fn render_comment(node: &CommentNode, max_tokens: usize) -> String {
+    let comment = &node.comment;
+    let text = match &comment.text {
+        _ if comment.deleted => "[deleted]".to_string(),
+        Some(text) => html::to_text(text).split_whitespace().take(max_tokens / 2).collect::<Vec<_>>().join(" "),
+        None => String::new(),
+    };
+    format!(
+        "{}[{}] {} (reply to {}): {}\n",
+        "  ".repeat(node.depth),
+        comment.id,
+        comment.by.as_deref().unwrap_or("[unknown]"),
+        comment.parent,
+        text
+    )
+}
+
+fn describe_root(root: &Item) -> String {
+    match root.title() {
+        Some(title) => format!("\"{}\" (item {})", title, root.id()),
+        None => format!("the replies to item {}", root.id()),
+    }
+}
+
+fn map_prompt(title: &str, comments: &str) -> String {
+    format!(
+        "Below is part of the Hacker News discussion of {}. Each comment starts with its ID in \
+         square brackets, then its author and the comment it replies to; replies are indented.\n\n\
+         Write concise notes on this part of the discussion: the main points made, who agrees or \
+         disagrees with whom, and any notable facts or links. Cite every point with the IDs of the \
+         comments that make it, like [8863].\n\n{}",
+        title, comments
+    )
+}
+
+fn merge_prompt(title: &str, notes: &str) -> String {
+    format!(
+        "Below are notes on different parts of the Hacker News discussion of {}. Merge them into \
+         one set of notes, combining points that appear more than once. Keep the comment IDs in \
+         square brackets that support each point.\n\n{}",
+        title, notes
+    )
+}
+
+fn final_prompt(title: &str, kind: &str, text: &str) -> String {
+    format!(
+        "Below are {} from the Hacker News discussion of {}. Summarize the discussion with these \
+         sections:\n\
+         Overview: two or three sentences on what the discussion is about.\n\
+         Key viewpoints: the main positions people take.\n\
+         Disagreements: where commenters disagree, and the arguments on each side.\n\
+         Cite the comment IDs that support each point in square brackets, like [8863], and only \
+         cite IDs that appear below.\n\n{}",
+        kind, title, text
+    )
+}
+
+// Every number written in square brackets, e.g. [8863] or [8863, 8864], in order of first appearance
+pub fn cited_ids(text: &str) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut ids = Vec::new();
+    for (start, _) in text.match_indices('[') {
+        let Some(end) = text[start..].find(']') else { break };
+        for part in text[start + 1..start + end].split(',') {
+            if let Ok(id) = part.trim().parse::<u32>() {
+                if seen.insert(id) {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+    ids
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SummarizeThreadArgs {
+    pub item: ItemRef,
+}
+
+impl<C: HnClient, P: Prompt + 'static> Tool for SummarizeThreadTool<C, P> {
+    const NAME: &'static str = "summarize_thread";
+    type Error = HNError;
+    type Args = SummarizeThreadArgs;
+    type Output = ThreadSummary;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "summarize_thread".to_string(),
+            description: "Summarize a whole Hacker News discussion, however long: its key viewpoints \
+                          and disagreements, citing comment IDs. Use this instead of reading a large \
+                          thread comment by comment."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "item": {
+                        "type": ["integer", "string"],
+                        "description": "ID or link of the story (or comment) whose replies to summarize"
+                    }
+                },
+                "required": ["item"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let id = args.item.resolve()?;
+        self.summarize(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story, MemoryClient};
+
+    fn words(count: usize) -> String {
+        vec!["word"; count].join(" ")
+    }
+
+    // Comment 2 has two replies, 3 and 4; comment 5 has none. Each comment
+    // renders to about 30 tokens.
+    async fn tree(text: &str) -> CommentTree {
+        let client = MemoryClient::new().with_items([
+            story(1, "pg", "Root", &[2, 5]),
+            comment(2, "a", 1, text, &[3, 4]),
+            comment(3, "b", 2, text, &[]),
+            comment(4, "c", 2, text, &[]),
+            comment(5, "d", 1, text, &[]),
+        ]);
+        let root = client.get_item(1).await.unwrap().unwrap();
+        CommentTree::fetch(&client, root, TreeLimits::default()).await.unwrap()
+    }
+
+    #[test]
+    fn estimates_four_characters_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("éééé"), 1);
+    }
+
+    #[test]
+    fn pack_keeps_order_within_the_budget() {
+        let pieces: Vec<String> =
+            ["aaaaaaaa", "bbbbbbbb", "cccccccc", "dddddddddddddddddddd", "e"].into_iter().map(str::to_string).collect();
+        let groups = pack(pieces.clone(), 4);
+        assert_eq!(groups, [vec!["aaaaaaaa", "bbbbbbbb"], vec!["cccccccc"], vec!["dddddddddddddddddddd"], vec!["e"]]);
+        assert_eq!(groups.concat(), pieces);
+        assert_eq!(pack(pieces.clone(), 1000), [pieces]);
+        assert!(pack(Vec::new(), 4).is_empty());
+    }
+
+    #[tokio::test]
+    async fn small_threads_are_one_chunk() {
+        let tree = tree(&words(20)).await;
+        let chunks = chunk_tree(&tree, 10_000);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].starts_with("[2] a (reply to 1): word"));
+        assert!(chunks[0].contains("\n  [3] b (reply to 2): word"));
+    }
+
+    #[tokio::test]
+    async fn subtrees_over_budget_are_split() {
+        let tree = tree(&words(20)).await;
+        let chunks = chunk_tree(&tree, 80);
+        // 2's subtree is too big, so 2 goes with 3, and 4 with 5
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|chunk| estimate_tokens(chunk) <= 80));
+        assert_eq!(cited_ids(&chunks[0]), [2, 3]);
+        assert_eq!(cited_ids(&chunks[1]), [4, 5]);
+    }
+
+    #[tokio::test]
+    async fn long_comments_are_cut_to_fit() {
+        let tree = tree(&words(1000)).await;
+        let chunks = chunk_tree(&tree, 100);
+        assert_eq!(chunks.len(), 4);
+        for chunk in &chunks {
+            assert_eq!(chunk.matches("word").count(), 50);
+        }
+    }
+
+    #[test]
+    fn cited_ids_skip_text_and_repeats() {
+        let text = "As [3] and [4, 5] say [see here], [3] and [5,6] agree; [ 7 ] [#8] [note 9] [";
+        assert_eq!(cited_ids(text), [3, 4, 5, 6, 7]);
+        assert!(cited_ids("no citations [at all]").is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::Archive;
+use crate::client::HnClient;
+use crate::error::{HNError, SkippedItem};
+use crate::index::SearchIndex;
+use crate::item::Item;
+use crate::search::DEFAULT_CONCURRENCY;
+
+// Keys in the archive's sync state: the newest and oldest item IDs archived so
+// far. Everything between them has been fetched.
+const TOP_KEY: &str = "top";
+const BOTTOM_KEY: &str = "bottom";
+
+// How far and how fast `sync` mirrors HN
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    pub horizon: Duration,        // how far back from now to backfill
+    pub concurrency: usize,       // max number of items fetched at once
+    pub batch_size: usize,        // items stored per transaction; progress is saved after each
+    pub max_items: Option<usize>, // stop after fetching this many new IDs, to split a long backfill
+    pub follow_updates: bool,     // refetch items and profiles listed in /updates.json
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            horizon: Duration::from_secs(30 * 24 * 60 * 60),
+            concurrency: DEFAULT_CONCURRENCY,
+            batch_size: 500,
+            max_items: None,
+            follow_updates: true,
+        }
+    }
+}
+
+// What a sync did. Also passed to the progress callback after each batch.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncReport {
+    pub new_items: usize,     // items newer than the previous sync
+    pub backfilled: usize,    // items older than the archive's oldest
+    pub updated_items: usize, // archived items refetched because HN listed them as changed
+    pub updated_users: usize,
+    pub indexed: usize,             // stories written to the search index
+    pub embedded: usize,            // passages embedded afterwards by `semantic::embed_archive`
+    pub retried: usize,             // items an earlier sync failed to fetch, fetched this time
+    pub missing: usize,             // IDs the API returned nothing for
+    pub skipped: Vec<SkippedItem>,  // failed even after retries; the next sync tries them again
+    pub skipped_users: Vec<String>, // profiles that failed, as "username: reason"
+    pub reached_horizon: bool,      // the backfill has gone back as far as the horizon, or to the first item
+}
+
+// Mirror HN into `archive`: first the items earlier syncs failed to fetch,
+// then the items added since the last sync, up to /maxitem.json, then older
+// items down to the horizon, then whatever /updates.json lists as changed.
+// Progress is saved after each batch, so an interrupted or `max_items`-limited
+// sync resumes where it stopped. With an `index`, the stories each batch
+// touches are re-indexed as it is stored.
+pub async fn sync<C: HnClient>(
+    client: &C,
+    archive: &Archive,
+    index: Option<&SearchIndex>,
+    options: &SyncOptions,
+    progress: impl Fn(&SyncReport),
+) -> Result<SyncReport, HNError> {
+    let mut report = SyncReport::default();
+    let store = |fetched: Fetched, report: &mut SyncReport| -> Result<Vec<Item>, HNError> {
+        archive.put_items(&fetched.items)?;
+        if let Some(index) = index {
+            report.indexed += index.update(archive, &fetched.items)?;
+        }
+        // The watermarks move past failed IDs, so they are kept to be tried again
+        archive.update_pending(&fetched.failed, &fetched.settled)?;
+        report.skipped.extend(fetched.failed);
+        Ok(fetched.items)
+    };
+
+    // A new index starts with everything archived so far
+    if let Some(index) = index.filter(|index| index.num_docs() == 0) {
+        report.indexed += index.rebuild(archive)?;
+    }
+    let mut budget = options.max_items.map_or(u32::MAX, |max| max.min(u32::MAX as usize) as u32);
+    let batch_size = options.batch_size.max(1) as u32;
+    let cutoff = chrono::Utc::now().timestamp() - options.horizon.as_secs() as i64;
+
+    let max_item = client.get_max_item().await?;
+    // A new archive starts at the newest item and fills in backwards
+    let top = match archive.state(TOP_KEY)? {
+        Some(top) => top,
+        None => {
+            archive.set_state(TOP_KEY, max_item)?;
+            archive.set_state(BOTTOM_KEY, max_item + 1)?;
+            max_item
+        }
+    };
+    let mut bottom = archive.state(BOTTOM_KEY)?.unwrap_or(top + 1);
+
+    let pending = archive.pending()?;
+    if !pending.is_empty() {
+        let items = store(fetch_items(client, pending, options.concurrency, &mut report).await?, &mut report)?;
+        report.retried += items.len();
+        progress(&report);
+    }
+
+    // Forward, oldest first, so the saved top never passes a gap
+    let mut next = top + 1;
+    while next <= max_item && budget > 0 {
+        let count = (max_item - next + 1).min(batch_size).min(budget);
+        let end = next + count - 1;
+        let items = store(fetch_items(client, next..=end, options.concurrency, &mut report).await?, &mut report)?;
+        archive.set_state(TOP_KEY, end)?;
+        report.new_items += items.len();
+        budget -= count;
+        next = end + 1;
+        progress(&report);
+    }
+
+    // Backward, newest first, until an item older than the horizon turns up
+    report.reached_horizon = archive.stats()?.oldest_time.is_some_and(|time| time < cutoff);
+    while bottom > 1 && budget > 0 && !report.reached_horizon {
+        let start = bottom - (bottom - 1).min(batch_size).min(budget);
+        let fetched = fetch_items(client, (start..bottom).rev(), options.concurrency, &mut report).await?;
+        let items = store(fetched, &mut report)?;
+        archive.set_state(BOTTOM_KEY, start)?;
+        report.backfilled += items.len();
+        report.reached_horizon = items.iter().any(|item| item.time() > 0 && item.time() < cutoff);
+        budget -= bottom - start;
+        bottom = start;
+        progress(&report);
+    }
+    // Item 1 is as far back as HN goes
+    report.reached_horizon |= bottom <= 1;
+
+    if options.follow_updates {
+        let updates = client.get_updates().await?;
+        let top = next - 1;
+        // Items outside the archived range are left to the forward pass or the backfill
+        let changed: Vec<u32> = updates.items.into_iter().filter(|id| (bottom..=top).contains(id)).collect();
+        let items = store(fetch_items(client, changed, options.concurrency, &mut report).await?, &mut report)?;
+        report.updated_items += items.len();
+
+        let users = stream::iter(updates.profiles)
+            .map(|username| async move { (client.get_user(&username).await, username) })
+            .buffered(options.concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        for (result, username) in users {
+            match result {
+                Ok(Some(user)) => {
+                    archive.put_user(&user)?;
+                    report.updated_users += 1;
+                }
+                Ok(None) => {}
+                Err(e) if e.is_recoverable() => report.skipped_users.push(format!("{}: {}", username, e)),
+                Err(e) => return Err(e),
+            }
+        }
+        progress(&report);
+    }
+
+    Ok(report)
+}
+
+// One batch of fetches: the items found, the IDs that need no further fetch,
+// found or not, and the ones that failed
+struct Fetched {
+    items: Vec<Item>,
+    settled: Vec<u32>,
+    failed: Vec<SkippedItem>,
+}
+
+// Fetch items concurrently, counting IDs that came back empty
+async fn fetch_items<C: HnClient>(
+    client: &C,
+    ids: impl IntoIterator<Item = u32>,
+    concurrency: usize,
+    report: &mut SyncReport,
+) -> Result<Fetched, HNError> {
+    let fetched = stream::iter(ids)
+        .map(|id| async move { (id, client.get_item(id).await) })
+        .buffered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut batch = Fetched { items: Vec::new(), settled: Vec::new(), failed: Vec::new() };
+    for (id, result) in fetched {
+        match result {
+            Ok(Some(item)) => batch.items.push(item),
+            Ok(None) => report.missing += 1,
+            Err(e) if e.is_recoverable() => {
+                batch.failed.push(SkippedItem { id, reason: e.to_string() });
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+        batch.settled.push(id);
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{story, MemoryClient};
+    use crate::item::User;
+
+    // `item` as if posted `days` ago
+    fn aged(mut item: Item, days: i64) -> Item {
+        let time = chrono::Utc::now().timestamp() - days * 24 * 60 * 60;
+        if let Item::Story(story) = &mut item {
+            story.time = time;
+        }
+        item
+    }
+
+    fn stories(ids: impl IntoIterator<Item = u32>, days: i64) -> Vec<Item> {
+        ids.into_iter().map(|id| aged(story(id, "pg", &format!("Story {}", id), &[]), days)).collect()
+    }
+
+    fn options(max_items: Option<usize>) -> SyncOptions {
+        SyncOptions { batch_size: 3, max_items, follow_updates: false, ..SyncOptions::default() }
+    }
+
+    async fn run(client: &MemoryClient, archive: &Archive, options: SyncOptions) -> SyncReport {
+        sync(client, archive, None, &options, |_| {}).await.unwrap()
+    }
+
+    fn archived(archive: &Archive, ids: impl IntoIterator<Item = u32>) -> Vec<u32> {
+        ids.into_iter().filter(|id| archive.item(*id).unwrap().is_some()).collect()
+    }
+
+    #[tokio::test]
+    async fn resumes_where_max_items_stopped() {
+        let client = MemoryClient::new().with_items(stories(1..=10, 1));
+        let archive = Archive::in_memory().unwrap();
+
+        let report = run(&client, &archive, options(Some(4))).await;
+        assert_eq!((report.new_items, report.backfilled, report.reached_horizon), (0, 4, false));
+        assert_eq!(archived(&archive, 1..=10), [7, 8, 9, 10]);
+
+        let report = run(&client, &archive, options(Some(4))).await;
+        assert_eq!((report.backfilled, report.reached_horizon), (4, false));
+        let report = run(&client, &archive, options(Some(4))).await;
+        assert_eq!((report.backfilled, report.reached_horizon), (2, true));
+        assert_eq!(archived(&archive, 1..=10).len(), 10);
+
+        // Later syncs only fetch what is new
+        let client = MemoryClient::new().with_items(stories(1..=12, 1));
+        let report = run(&client, &archive, options(Some(4))).await;
+        assert_eq!((report.new_items, report.backfilled, report.reached_horizon), (2, 0, true));
+        assert_eq!(client.requests(), 1 + 2);
+    }
+
+    #[tokio::test]
+    async fn backfill_stops_at_the_horizon() {
+        let client = MemoryClient::new().with_items(stories(1..=4, 40)).with_items(stories(5..=10, 1));
+        let archive = Archive::in_memory().unwrap();
+
+        let report = run(&client, &archive, options(None)).await;
+        // The batch that crosses the horizon is the last one
+        assert_eq!((report.backfilled, report.reached_horizon), (9, true));
+        assert_eq!(archived(&archive, 1..=10), (2..=10).collect::<Vec<_>>());
+
+        let client = MemoryClient::new().with_items(stories(1..=4, 40)).with_items(stories(5..=10, 1));
+        let report = run(&client, &archive, options(None)).await;
+        assert_eq!((report.backfilled, report.reached_horizon), (0, true));
+        assert_eq!(client.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn updates_are_limited_to_the_archived_range() {
+        let archive = Archive::in_memory().unwrap();
+        run(&MemoryClient::new().with_items(stories(1..=10, 1)), &archive, options(Some(6))).await;
+        assert_eq!(archived(&archive, 1..=10), [5, 6, 7, 8, 9, 10]);
+
+        let user = User { id: "pg".to_string(), created: 0, karma: 1, about: None, submitted: None };
+        let client = MemoryClient::new()
+            .with_items(stories(1..=12, 1))
+            .with_item(aged(story(7, "pg", "Edited", &[]), 1))
+            .with_updates(vec![3, 7, 12], vec!["pg".to_string()])
+            .with_user(user);
+        let report = run(&client, &archive, SyncOptions { follow_updates: true, ..options(Some(0)) }).await;
+
+        assert_eq!((report.new_items, report.updated_items, report.updated_users), (0, 1, 1));
+        assert_eq!(archive.item(7).unwrap().unwrap().title(), Some("Edited"));
+        assert!(archived(&archive, [3, 12]).is_empty());
+        assert!(archive.user("pg").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_items_are_tried_again_at_the_next_sync() {
+        let archive = Archive::in_memory().unwrap();
+        let client = MemoryClient::new().with_items(stories(1..=6, 1)).with_failure(4);
+        let report = run(&client, &archive, options(None)).await;
+        assert_eq!(report.skipped.iter().map(|skipped| skipped.id).collect::<Vec<_>>(), [4]);
+        assert_eq!(archive.pending().unwrap(), [4]);
+
+        // Still failing: kept for the sync after
+        let report = run(&client, &archive, options(None)).await;
+        assert_eq!((report.retried, report.skipped.len()), (0, 1));
+        assert_eq!(archive.pending().unwrap(), [4]);
+
+        let client = MemoryClient::new().with_items(stories(1..=6, 1));
+        let report = run(&client, &archive, options(None)).await;
+        assert_eq!((report.retried, report.skipped.len()), (1, 0));
+        assert!(archive.pending().unwrap().is_empty());
+        assert_eq!(archived(&archive, 1..=6).len(), 6);
+        assert_eq!(client.requests(), 1 + 1);
+    }
+}
@@ -0,0 +1,74 @@
+use std::future::Future;
+
+use tokio::task::{JoinError, JoinHandle};
+
+// rig wants tool futures to be Sync, but the futures of its models and vector
+// stores are only Send. A tool awaiting one runs it as a task of its own and
+// awaits that instead; `failed` turns a task that panicked into an error. The
+// task starts right away, so `future` is never part of the caller's future,
+// and it is aborted if the caller's future is dropped first, e.g. on Ctrl-C
+// or when a deadline passes, so no model call goes on running unseen.
+pub(crate) fn run_as_task<T, E>(
+    future: impl Future<Output = Result<T, E>> + Send + 'static,
+    failed: impl FnOnce(JoinError) -> E,
+) -> impl Future<Output = Result<T, E>>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    let mut task = AbortOnDrop(tokio::spawn(future));
+    async move { (&mut task.0).await.map_err(failed)? }
+}
+
+// A task that is aborted when its handle is dropped; aborting a finished task does nothing
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    fn failed(error: JoinError) -> String {
+        format!("task failed: {}", error.is_panic())
+    }
+
+    #[tokio::test]
+    async fn returns_what_the_task_returns() {
+        assert_eq!(run_as_task(async { Ok::<_, String>(1) }, failed).await, Ok(1));
+        assert_eq!(run_as_task(async { Err::<i32, _>("no".to_string()) }, failed).await, Err("no".to_string()));
+    }
+
+    #[tokio::test]
+    async fn panics_become_errors() {
+        let task = run_as_task(async { panic!("model client bug") }, failed);
+        assert_eq!(task.await, Err::<(), _>("task failed: true".to_string()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropping_the_future_aborts_the_task() {
+        let (alive, dropped) = oneshot::channel::<()>();
+        let task = run_as_task(
+            async move {
+                let _alive = alive;
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+                Ok::<_, String>(())
+            },
+            failed,
+        );
+
+        // A deadline passes while the task is still running
+        assert!(tokio::time::timeout(Duration::from_secs(1), task).await.is_err());
+        // The sender is only dropped early if the task was aborted
+        let wait = tokio::time::timeout(Duration::from_secs(10), dropped).await;
+        assert!(matches!(wait, Ok(Err(_))), "the task is still running");
+    }
+}
@@ -0,0 +1,247 @@
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::client::HnClient;
+use crate::error::{HNError, SkippedItem};
+use crate::html::MapText;
+use crate::item::{Comment, Item};
+use crate::search::DEFAULT_CONCURRENCY;
+
+// Bounds on how much of a discussion `CommentTree::fetch` retrieves
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct TreeLimits {
+    pub max_depth: usize,    // levels of replies below the root, 1 = top-level comments only
+    pub max_children: usize, // replies followed per comment (and per root)
+    pub max_nodes: usize,    // comments fetched in total
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        Self { max_depth: 8, max_children: 50, max_nodes: 500 }
+    }
+}
+
+// A comment and the replies that were fetched for it, in HN's ranking order
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommentNode {
+    pub comment: Comment,
+    pub depth: usize, // 0 for top-level comments
+    pub children: Vec<CommentNode>,
+}
+
+// The discussion under a story, poll or comment
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommentTree {
+    pub root: Item,
+    pub comments: Vec<CommentNode>,
+    pub truncated: bool,           // true if any limit cut off part of the discussion
+    pub skipped: Vec<SkippedItem>, // comments that could not be fetched; their replies are missing too
+}
+
+impl MapText for CommentNode {
+    fn map_text(self, convert: fn(&str) -> String) -> Self {
+        CommentNode { comment: self.comment.map_text(convert), children: self.children.map_text(convert), ..self }
+    }
+}
+
+impl MapText for CommentTree {
+    fn map_text(self, convert: fn(&str) -> String) -> Self {
+        CommentTree { root: self.root.map_text(convert), comments: self.comments.map_text(convert), ..self }
+    }
+}
+
+// A fetched comment waiting to be assembled into the tree
+struct Fetched {
+    parent: Option<usize>, // index into the arena, `None` for top-level comments
+    comment: Comment,
+}
+
+impl CommentTree {
+    // Fetch the discussion under `root` breadth first, one level at a time.
+    // Each level is fetched concurrently; ordering follows each item's `kids`.
+    pub async fn fetch<C: HnClient>(client: &C, root: Item, limits: TreeLimits) -> Result<Self, HNError> {
+        let mut arena: Vec<Fetched> = Vec::new();
+        let mut truncated = false;
+        let mut skipped = Vec::new();
+
+        let mut frontier: Vec<(Option<usize>, u32)> =
+            take_children(root.kids(), limits.max_children, &mut truncated).map(|id| (None, id)).collect();
+
+        for depth in 0..limits.max_depth {
+            if frontier.is_empty() {
+                break;
+            }
+
+            let remaining = limits.max_nodes.saturating_sub(arena.len());
+            if frontier.len() > remaining {
+                frontier.truncate(remaining);
+                truncated = true;
+            }
+
+            let mut level = stream::iter(frontier)
+                .map(|(parent, id)| async move { (parent, id, client.get_item(id).await) })
+                .buffered(DEFAULT_CONCURRENCY);
+
+            let mut next = Vec::new();
+            while let Some((parent, id, result)) = level.next().await {
+                let comment = match result {
+                    Ok(Some(Item::Comment(comment))) => comment,
+                    Ok(Some(other)) => {
+                        let reason = format!("item is a {}, not a comment", other.kind());
+                        skipped.push(SkippedItem { id, reason });
+                        continue;
+                    }
+                    Ok(None) => continue,
+                    Err(e) if e.is_recoverable() => {
+                        skipped.push(SkippedItem { id, reason: e.to_string() });
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                let index = arena.len();
+                if depth + 1 < limits.max_depth {
+                    let kids = comment.kids.as_deref().unwrap_or_default();
+                    next.extend(take_children(kids, limits.max_children, &mut truncated).map(|id| (Some(index), id)));
+                } else if comment.kids.as_ref().is_some_and(|kids| !kids.is_empty()) {
+                    truncated = true;
+                }
+                arena.push(Fetched { parent, comment });
+            }
+
+            frontier = next;
+        }
+
+        if !frontier.is_empty() {
+            truncated = true;
+        }
+
+        Ok(Self { root, comments: assemble(arena), truncated, skipped })
+    }
+
+    // Number of comments in the tree
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    // All comments in depth-first order, i.e. the order they appear on the HN page
+    pub fn iter(&self) -> impl Iterator<Item = &CommentNode> {
+        let mut stack: Vec<&CommentNode> = self.comments.iter().rev().collect();
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+}
+
+fn take_children<'a>(kids: &'a [u32], max_children: usize, truncated: &mut bool) -> impl Iterator<Item = u32> + 'a {
+    if kids.len() > max_children {
+        *truncated = true;
+    }
+    kids.iter().take(max_children).copied()
+}
+
+// Turn the flat, breadth-first arena into nested nodes. Children were pushed in
+// the order of their parent's `kids`, so sibling order is preserved.
+fn assemble(arena: Vec<Fetched>) -> Vec<CommentNode> {
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); arena.len()];
+    let mut top_level = Vec::new();
+    for (index, fetched) in arena.iter().enumerate() {
+        match fetched.parent {
+            Some(parent) => children[parent].push(index),
+            None => top_level.push(index),
+        }
+    }
+
+    let mut comments: Vec<Option<Comment>> = arena.into_iter().map(|f| Some(f.comment)).collect();
+
+    fn build(index: usize, depth: usize, children: &[Vec<usize>], comments: &mut [Option<Comment>]) -> CommentNode {
+        let comment = comments[index].take().expect("each comment is assembled once");
+        let children = children[index].iter().map(|&child| build(child, depth + 1, children, comments)).collect();
+        CommentNode { comment, depth, children }
+    }
+
+    top_level.into_iter().map(|index| build(index, 0, &children, &mut comments)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::memory::{comment, story, MemoryClient};
+
+    // 1 ─┬─ 2 ─┬─ 5 ── 7
+    //    │     └─ 6
+    //    ├─ 3
+    //    ├─ 4 (fails to load)
+    //    └─ 8 (a story, not a comment)
+    fn client() -> MemoryClient {
+        MemoryClient::new()
+            .with_items([
+                story(1, "pg", "Root", &[2, 3, 4, 8]),
+                comment(2, "a", 1, "two", &[5, 6]),
+                comment(3, "b", 1, "three", &[]),
+                comment(5, "c", 2, "five", &[7]),
+                comment(6, "d", 2, "six", &[]),
+                comment(7, "e", 5, "seven", &[]),
+                story(8, "f", "Not a comment", &[]),
+            ])
+            .with_failure(4)
+    }
+
+    async fn fetch(limits: TreeLimits) -> CommentTree {
+        let client = client();
+        let root = client.get_item(1).await.unwrap().unwrap();
+        CommentTree::fetch(&client, root, limits).await.unwrap()
+    }
+
+    fn ids(tree: &CommentTree) -> Vec<(u32, usize)> {
+        tree.iter().map(|node| (node.comment.id, node.depth)).collect()
+    }
+
+    #[tokio::test]
+    async fn fetches_the_whole_discussion_in_page_order() {
+        let tree = fetch(TreeLimits::default()).await;
+        assert_eq!(ids(&tree), [(2, 0), (5, 1), (7, 2), (6, 1), (3, 0)]);
+        assert_eq!(tree.len(), 5);
+        assert!(!tree.truncated);
+    }
+
+    #[tokio::test]
+    async fn skips_failed_fetches_and_items_that_are_not_comments() {
+        let tree = fetch(TreeLimits::default()).await;
+        let skipped: Vec<u32> = tree.skipped.iter().map(|skipped| skipped.id).collect();
+        assert_eq!(skipped, [4, 8]);
+        assert!(tree.skipped[1].reason.contains("story"));
+    }
+
+    #[tokio::test]
+    async fn max_depth_stops_below_the_last_level() {
+        let tree = fetch(TreeLimits { max_depth: 1, ..TreeLimits::default() }).await;
+        assert_eq!(ids(&tree), [(2, 0), (3, 0)]);
+        assert!(tree.truncated);
+
+        let tree = fetch(TreeLimits { max_depth: 3, ..TreeLimits::default() }).await;
+        assert_eq!(tree.len(), 5);
+        assert!(!tree.truncated);
+    }
+
+    #[tokio::test]
+    async fn max_children_follows_only_the_first_replies() {
+        let tree = fetch(TreeLimits { max_children: 1, ..TreeLimits::default() }).await;
+        assert_eq!(ids(&tree), [(2, 0), (5, 1), (7, 2)]);
+        assert!(tree.truncated);
+    }
+
+    #[tokio::test]
+    async fn max_nodes_caps_the_comments_fetched() {
+        let tree = fetch(TreeLimits { max_nodes: 3, ..TreeLimits::default() }).await;
+        // 8 is cut from the first level; 2 and 3 leave room for one reply
+        assert_eq!(ids(&tree), [(2, 0), (5, 1), (3, 0)]);
+        assert!(tree.truncated);
+    }
+}